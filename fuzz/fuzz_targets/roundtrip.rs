@@ -0,0 +1,9 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use libfuzzer_sys::fuzz_target;
+
+// Renders generated values, parses them back, and panics on any difference.
+fuzz_target!(|data: &[u8]| {
+    let _ = pit_core::fuzz::check_roundtrips(&mut Unstructured::new(data));
+});
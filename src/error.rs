@@ -0,0 +1,144 @@
+use core::fmt::{self, Display, Formatter};
+
+use nom::error::{ErrorKind, ParseError};
+
+/// Error produced by the PIT parsers.
+///
+/// Every variant records `remaining`, the length of the input that was left when the
+/// error was raised; [`PitError::offset_in`] turns it into a byte offset.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
+pub enum PitError {
+    /// A fixed token such as `->` was expected.
+    ExpectedToken {
+        expected: &'static str,
+        remaining: usize,
+    },
+    /// A single delimiter character such as `[` or `)` was expected.
+    ExpectedChar { expected: char, remaining: usize },
+    /// A hex-encoded id did not have the expected number of hex digits.
+    BadHexLength { expected: usize, remaining: usize },
+    /// An argument did not start with a known type token.
+    UnknownArgType { remaining: usize },
+    /// Input remained after a complete value was parsed.
+    TrailingInput { remaining: usize },
+    /// A param or return index in an info line that is not a decimal `usize`. The
+    /// offending token is the `len` bytes at the error offset; see [`PitError::token_in`].
+    InvalidIndex { len: usize, remaining: usize },
+    /// An all-zero resource id, rejected by [`crate::parse_resty_strict`] and under
+    /// [`crate::ParseOptions::strict_ids`].
+    ZeroResourceId { remaining: usize },
+    /// Function arguments nested deeper than [`crate::MAX_FUNC_DEPTH`].
+    TooDeep { remaining: usize },
+    /// Any other failure reported by a nom combinator.
+    Nom { kind: ErrorKind, remaining: usize },
+}
+
+impl PitError {
+    /// Returns the length of the input that was left when the error was raised.
+    pub fn remaining(&self) -> usize {
+        match *self {
+            PitError::ExpectedToken { remaining, .. }
+            | PitError::ExpectedChar { remaining, .. }
+            | PitError::BadHexLength { remaining, .. }
+            | PitError::UnknownArgType { remaining }
+            | PitError::TrailingInput { remaining }
+            | PitError::InvalidIndex { remaining, .. }
+            | PitError::ZeroResourceId { remaining }
+            | PitError::TooDeep { remaining }
+            | PitError::Nom { remaining, .. } => remaining,
+        }
+    }
+
+    /// Returns the byte offset of the error within the original `input`.
+    pub fn offset_in(&self, input: &str) -> usize {
+        input.len().saturating_sub(self.remaining())
+    }
+
+    /// Returns the offending token within the original `input`, for errors that record
+    /// one ([`PitError::InvalidIndex`]).
+    pub fn token_in<'a>(&self, input: &'a str) -> Option<&'a str> {
+        match *self {
+            PitError::InvalidIndex { len, .. } => {
+                let start = self.offset_in(input);
+                input.get(start..start + len)
+            }
+            _ => None,
+        }
+    }
+
+    /// Converts the error to nom's default error type, for the parsers that keep it.
+    ///
+    /// `input` is the input the failing parser was given. Variants without a nom
+    /// counterpart map to the closest [`ErrorKind`].
+    pub fn into_nom_error(self, input: &str) -> nom::error::Error<&str> {
+        let kind = match self {
+            PitError::ExpectedToken { .. } => ErrorKind::Tag,
+            PitError::ExpectedChar { .. } => ErrorKind::Char,
+            PitError::BadHexLength { .. } => ErrorKind::TakeWhileMN,
+            PitError::UnknownArgType { .. } => ErrorKind::Alt,
+            PitError::TrailingInput { .. } => ErrorKind::Eof,
+            PitError::InvalidIndex { .. } => ErrorKind::Digit,
+            PitError::ZeroResourceId { .. } => ErrorKind::Verify,
+            PitError::TooDeep { .. } => ErrorKind::TooLarge,
+            PitError::Nom { kind, .. } => kind,
+        };
+        nom::error::Error::new(input.get(self.offset_in(input)..).unwrap_or(""), kind)
+    }
+
+    /// Extracts the error from a nom result error.
+    ///
+    /// `Incomplete` cannot occur with the complete parsers used by this crate; it is
+    /// reported as an end-of-input error.
+    pub fn from_nom(e: nom::Err<PitError>) -> PitError {
+        match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e,
+            nom::Err::Incomplete(_) => PitError::Nom {
+                kind: ErrorKind::Eof,
+                remaining: 0,
+            },
+        }
+    }
+}
+
+impl<'a> ParseError<&'a str> for PitError {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        PitError::Nom {
+            kind,
+            remaining: input.len(),
+        }
+    }
+
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn from_char(input: &'a str, c: char) -> Self {
+        PitError::ExpectedChar {
+            expected: c,
+            remaining: input.len(),
+        }
+    }
+}
+
+impl Display for PitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PitError::ExpectedToken { expected, .. } => write!(f, "expected `{expected}`"),
+            PitError::ExpectedChar { expected, .. } => write!(f, "expected `{expected}`"),
+            PitError::BadHexLength { expected, .. } => {
+                write!(f, "expected an id of {expected} hex digits")
+            }
+            PitError::UnknownArgType { .. } => write!(f, "unknown argument type"),
+            PitError::TrailingInput { .. } => write!(f, "unexpected trailing input"),
+            PitError::InvalidIndex { .. } => write!(f, "invalid index: expected a decimal number"),
+            PitError::ZeroResourceId { .. } => {
+                write!(f, "all-zero resource id: omit the id for an untyped resource")
+            }
+            PitError::TooDeep { .. } => write!(f, "function arguments nested too deeply"),
+            PitError::Nom { kind, .. } => write!(f, "parse error: {}", kind.description()),
+        }
+    }
+}
+
+impl core::error::Error for PitError {}
@@ -0,0 +1,284 @@
+//! Random generators for round-trip testing (`testing` feature).
+//!
+//! The [`Arbitrary`] impls build values in canonical form, the way the parsers return them:
+//! annotation lists are sorted by name, method and slot names are identifiers, and info
+//! entries are never empty. Attribute names and values include characters that need
+//! escaping inside interfaces that declare `escFmtVer`; elsewhere names leave out `=` and
+//! `]` and values keep their brackets balanced, as they must without escapes. So rendering
+//! a generated value and parsing the text back must give an equal value;
+//! [`check_roundtrips`] asserts that for every type with a textual form.
+use alloc::{borrow::ToOwned, boxed::Box, collections::BTreeMap, format, string::String, vec::Vec};
+use core::fmt::{Debug, Display};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{
+    Arg, ArgTy, Arity, Attr, Interface, PitError, ResTy, Sig,
+    info::{Info, InfoEntry, MethEntry, ParamEntry},
+};
+
+/// Characters of an [`crate::ident`].
+const IDENT: &[u8] = b"abcnxyzABR019_$.";
+/// Characters of attribute names, including those that need escaping.
+const NAME: &[char] = &['a', 'b', 'n', '0', '.', '_', '=', '[', ']', '\\', ' ', '\n'];
+/// Characters of attribute values, including brackets, escapes, and line breaks.
+const VALUE: &[char] = &[
+    'a', 'b', ' ', '[', ']', '\\', '=', '\n', '\t', ';', '(', 'é', '\u{1f980}',
+];
+/// Version attributes that change the rendering, set with a low value.
+const VERSIONS: &[&str] = &["attrFmtVer", "escFmtVer", "primFmtVer", "ridFmtVer"];
+
+fn ident(u: &mut Unstructured) -> Result<String> {
+    let len = u.int_in_range(1..=6)?;
+    (0..len)
+        .map(|_| Ok(char::from(*u.choose(IDENT)?)))
+        .collect()
+}
+
+/// Without `esc`, brackets are balanced and the value does not start with whitespace.
+fn value(u: &mut Unstructured, esc: bool) -> Result<String> {
+    let len = u.int_in_range(0..=8)?;
+    let v: String = (0..len).map(|_| u.choose(VALUE).copied()).collect::<Result<_>>()?;
+    if esc {
+        return Ok(v);
+    }
+    let v: String = v.chars().filter(|c| !matches!(c, '[' | ']')).collect();
+    let v = v.trim_start();
+    Ok(if u.arbitrary()? {
+        let mid = v.char_indices().nth(v.chars().count() / 2).map_or(v.len(), |(i, _)| i);
+        format!("{}[{}]", &v[..mid], &v[mid..])
+    } else {
+        v.to_owned()
+    })
+}
+
+/// Without `esc`, the name has no `=` or `]` and does not start with whitespace.
+fn attr(u: &mut Unstructured, esc: bool) -> Result<Attr> {
+    let len = u.int_in_range(1..=6)?;
+    let name: String = (0..len).map(|_| u.choose(NAME).copied()).collect::<Result<_>>()?;
+    let name = if esc {
+        name
+    } else {
+        let name: String = name.chars().filter(|c| !matches!(c, '=' | ']')).collect();
+        name.trim_start().to_owned()
+    };
+    Ok(Attr {
+        name,
+        value: value(u, esc)?,
+    })
+}
+
+/// Sorted by name, as the parsers leave annotation lists.
+fn attrs(u: &mut Unstructured, max: usize, esc: bool) -> Result<Vec<Attr>> {
+    let len = u.int_in_range(0..=max)?;
+    let mut v = (0..len).map(|_| attr(u, esc)).collect::<Result<Vec<_>>>()?;
+    v.sort_by_key(|a| a.name.clone());
+    Ok(v)
+}
+
+fn sig(u: &mut Unstructured, depth: usize, esc: bool) -> Result<Sig> {
+    let args = |u: &mut Unstructured| {
+        let len = u.int_in_range(0..=3)?;
+        (0..len).map(|_| arg(u, depth, esc)).collect::<Result<Vec<_>>>()
+    };
+    Ok(Sig {
+        params: args(u)?,
+        rets: args(u)?,
+        ann: attrs(u, 2, esc)?,
+    })
+}
+
+fn arg(u: &mut Unstructured, depth: usize, esc: bool) -> Result<Arg> {
+    let ty = match u.int_in_range(0..=10)? {
+        0 => ArgTy::I32,
+        1 => ArgTy::I64,
+        2 => ArgTy::F32,
+        3 => ArgTy::F64,
+        4 => ArgTy::V128,
+        5 => ArgTy::I8,
+        6 => ArgTy::I16,
+        7 => ArgTy::U8,
+        8 => ArgTy::U16,
+        9 if depth > 0 => ArgTy::Func(Box::new(sig(u, depth - 1, esc)?)),
+        _ => ArgTy::Resource {
+            ty: ResTy::arbitrary(u)?,
+            nullable: u.arbitrary()?,
+            take: u.arbitrary()?,
+        },
+    };
+    Ok(Arg {
+        ty,
+        ann: attrs(u, 2, esc)?,
+    })
+}
+
+impl<'a> Arbitrary<'a> for Attr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        attr(u, false)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ResTy {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=3)? {
+            0 => ResTy::None,
+            1 => ResTy::This,
+            2 => ResTy::Named(ident(u)?),
+            _ => ResTy::Of(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Arg {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arg(u, 2, false)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Sig {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        sig(u, 2, false)
+    }
+}
+
+fn arity(u: &mut Unstructured, depth: usize) -> Result<Arity> {
+    let len = if depth == 0 { 0 } else { u.int_in_range(0..=3)? };
+    Ok(Arity {
+        to_fill: (0..len)
+            .map(|_| Ok((ident(u)?, arity(u, depth - 1)?)))
+            .collect::<Result<_>>()?,
+    })
+}
+
+impl<'a> Arbitrary<'a> for Arity {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arity(u, 3)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Interface {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut versions = Vec::new();
+        for v in VERSIONS {
+            if u.ratio(1, 3)? {
+                versions.push(Attr {
+                    name: (*v).to_owned(),
+                    value: format!("{}", u.int_in_range(0..=1u8)?),
+                });
+            }
+        }
+        let esc = versions.iter().any(|a| a.name == "escFmtVer");
+        let mut ann = attrs(u, 3, esc)?;
+        ann.extend(versions);
+        ann.sort_by_key(|a| a.name.clone());
+        let len = u.int_in_range(0..=4)?;
+        let methods = (0..len)
+            .map(|_| Ok((ident(u)?, sig(u, 2, esc)?)))
+            .collect::<Result<_>>()?;
+        Ok(Interface { methods, ann })
+    }
+}
+
+/// Non-empty, as empty entries render as nothing.
+impl<'a> Arbitrary<'a> for ParamEntry {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut attrs = attrs(u, 2, false)?;
+        if attrs.is_empty() {
+            attrs.push(Attr::arbitrary(u)?);
+        }
+        Ok(ParamEntry { attrs })
+    }
+}
+
+/// Non-empty, as empty entries render as nothing.
+impl<'a> Arbitrary<'a> for MethEntry {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let entries = |u: &mut Unstructured<'a>| {
+            let len = u.int_in_range(0..=2)?;
+            (0..len)
+                .map(|_| Ok((u.int_in_range(0..=9)?, ParamEntry::arbitrary(u)?)))
+                .collect::<Result<BTreeMap<_, _>>>()
+        };
+        let mut m = MethEntry {
+            attrs: attrs(u, 2, false)?,
+            params: entries(u)?,
+            returns: entries(u)?,
+        };
+        if m.attrs.is_empty() && m.params.is_empty() && m.returns.is_empty() {
+            m.attrs.push(Attr::arbitrary(u)?);
+        }
+        Ok(m)
+    }
+}
+
+impl<'a> Arbitrary<'a> for InfoEntry {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=3)?;
+        Ok(InfoEntry {
+            attrs: attrs(u, 2, false)?,
+            methods: (0..len)
+                .map(|_| Ok((ident(u)?, MethEntry::arbitrary(u)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Info {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=2)?;
+        Ok(Info {
+            interfaces: (0..len)
+                .map(|_| Ok((u.arbitrary()?, InfoEntry::arbitrary(u)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// Renders `x`, parses the text with `parse`, and panics unless the result equals `x`.
+fn check<T: Display + Debug + PartialEq>(
+    x: &T,
+    parse: impl Fn(&str) -> core::result::Result<T, PitError>,
+) {
+    let text = format!("{x}");
+    match parse(&text) {
+        Ok(y) => assert_eq!(&y, x, "round trip of {text:?}"),
+        Err(e) => panic!("{e} at {} in {text:?}", e.offset_in(&text)),
+    }
+}
+
+/// Generates one value of each textual type from `u` and checks that it round-trips.
+///
+/// Panics on the first value that renders to text parsing to something else; returns an
+/// error only if `u` runs out of data. Suitable as the body of a fuzz target.
+pub fn check_roundtrips(u: &mut Unstructured) -> Result<()> {
+    check(&Attr::arbitrary(u)?, str::parse);
+    check(&ResTy::arbitrary(u)?, str::parse);
+    check(&Arg::arbitrary(u)?, str::parse);
+    check(&Sig::arbitrary(u)?, str::parse);
+    check(&Arity::arbitrary(u)?, str::parse);
+    check(&Interface::arbitrary(u)?, str::parse);
+    check(&Info::arbitrary(u)?, |s| crate::parse_all(s, Info::parse_typed));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrips() {
+        // A fixed xorshift stream, so failures reproduce.
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut data = Vec::new();
+        for _ in 0..2000 {
+            data.clear();
+            for _ in 0..256 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                data.extend_from_slice(&state.to_le_bytes());
+            }
+            check_roundtrips(&mut Unstructured::new(&data)).unwrap();
+        }
+    }
+}
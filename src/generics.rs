@@ -1,7 +1,8 @@
 use core::{fmt::Formatter, num};
 
 use nom::{
-    multi::{count, many1},
+    character::complete::digit1,
+    multi::count,
     number,
 };
 
@@ -13,7 +14,7 @@ pub const GENERIC_KEY: &'static str = "generics.modern";
 /// Trait for mangling and demangling generic types.
 pub trait Mangle {
     /// Demangles a string into a generic type.
-    fn demangle(a: &str) -> IResult<&str, Self>
+    fn demangle(a: &str) -> IResult<&str, Self, PitError>
     where
         Self: Sized;
     /// Mangles a generic type into a string.
@@ -28,30 +29,39 @@ impl<'a> Display for Mangled<'a> {
         self.0.mangle(f)
     }
 }
+/// Parses the `;<count>` that opens a mangled arity or param.
+fn mangled_count(a: &str) -> IResult<&str, usize, PitError> {
+    preceded(tag(";"), digit1.map_opt(|a: &str| a.parse::<usize>().ok())).parse(a)
+}
+/// Mangles an arity in prefix order:
+///
+/// ```text
+/// arity := ";" count ( "P" name arity ){count}
+/// ```
+///
+/// Slots are written in name order, so `<K <> V <T <>>>` mangles to `;2PK;0PV;1PT;0`.
+/// Demangling reads the same grammar recursively, and fails on a repeated slot name, so
+/// the two are inverse.
 impl Mangle for Arity {
-    fn demangle(a: &str) -> IResult<&str, Self>
+    fn demangle(a: &str) -> IResult<&str, Self, PitError>
     where
         Self: Sized,
     {
-        let (a, b) = tag(";")
-            .and_then(ident.map_opt(|a: &str| a.parse::<usize>().ok()))
-            .parse(a)?;
-        let (a, m) = many1((
-            tag("P").and_then(ident),
-            tag(";").and_then(ident.map_opt(|a: &str| a.parse::<usize>().ok())),
-        ))
-        .parse(a)?;
-        let mut stack = vec![];
-        for (i, j) in m.into_iter().rev() {
-            let m = Arity {
-                to_fill: (0..j).filter_map(|a| stack.pop()).collect(),
-            };
-            stack.push((i.to_owned(), m));
+        let (mut a, n) = mangled_count(a)?;
+        let mut to_fill = BTreeMap::new();
+        for _ in 0..n {
+            let start = a;
+            let (b, name) = preceded(tag("P"), ident_typed).parse(a)?;
+            let (b, arity) = Arity::demangle(b)?;
+            if to_fill.insert(name.to_owned(), arity).is_some() {
+                return Err(nom::Err::Failure(PitError::Nom {
+                    kind: nom::error::ErrorKind::Verify,
+                    remaining: start.len(),
+                }));
+            }
+            a = b;
         }
-        let p = Arity {
-            to_fill: (0..b).filter_map(|a| stack.pop()).collect(),
-        };
-        Ok((a, p))
+        Ok((a, Arity { to_fill }))
     }
 
     fn mangle(&self, f: &mut Formatter) -> core::fmt::Result {
@@ -82,63 +92,48 @@ pub enum Param {
 }
 /// Implements mangling and demangling for Param, supporting Attr, Interface, and Param variants.
 impl Mangle for Param {
-    fn demangle(a: &str) -> IResult<&str, Self>
+    fn demangle(a: &str) -> IResult<&str, Self, PitError>
     where
         Self: Sized,
     {
-        fn parse_nonattr(a: &str) -> IResult<&str, Param> {
-            let (a, b) = (
-                tag("R").and_then(take_while_m_n(64, 64, |a: char| a.is_digit(16)).map(|a| {
-                    let mut b = [0u8; 32];
-                    hex::decode_to_slice(a, &mut b).unwrap();
-                    b
-                })),
-                tag(";").and_then(ident.map_opt(|a: &str| a.parse::<usize>().ok())),
-            )
-                .parse(a)?;
-
-            let (a, params) = count(
+        fn entries(a: &str, n: usize) -> IResult<&str, BTreeMap<String, Param>, PitError> {
+            let (a, entries) = count(
                 (
-                    tag(";").and_then(ident),
-                    tag(";").and_then(Param::demangle),
+                    preceded(tag(";"), ident_typed),
+                    preceded(tag(";"), Param::demangle),
                 ),
-                b.1,
+                n,
             )
             .parse(a)?;
-
             Ok((
                 a,
-                Param::Interface {
-                    rid: b.0,
-                    params: params.into_iter().map(|(a, b)| (a.to_owned(), b)).collect(),
-                },
+                entries.into_iter().map(|(a, b)| (a.to_owned(), b)).collect(),
             ))
         }
-        fn parse_param(a: &str) -> IResult<&str, Param> {
-            let (a, b) = (
-                tag("$").and_then(ident),
-                tag(";").and_then(ident.map_opt(|a: &str| a.parse::<usize>().ok())),
-            )
-                .parse(a)?;
-
-            let (a, params) = count(
-                (
-                    tag(";").and_then(ident),
-                    tag(";").and_then(Param::demangle),
-                ),
-                b.1,
-            )
-            .parse(a)?;
-
+        fn parse_nonattr(a: &str) -> IResult<&str, Param, PitError> {
+            let a = tag("R").parse(a)?.0;
+            // Take any 64 alphanumerics, so a non-hex rid fails in `decode_rid` rather
+            // than falling through to the other param forms.
+            let (rest, hex) =
+                take_while_m_n(64, 64, |a: char| a.is_ascii_alphanumeric()).parse(a)?;
+            let rid = decode_rid(a, hex)?;
+            let (a, n) = mangled_count(rest)?;
+            let (a, params) = entries(a, n)?;
+            Ok((a, Param::Interface { rid, params }))
+        }
+        fn parse_param(a: &str) -> IResult<&str, Param, PitError> {
+            let (a, param) = preceded(tag("$"), ident_typed).parse(a)?;
+            let (a, n) = mangled_count(a)?;
+            let (a, nest) = entries(a, n)?;
             Ok((
                 a,
                 Param::Param {
-                    param: b.0.to_owned(),
-                    nest: params.into_iter().map(|(a, b)| (a.to_owned(), b)).collect(),
+                    param: param.to_owned(),
+                    nest,
                 },
             ))
         }
-        return parse_attr
+        return parse_attr_typed
             .map(Param::Attr)
             .or(parse_nonattr)
             .or(parse_param)
@@ -165,3 +160,427 @@ impl Mangle for Param {
         }
     }
 }
+impl Param {
+    /// Returns the mangled form; [`Mangle::demangle`] parses it back.
+    ///
+    /// An attribute mangles as itself (`[name=value]`), an interface as
+    /// `R<rid>;<count>` and a named param as `$<name>;<count>`, each followed by
+    /// `;<slot>;<param>` per entry in slot name order.
+    pub fn mangle_to_string(&self) -> String {
+        Mangled(self).to_string()
+    }
+
+    /// Checks that the param has the shape `expected` describes.
+    ///
+    /// The params of a `Param::Interface`, or the nest of a `Param::Param`, must have
+    /// exactly the slots of `expected.to_fill`, each checked recursively against its own
+    /// arity. A `Param::Attr` fills a leaf slot, so `expected` must be empty. Errors name
+    /// the slot by its path from this param, joined with `.`.
+    pub fn check_arity(&self, expected: &Arity) -> Result<(), GenericsError> {
+        fn go(p: &Param, expected: &Arity, path: &str) -> Result<(), GenericsError> {
+            let join = |k: &str| {
+                if path.is_empty() {
+                    k.to_owned()
+                } else {
+                    format!("{path}.{k}")
+                }
+            };
+            let params = match p {
+                Param::Attr(_) => {
+                    return match expected.to_fill.keys().next() {
+                        Some(k) => Err(GenericsError::MissingParam(join(k))),
+                        None => Ok(()),
+                    };
+                }
+                Param::Interface { params, .. } => params,
+                Param::Param { nest, .. } => nest,
+            };
+            if let Some(k) = expected.to_fill.keys().find(|k| !params.contains_key(*k)) {
+                return Err(GenericsError::MissingParam(join(k)));
+            }
+            for (k, p) in params {
+                match expected.to_fill.get(k) {
+                    Some(a) => go(p, a, &join(k))?,
+                    None => return Err(GenericsError::UnexpectedParam(join(k))),
+                }
+            }
+            Ok(())
+        }
+        go(self, expected, "")
+    }
+
+    /// Returns the SHA3-256 of the param's mangled form.
+    ///
+    /// The mangling visits slot maps in name order and spells out every rid, slot name,
+    /// nested param and attribute, so equal params hash identically. Useful for deduplicating
+    /// instantiations.
+    pub fn struct_hash(&self) -> [u8; 32] {
+        use core::fmt::Write;
+        let mut h = Sha3_256::default();
+        write!(WriteUpdate { wrapped: &mut h }, "{}", Mangled(self)).unwrap();
+        h.finalize().into()
+    }
+
+    /// Returns the rid of every `Param::Interface` in this param, including nested ones.
+    ///
+    /// Together with [`Interface::referenced_rids`], this gives the dependencies of an
+    /// instantiation.
+    pub fn referenced_rids(&self) -> BTreeSet<[u8; 32]> {
+        fn go(p: &Param, rids: &mut BTreeSet<[u8; 32]>) {
+            match p {
+                Param::Attr(_) => {}
+                Param::Interface { rid, params } => {
+                    rids.insert(*rid);
+                    params.values().for_each(|p| go(p, rids));
+                }
+                Param::Param { nest, .. } => nest.values().for_each(|p| go(p, rids)),
+            }
+        }
+        let mut rids = BTreeSet::new();
+        go(self, &mut rids);
+        rids
+    }
+}
+/// Error returned by [`instantiate`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum GenericsError {
+    /// A slot of the arity with no param supplied.
+    MissingParam(String),
+    /// A supplied param that is not a slot of the arity.
+    UnexpectedParam(String),
+    /// A slot used by the interface that is not declared in the arity.
+    UnknownSlot(String),
+    /// A slot used as a resource type whose param is not an interface.
+    NotAnInterface(String),
+}
+impl Display for GenericsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GenericsError::MissingParam(s) => write!(f, "no param supplied for slot `{s}`"),
+            GenericsError::UnexpectedParam(s) => write!(f, "`{s}` is not a generic slot"),
+            GenericsError::UnknownSlot(s) => write!(f, "slot `{s}` is not declared"),
+            GenericsError::NotAnInterface(s) => write!(f, "param for `{s}` is not an interface"),
+        }
+    }
+}
+impl core::error::Error for GenericsError {}
+/// Monomorphizes a generic interface.
+///
+/// A resource argument annotated `[generics.modern=<slot>]` (see [`GENERIC_KEY`]) is a
+/// generic slot; its type is replaced by the rid of the `Param::Interface` supplied for that
+/// slot and the annotation is dropped. Slots inside function arguments are included. The
+/// `params` keys must match `arity.to_fill` exactly. Any [`ARITY_KEY`] annotation on the
+/// interface is removed, as the result is no longer generic.
+pub fn instantiate(
+    iface: &Interface,
+    arity: &Arity,
+    params: &BTreeMap<String, Param>,
+) -> Result<Interface, GenericsError> {
+    if let Some(k) = arity.to_fill.keys().find(|k| !params.contains_key(*k)) {
+        return Err(GenericsError::MissingParam(k.clone()));
+    }
+    if let Some(k) = params.keys().find(|k| !arity.to_fill.contains_key(*k)) {
+        return Err(GenericsError::UnexpectedParam(k.clone()));
+    }
+    fn go(sig: &mut Sig, params: &BTreeMap<String, Param>) -> Result<(), GenericsError> {
+        for arg in sig.params.iter_mut().chain(sig.rets.iter_mut()) {
+            if let Some(i) = arg.ann.iter().position(|a| a.name == GENERIC_KEY) {
+                let slot = &arg.ann[i].value;
+                let rid = match params.get(slot) {
+                    Some(Param::Interface { rid, .. }) => *rid,
+                    Some(_) => return Err(GenericsError::NotAnInterface(slot.clone())),
+                    None => return Err(GenericsError::UnknownSlot(slot.clone())),
+                };
+                if let ArgTy::Resource { ty, .. } = &mut arg.ty {
+                    *ty = ResTy::Of(rid);
+                    arg.ann.remove(i);
+                }
+            }
+            if let ArgTy::Func(inner) = &mut arg.ty {
+                go(inner, params)?;
+            }
+        }
+        Ok(())
+    }
+    let mut out = iface.clone();
+    out.ann.retain(|a| a.name != ARITY_KEY);
+    for sig in out.methods.values_mut() {
+        go(sig, params)?;
+    }
+    Ok(out)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instantiate() {
+        let elem = parse_interface("{len() -> (I64)}").unwrap().1;
+        let elem_rid = elem.rid();
+        let container = parse_interface(
+            "[generic_params.modern=T]{get(I32) -> ([generics.modern=T]Rn);put([generics.modern=T]R&,^([generics.modern=T]R) -> ()) -> ()}",
+        )
+        .unwrap()
+        .1;
+        let arity: Arity = "<T<>>".parse().unwrap();
+        let elem_param = Param::Interface {
+            rid: elem_rid,
+            params: BTreeMap::new(),
+        };
+        let params = BTreeMap::from([("T".to_owned(), elem_param)]);
+
+        let concrete = instantiate(&container, &arity, &params).unwrap();
+        let expected = parse_interface(&format!(
+            "{{get(I32) -> (R{e}n);put(R{e}&,^(R{e}) -> ()) -> ()}}",
+            e = hex::encode(elem_rid)
+        ))
+        .unwrap()
+        .1;
+        assert_eq!(concrete, expected);
+
+        assert_eq!(
+            instantiate(&container, &arity, &BTreeMap::new()),
+            Err(GenericsError::MissingParam("T".to_owned()))
+        );
+        let mut extra = params.clone();
+        extra.insert("U".to_owned(), params["T"].clone());
+        assert_eq!(
+            instantiate(&container, &arity, &extra),
+            Err(GenericsError::UnexpectedParam("U".to_owned()))
+        );
+        let attr = BTreeMap::from([(
+            "T".to_owned(),
+            Param::Attr(Attr {
+                name: "x".to_owned(),
+                value: "1".to_owned(),
+            }),
+        )]);
+        assert_eq!(
+            instantiate(&container, &arity, &attr),
+            Err(GenericsError::NotAnInterface("T".to_owned()))
+        );
+        assert_eq!(
+            instantiate(&container, &Arity::default(), &BTreeMap::new()),
+            Err(GenericsError::UnknownSlot("T".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_param_struct_hash() {
+        let leaf = |rid: [u8; 32]| Param::Interface {
+            rid,
+            params: BTreeMap::new(),
+        };
+        let outer = |inner: Param| Param::Interface {
+            rid: [7; 32],
+            params: [
+                ("T".to_owned(), inner),
+                (
+                    "U".to_owned(),
+                    Param::Attr(Attr {
+                        name: "n".to_owned(),
+                        value: "1".to_owned(),
+                    }),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let a = outer(leaf([1; 32]));
+        assert_eq!(a.struct_hash(), a.clone().struct_hash());
+        assert_eq!(a.struct_hash(), outer(leaf([1; 32])).struct_hash());
+        assert_ne!(a.struct_hash(), outer(leaf([2; 32])).struct_hash());
+    }
+
+    #[test]
+    fn test_param_referenced_rids() {
+        let p = Param::Interface {
+            rid: [1; 32],
+            params: [(
+                "T".to_owned(),
+                Param::Param {
+                    param: "U".to_owned(),
+                    nest: [(
+                        "V".to_owned(),
+                        Param::Interface {
+                            rid: [2; 32],
+                            params: BTreeMap::new(),
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        assert_eq!(
+            p.referenced_rids().into_iter().collect::<Vec<_>>(),
+            [[1; 32], [2; 32]]
+        );
+    }
+
+    #[test]
+    fn test_param_demangle_bad_hex() {
+        // Well-formed apart from a non-hex digit in the rid
+        for src in [
+            format!("Rg{};0", "a".repeat(63)),
+            format!("R{}z;0", "a".repeat(63)),
+            format!("R{}G{};1;x;[a=b]", "0".repeat(31), "f".repeat(32)),
+        ] {
+            match Param::demangle(&src) {
+                Err(nom::Err::Failure(e)) => {
+                    assert_eq!(
+                        e,
+                        PitError::Nom {
+                            kind: nom::error::ErrorKind::HexDigit,
+                            remaining: src.len() - 1,
+                        },
+                        "{src}"
+                    );
+                    assert_eq!(e.offset_in(&src), 1);
+                }
+                other => panic!("{src}: expected a hex failure, got {other:?}"),
+            }
+        }
+
+        // A short or non-ASCII rid is not an interface param at all
+        for src in [
+            format!("R{};0", "a".repeat(63)),
+            format!("R{};0", "\u{663}".repeat(64)),
+        ] {
+            assert!(matches!(Param::demangle(&src), Err(nom::Err::Error(_))), "{src}");
+        }
+
+        // The same input with a valid rid demangles
+        let src = format!("R{};1;x;[a=b]", "0f".repeat(32));
+        let (rest, p) = Param::demangle(&src).unwrap();
+        assert!(rest.is_empty());
+        assert!(matches!(p, Param::Interface { rid, .. } if rid == [0x0f; 32]));
+    }
+
+    #[test]
+    fn test_param_mangle_roundtrip() {
+        let attr = |name: &str, value: &str| {
+            Param::Attr(Attr {
+                name: name.to_owned(),
+                value: value.to_owned(),
+            })
+        };
+        let leaf = Param::Interface {
+            rid: [0xab; 32],
+            params: BTreeMap::new(),
+        };
+        let inner = Param::Interface {
+            rid: [1; 32],
+            params: [
+                ("K".to_owned(), leaf.clone()),
+                ("V".to_owned(), attr("doc", "a [nested] value")),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let nested = Param::Param {
+            param: "Elem".to_owned(),
+            nest: [("T".to_owned(), inner.clone()), ("flag".to_owned(), attr("x", ""))]
+                .into_iter()
+                .collect(),
+        };
+        let outer = Param::Interface {
+            rid: [7; 32],
+            params: [
+                ("A".to_owned(), inner.clone()),
+                ("B".to_owned(), nested.clone()),
+                ("C".to_owned(), leaf.clone()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        for p in [attr("n", "1"), leaf, inner, nested, outer] {
+            let s = p.mangle_to_string();
+            assert_eq!(Param::demangle(&s), Ok(("", p)), "{s}");
+        }
+        assert_eq!(
+            Param::Interface {
+                rid: [0xab; 32],
+                params: [("K".to_owned(), attr("n", "1"))].into_iter().collect(),
+            }
+            .mangle_to_string(),
+            format!("R{};1;K;[n=1]", "ab".repeat(32))
+        );
+    }
+
+    #[test]
+    fn test_arity_mangle_roundtrip() {
+        let arity = |s: &str| Arity::parse(s).unwrap().1;
+        for src in [
+            "<>",
+            "<T <>>",
+            "<A <> B <> C <>>",
+            "<K <> V <T <>>>",
+            "<F <A <B <C <>> D <>>> G <>>",
+            "<T <U <V <W <X <>>>>>>",
+        ] {
+            let a = arity(src);
+            let mangled = format!("{}", Mangled(&a));
+            assert_eq!(Arity::demangle(&mangled), Ok(("", a)), "{src}: {mangled}");
+        }
+        assert_eq!(format!("{}", Mangled(&arity("<K <> V <T <>>>"))), ";2PK;0PV;1PT;0");
+
+        // Too few entries, and a repeated slot, are errors rather than silently dropped.
+        assert!(Arity::demangle(";2PK;0").is_err());
+        assert!(Arity::demangle(";1PK;1").is_err());
+        assert!(Arity::demangle(";2PK;0PK;0").is_err());
+        assert_eq!(Arity::demangle(";1PK;0PV;0"), Ok(("PV;0", arity("<K <>>"))));
+    }
+
+    #[test]
+    fn test_param_check_arity() {
+        let arity = Arity::parse("<K <> V <T <>>>").unwrap().1;
+        let attr = Param::Attr(Attr {
+            name: "n".to_owned(),
+            value: "1".to_owned(),
+        });
+        let iface = |params: &[(&str, Param)]| Param::Interface {
+            rid: [1; 32],
+            params: params
+                .iter()
+                .map(|(k, p)| ((*k).to_owned(), p.clone()))
+                .collect(),
+        };
+        let v = iface(&[("T", attr.clone())]);
+        let good = iface(&[("K", attr.clone()), ("V", v.clone())]);
+        assert_eq!(good.check_arity(&arity), Ok(()));
+        let nested = Param::Param {
+            param: "P".to_owned(),
+            nest: [("K".to_owned(), attr.clone()), ("V".to_owned(), v)]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(nested.check_arity(&arity), Ok(()));
+        assert_eq!(attr.check_arity(&Arity::default()), Ok(()));
+
+        let missing = iface(&[("K", attr.clone()), ("V", iface(&[]))]);
+        assert_eq!(
+            missing.check_arity(&arity),
+            Err(GenericsError::MissingParam("V.T".to_owned()))
+        );
+        assert_eq!(
+            iface(&[("K", attr.clone())]).check_arity(&arity),
+            Err(GenericsError::MissingParam("V".to_owned()))
+        );
+        let extra = iface(&[
+            ("K", attr.clone()),
+            ("V", iface(&[("T", attr.clone()), ("U", attr.clone())])),
+        ]);
+        assert_eq!(
+            extra.check_arity(&arity),
+            Err(GenericsError::UnexpectedParam("V.U".to_owned()))
+        );
+        assert_eq!(
+            iface(&[("K", attr.clone()), ("V", attr.clone())]).check_arity(&arity),
+            Err(GenericsError::MissingParam("V.T".to_owned()))
+        );
+    }
+}
@@ -1,18 +1,24 @@
 use alloc::{borrow::ToOwned, collections::BTreeMap, string::String, vec::Vec};
 
-#[cfg(test)]
+#[cfg(any(test, feature = "doc-attrs"))]
 use alloc::format;
 use core::fmt::Display;
 
 use nom::{
     bytes::complete::{tag, take_while_m_n},
-    character::complete::{alphanumeric1, multispace0},
+    character::complete::{alphanumeric1, digit1, multispace0},
+    error::{ErrorKind, ParseError},
     multi::many0,
     sequence::delimited,
     IResult, Parser,
 };
 
-use crate::{merge, parse_attr, Attr};
+use crate::{
+    ident_typed, legacy, merge, parse_attr_typed, registry::Registry, streaming, Arg, ArgSide,
+    Attr, Interface, PitError, Sig,
+};
+#[cfg(feature = "doc-attrs")]
+use crate::{ArgTy, Location, ResTy};
 
 /// Macro to generate documentation attribute accessor methods for types that have an `attrs` field.
 ///
@@ -28,6 +34,8 @@ use crate::{merge, parse_attr, Attr};
 /// - `category()` - Returns the category
 /// - `since()` - Returns version when introduced
 /// - `get_attr(name)` - Returns value of any attribute by name
+/// - `doc_for_locale(locale)`, `brief_for_locale(locale)`, `name_for_locale(locale)` -
+///   Return `<attr>.<locale>`, falling back to the unlocalized attribute
 ///
 /// All methods are feature-gated behind `#[cfg(feature = "doc-attrs")]`.
 ///
@@ -91,11 +99,78 @@ macro_rules! impl_doc_attrs {
             pub fn get_attr(&self, name: &str) -> Option<&str> {
                 self.attrs.iter().find_map(|a| a.as_attr(name))
             }
+
+            /// Returns `<base>.<locale>` if set, falling back to plain `base`.
+            fn localized(&self, base: &str, locale: &str) -> Option<&str> {
+                self.attrs
+                    .iter()
+                    .find(|a| {
+                        a.name
+                            .strip_prefix(base)
+                            .and_then(|n| n.strip_prefix('.'))
+                            == Some(locale)
+                    })
+                    .map(|a| a.value.as_str())
+                    .or_else(|| self.get_attr(base))
+            }
+
+            /// Returns the documentation for `locale` (`doc.<locale>`), falling back to `doc`.
+            pub fn doc_for_locale(&self, locale: &str) -> Option<&str> {
+                self.localized("doc", locale)
+            }
+
+            /// Returns the brief summary for `locale` (`brief.<locale>`), falling back to `brief`.
+            pub fn brief_for_locale(&self, locale: &str) -> Option<&str> {
+                self.localized("brief", locale)
+            }
+
+            /// Returns the display name for `locale` (`name.<locale>`), falling back to `name`.
+            pub fn name_for_locale(&self, locale: &str) -> Option<&str> {
+                self.localized("name", locale)
+            }
+        }
+    };
+}
+
+/// Macro to generate attribute mutation methods for types that have an `attrs` field.
+///
+/// The generated `set_attr` and `remove_attr` keep `attrs` sorted by name, as the parser
+/// leaves it, so callers don't need to touch the vector directly.
+macro_rules! impl_attr_mut {
+    ($type:ty) => {
+        impl $type {
+            /// Returns the attributes, sorted by name.
+            pub fn attrs(&self) -> &[Attr] {
+                &self.attrs
+            }
+
+            /// Sets attribute `name` to `value`, replacing any existing value.
+            pub fn set_attr(&mut self, name: impl Into<String>, value: impl Into<String>) {
+                let name = name.into();
+                self.attrs.retain(|a| a.name != name);
+                let i = self.attrs.partition_point(|a| a.name < name);
+                self.attrs.insert(
+                    i,
+                    Attr {
+                        name,
+                        value: value.into(),
+                    },
+                );
+            }
+
+            /// Removes attribute `name`, returning its value if it was set.
+            pub fn remove_attr(&mut self, name: &str) -> Option<String> {
+                let i = self.attrs.iter().position(|a| a.name == name)?;
+                let old = self.attrs.remove(i);
+                self.attrs.retain(|a| a.name != name);
+                Some(old.value)
+            }
         }
     };
 }
 
 /// Stores attributes for a method parameter or return value.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Default, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct ParamEntry {
     pub attrs: Vec<Attr>,
@@ -114,9 +189,12 @@ impl ParamEntry {
 // This provides: name(), doc(), brief(), deprecated(), llm_context(), llm_intent(), 
 // category(), since(), and get_attr() methods
 impl_doc_attrs!(ParamEntry);
+impl_attr_mut!(ParamEntry);
 /// Stores interface information for the crate.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Default, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct Info {
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex_id::map"))]
     pub interfaces: BTreeMap<[u8; 32], InfoEntry>,
 }
 
@@ -131,21 +209,93 @@ impl Info {
         Info { interfaces: m }
     }
 
-    /// Parses info from a string.
+    /// Merges like [`Info::merge`], but fails if the result documents a method that the
+    /// interface registered in `reg` under the same id does not declare.
+    ///
+    /// Entries whose id is not in `reg` are merged without checks.
+    pub fn merge_for(self, reg: &Registry, other: Info) -> Result<Info, Vec<DanglingDoc>> {
+        let merged = self.merge(other);
+        let dangling: Vec<_> = merged
+            .cross_check(reg)
+            .into_iter()
+            .filter_map(|issue| match issue {
+                CrossCheckIssue::UnknownMethod { rid, method } => Some(DanglingDoc { rid, method }),
+                _ => None,
+            })
+            .collect();
+        if dangling.is_empty() {
+            Ok(merged)
+        } else {
+            Err(dangling)
+        }
+    }
+
+    /// Drops documentation entries that carry nothing: [`ParamEntry`]s without
+    /// attributes, then [`MethEntry`]s left with no attributes, params, or returns.
+    ///
+    /// Interface entries themselves are kept, even when empty.
+    pub fn prune_empty(&mut self) {
+        for entry in self.interfaces.values_mut() {
+            entry.methods.retain(|_, m| {
+                m.params.retain(|_, p| !p.attrs.is_empty());
+                m.returns.retain(|_, p| !p.attrs.is_empty());
+                !(m.attrs.is_empty() && m.params.is_empty() && m.returns.is_empty())
+            });
+        }
+    }
+
+    /// [`Info::parse_typed`] with nom's default error type.
     pub fn parse(input: &str) -> IResult<&str, Info> {
-        fn parse_interface_entry(input: &str) -> IResult<&str, ([u8; 32], InfoEntry)> {
-            let (input, _) = multispace0(input)?;
-            let (input, hex_id) = take_while_m_n(64, 64, |c: char| c.is_digit(16))(input)?;
-            let mut id = [0u8; 32];
-            hex::decode_to_slice(hex_id, &mut id).unwrap();
-            let (input, _) = multispace0(input)?;
-            let (input, _) = tag(":")(input)?;
-            let (input, _) = multispace0(input)?;
-            let (input, entry) = delimited(tag("["), InfoEntry::parse, tag("]")).parse(input)?;
-            Ok((input, (id, entry)))
+        legacy(input, Info::parse_typed)
+    }
+
+    /// Parses one `id: [...]` entry of an info, as [`Info::parse_typed`] reads them.
+    fn parse_entry_typed(input: &str) -> IResult<&str, ([u8; 32], InfoEntry), PitError> {
+        let (input, _) = multispace0(input)?;
+        // Anything shaped like `token:` is meant to be an id, so a malformed one is a
+        // hard failure pointing at the id rather than a silent end of the entry list.
+        let bad_id = || {
+            nom::Err::Failure(PitError::BadHexLength {
+                expected: 64,
+                remaining: input.len(),
+            })
+        };
+        let token_len = input
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(input.len());
+        // An empty token before the `:` is a missing id, not the end of the list.
+        let is_header = input[token_len..].trim_start().starts_with(':');
+        if is_header && token_len != 64 {
+            return Err(bad_id());
         }
+        let (input, hex_id) = take_while_m_n(64, 64, |c: char| c.is_ascii_hexdigit())(input)
+            .map_err(|e| if is_header { bad_id() } else { e })?;
+        let mut id = [0u8; 32];
+        hex::decode_to_slice(hex_id, &mut id).map_err(|_| bad_id())?;
+        let (input, _) = multispace0(input)?;
+        let (input, _) = tag(":")(input)?;
+        let (input, _) = multispace0(input)?;
+        let (input, entry) = delimited(tag("["), InfoEntry::parse_typed, tag("]")).parse(input)?;
+        Ok((input, (id, entry)))
+    }
+
+    /// Parses one entry from input that may still be arriving, like
+    /// [`Interface::parse_streaming`].
+    ///
+    /// Info has no closing delimiter, so a stream is read an entry at a time: this returns
+    /// `Err(nom::Err::Incomplete(_))` until the `]` closing the entry is present, then
+    /// parses it like one entry of [`Info::parse_typed`]. An id with a non-hex character fails
+    /// straight away.
+    pub fn parse_entry_streaming(input: &str) -> IResult<&str, ([u8; 32], InfoEntry), PitError> {
+        // Only the header is seen outside brackets; the entry is one bracketed block.
+        streaming(input, Info::parse_entry_typed, false, |c| {
+            c == ']' || !(c.is_ascii_hexdigit() || c == ':' || c.is_whitespace())
+        })
+    }
 
-        let (input, entries) = many0(parse_interface_entry).parse(input)?;
+    /// Parses info from a string.
+    pub fn parse_typed(input: &str) -> IResult<&str, Info, PitError> {
+        let (input, entries) = many0(Info::parse_entry_typed).parse(input)?;
         Ok((
             input,
             Info {
@@ -153,8 +303,219 @@ impl Info {
             },
         ))
     }
+
+    /// Returns the interfaces ordered by their `name` attribute, ties broken by id.
+    ///
+    /// Unnamed interfaces sort after all named ones, in id order.
+    #[cfg(feature = "doc-attrs")]
+    pub fn interfaces_by_name(&self) -> Vec<(&[u8; 32], &InfoEntry)> {
+        let mut v: Vec<_> = self.interfaces.iter().collect();
+        v.sort_by_key(|(id, e)| (e.name().is_none(), e.name(), *id));
+        v
+    }
+
+    /// Checks every entry against the interfaces in a registry.
+    ///
+    /// Reports entries whose id has no registered interface and documented
+    /// methods that the interface does not declare.
+    pub fn cross_check(&self, reg: &Registry) -> Vec<CrossCheckIssue> {
+        self.cross_check_impl(reg, false)
+    }
+
+    /// Like [`Info::cross_check`], but additionally reports interface methods
+    /// that have no documentation at all.
+    pub fn cross_check_strict(&self, reg: &Registry) -> Vec<CrossCheckIssue> {
+        self.cross_check_impl(reg, true)
+    }
+
+    fn cross_check_impl(&self, reg: &Registry, strict: bool) -> Vec<CrossCheckIssue> {
+        let mut issues = Vec::new();
+        for (rid, entry) in self.interfaces.iter() {
+            let Some(iface) = reg.get(rid) else {
+                issues.push(CrossCheckIssue::UnknownInterface(*rid));
+                continue;
+            };
+            for method in entry.methods.keys() {
+                if !iface.methods.contains_key(method) {
+                    issues.push(CrossCheckIssue::UnknownMethod {
+                        rid: *rid,
+                        method: method.clone(),
+                    });
+                }
+            }
+            if strict {
+                for method in iface.methods.keys() {
+                    let documented = entry.methods.get(method).is_some_and(|m| {
+                        !m.attrs.is_empty() || !m.params.is_empty() || !m.returns.is_empty()
+                    });
+                    if !documented {
+                        issues.push(CrossCheckIssue::UndocumentedMethod {
+                            rid: *rid,
+                            method: method.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        issues
+    }
+}
+
+/// Documentation for a method its interface does not declare, from [`Info::merge_for`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct DanglingDoc {
+    /// Id of the documented interface.
+    pub rid: [u8; 32],
+    /// The undeclared method.
+    pub method: String,
+}
+impl Display for DanglingDoc {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} documents unknown method `{}`",
+            hex::encode(self.rid),
+            self.method
+        )
+    }
+}
+impl core::error::Error for DanglingDoc {}
+/// A mismatch between an [`Info`] and the interfaces in a [`Registry`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum CrossCheckIssue {
+    /// An info entry whose id has no registered interface.
+    UnknownInterface([u8; 32]),
+    /// Documentation for a method the interface does not declare.
+    UnknownMethod { rid: [u8; 32], method: String },
+    /// An interface method with no documentation (strict mode only).
+    UndocumentedMethod { rid: [u8; 32], method: String },
+}
+/// A mismatch found by [`InfoEntry::validate_against`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum InfoError {
+    /// Documentation for a method the interface does not declare.
+    UnknownMethod(String),
+    /// Documentation for a parameter or return index past the end of the signature,
+    /// which has `len` of them.
+    IndexOutOfRange {
+        method: String,
+        side: ArgSide,
+        index: usize,
+        len: usize,
+    },
+}
+impl Display for InfoError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InfoError::UnknownMethod(name) => write!(f, "info documents unknown method `{name}`"),
+            InfoError::IndexOutOfRange {
+                method,
+                side,
+                index,
+                len,
+            } => {
+                let side = match side {
+                    ArgSide::Param => "param",
+                    ArgSide::Return => "return",
+                };
+                write!(f, "info documents {side} {index} of `{method}`, which has {len}")
+            }
+        }
+    }
+}
+impl core::error::Error for InfoError {}
+/// A single line of an [`InfoEntry`], as read by [`InfoLine::parse`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub enum InfoLine {
+    /// `root [attr]`: an attribute of the interface.
+    Root(Attr),
+    /// `method name [attr]`: an attribute of a method.
+    Method(String, Attr),
+    /// `param name index [attr]`: an attribute of a method's parameter.
+    Param(String, usize, Attr),
+    /// `return name index [attr]`: an attribute of a method's return value.
+    Return(String, usize, Attr),
+}
+/// Parses a param or return index: a whole word of decimal digits that fits a `usize`.
+///
+/// Anything else, e.g. `0x10`, fails with [`PitError::InvalidIndex`] spanning the word.
+fn parse_index(input: &str) -> IResult<&str, usize, PitError> {
+    let (rest, token) = alphanumeric1(input)?;
+    match digit1::<&str, PitError>(token) {
+        Ok(("", digits)) => digits.parse().ok(),
+        _ => None,
+    }
+    .map(|index| (rest, index))
+    .ok_or(nom::Err::Failure(PitError::InvalidIndex {
+        len: token.len(),
+        remaining: input.len(),
+    }))
+}
+impl InfoLine {
+    /// Parses one `root`, `method`, `param` or `return` line, consuming the whitespace
+    /// around it, line breaks included.
+    pub fn parse(input: &str) -> IResult<&str, InfoLine, PitError> {
+        let (input, _) = multispace0(input)?;
+        
+        // Try to parse root attribute
+        if let Ok((input, _)) = tag::<&str, &str, PitError>("root")(input) {
+            let (input, _) = multispace0(input)?;
+            let (input, attr) = parse_attr_typed(input)?;
+            return Ok((input, InfoLine::Root(attr)));
+        }
+        
+        // Try to parse param attribute
+        if let Ok((input, _)) = tag::<&str, &str, PitError>("param")(input) {
+            let (input, _) = multispace0(input)?;
+            let (input, method_name) = ident_typed(input)?;
+            let (input, _) = multispace0(input)?;
+            let (input, index) = parse_index(input)?;
+            let (input, _) = multispace0(input)?;
+            let (input, attr) = parse_attr_typed(input)?;
+            return Ok((input, InfoLine::Param(method_name.to_owned(), index, attr)));
+        }
+        
+        // Try to parse return attribute
+        if let Ok((input, _)) = tag::<&str, &str, PitError>("return")(input) {
+            let (input, _) = multispace0(input)?;
+            let (input, method_name) = ident_typed(input)?;
+            let (input, _) = multispace0(input)?;
+            let (input, index) = parse_index(input)?;
+            let (input, _) = multispace0(input)?;
+            let (input, attr) = parse_attr_typed(input)?;
+            return Ok((input, InfoLine::Return(method_name.to_owned(), index, attr)));
+        }
+        
+        // Try to parse method attribute
+        if let Ok((input, _)) = tag::<&str, &str, PitError>("method")(input) {
+            let (input, _) = multispace0(input)?;
+            let (input, method_name) = ident_typed(input)?;
+            let (input, _) = multispace0(input)?;
+            let (input, attr) = parse_attr_typed(input)?;
+            return Ok((input, InfoLine::Method(method_name.to_owned(), attr)));
+        }
+        
+        // If none match, return an error
+        Err(nom::Err::Error(PitError::from_error_kind(input, ErrorKind::Tag)))
+    }
 }
+
+/// Writes the line as [`InfoEntry`]'s `Display` does, without the line break.
+impl Display for InfoLine {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InfoLine::Root(a) => write!(f, "root {a}"),
+            InfoLine::Method(k, a) => write!(f, "method {k} {a}"),
+            InfoLine::Param(k, idx, a) => write!(f, "param {k} {idx} {a}"),
+            InfoLine::Return(k, idx, a) => write!(f, "return {k} {idx} {a}"),
+        }
+    }
+}
+
 /// Stores attributes and methods for an interface.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Default, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct InfoEntry {
     pub attrs: Vec<Attr>,
@@ -175,72 +536,72 @@ impl InfoEntry {
         }
     }
 
-    /// Parses an InfoEntry from a string.
-    pub fn parse(input: &str) -> IResult<&str, InfoEntry> {
-        let (input, _) = multispace0(input)?;
+    /// Returns an entry with an empty [`MethEntry`] for every method of `iface`, and an
+    /// empty [`ParamEntry`] for each of its parameter and return indices.
+    ///
+    /// A starting point for documentation whose indices match the signatures. Note that
+    /// empty entries render as nothing, so they do not survive a [`Display`] round trip.
+    pub fn skeleton_for(iface: &Interface) -> InfoEntry {
+        let slots = |n: usize| (0..n).map(|i| (i, ParamEntry::default())).collect();
+        InfoEntry {
+            attrs: Vec::new(),
+            methods: iface
+                .methods
+                .iter()
+                .map(|(name, sig)| {
+                    let m = MethEntry {
+                        attrs: Vec::new(),
+                        params: slots(sig.params.len()),
+                        returns: slots(sig.rets.len()),
+                    };
+                    (name.clone(), m)
+                })
+                .collect(),
+        }
+    }
 
-        // Parse any line and categorize it
-        fn parse_info_line(input: &str) -> IResult<&str, InfoLine> {
-            let (input, _) = multispace0(input)?;
-            
-            // Try to parse root attribute
-            if let Ok((input, _)) = tag::<&str, &str, nom::error::Error<&str>>("root")(input) {
-                let (input, _) = multispace0(input)?;
-                let (input, attr) = parse_attr(input)?;
-                return Ok((input, InfoLine::Root(attr)));
-            }
-            
-            // Try to parse param attribute
-            if let Ok((input, _)) = tag::<&str, &str, nom::error::Error<&str>>("param")(input) {
-                let (input, _) = multispace0(input)?;
-                let (input, method_name) = alphanumeric1(input)?;
-                let (input, _) = multispace0(input)?;
-                let (input, index_str) = alphanumeric1(input)?;
-                let index = index_str.parse::<usize>().map_err(|_| {
-                    nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Digit))
-                })?;
-                let (input, _) = multispace0(input)?;
-                let (input, attr) = parse_attr(input)?;
-                return Ok((input, InfoLine::Param(method_name.to_owned(), index, attr)));
-            }
-            
-            // Try to parse return attribute
-            if let Ok((input, _)) = tag::<&str, &str, nom::error::Error<&str>>("return")(input) {
-                let (input, _) = multispace0(input)?;
-                let (input, method_name) = alphanumeric1(input)?;
-                let (input, _) = multispace0(input)?;
-                let (input, index_str) = alphanumeric1(input)?;
-                let index = index_str.parse::<usize>().map_err(|_| {
-                    nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Digit))
-                })?;
-                let (input, _) = multispace0(input)?;
-                let (input, attr) = parse_attr(input)?;
-                return Ok((input, InfoLine::Return(method_name.to_owned(), index, attr)));
-            }
-            
-            // Try to parse method attribute
-            if let Ok((input, _)) = tag::<&str, &str, nom::error::Error<&str>>("method")(input) {
-                let (input, _) = multispace0(input)?;
-                let (input, method_name) = alphanumeric1(input)?;
-                let (input, _) = multispace0(input)?;
-                let (input, attr) = parse_attr(input)?;
-                return Ok((input, InfoLine::Method(method_name.to_owned(), attr)));
-            }
-            
-            // If none match, return an error
-            Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag)))
-        }
-
-        #[derive(Debug)]
-        enum InfoLine {
-            Root(Attr),
-            Method(String, Attr),
-            Param(String, usize, Attr),
-            Return(String, usize, Attr),
+    /// Checks that every documented method, parameter, and return exists in `iface`.
+    ///
+    /// Catches documentation left stale by a signature change.
+    pub fn validate_against(&self, iface: &Interface) -> Result<(), Vec<InfoError>> {
+        let mut errors = Vec::new();
+        for (name, m) in self.methods.iter() {
+            let Some(sig) = iface.methods.get(name) else {
+                errors.push(InfoError::UnknownMethod(name.clone()));
+                continue;
+            };
+            for (side, entries, len) in [
+                (ArgSide::Param, &m.params, sig.params.len()),
+                (ArgSide::Return, &m.returns, sig.rets.len()),
+            ] {
+                for index in entries.keys().filter(|i| **i >= len) {
+                    errors.push(InfoError::IndexOutOfRange {
+                        method: name.clone(),
+                        side,
+                        index: *index,
+                        len,
+                    });
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
+    }
+
+    /// [`InfoEntry::parse_typed`] with nom's default error type.
+    pub fn parse(input: &str) -> IResult<&str, InfoEntry> {
+        legacy(input, InfoEntry::parse_typed)
+    }
+
+    /// Parses an InfoEntry from a string.
+    pub fn parse_typed(input: &str) -> IResult<&str, InfoEntry, PitError> {
+        let (input, _) = multispace0(input)?;
 
         // Parse all info lines
-        let (input, lines) = many0(parse_info_line).parse(input)?;
+        let (input, lines) = many0(InfoLine::parse).parse(input)?;
 
         // Process lines to build InfoEntry
         let mut root_attrs = Vec::new();
@@ -305,7 +666,9 @@ impl InfoEntry {
 // This provides: name(), doc(), brief(), deprecated(), llm_context(), llm_intent(),
 // category(), since(), and get_attr() methods  
 impl_doc_attrs!(InfoEntry);
+impl_attr_mut!(InfoEntry);
 /// Stores attributes for a method, including its parameters and return values.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Default, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct MethEntry {
     pub attrs: Vec<Attr>,
@@ -377,6 +740,7 @@ impl MethEntry {
 // This provides: name(), doc(), brief(), deprecated(), llm_context(), llm_intent(),
 // category(), since(), and get_attr() methods
 impl_doc_attrs!(MethEntry);
+impl_attr_mut!(MethEntry);
 /// Display implementation for InfoEntry, formats attributes as root entries.
 impl Display for InfoEntry {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -403,13 +767,260 @@ impl Display for InfoEntry {
 }
 impl Display for Info {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Each block is on its own lines so consecutive interfaces stay apart.
         for (i, j) in self.interfaces.iter() {
-            write!(f, "{}: [{}]", hex::encode(i), j)?;
+            writeln!(f, "{}: [\n{}]", hex::encode(i), j)?;
         }
         Ok(())
     }
 }
 
+/// An interface with its info entry zipped in, from [`document`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct DocumentedInterface {
+    /// The interface's own annotations.
+    pub ann: Vec<Attr>,
+    /// The info entry's `root` attributes.
+    pub attrs: Vec<Attr>,
+    /// Every method the interface declares, with its documentation if any.
+    pub methods: BTreeMap<String, DocumentedMethod>,
+    /// Documentation for methods the interface does not declare.
+    pub unknown: BTreeMap<String, MethEntry>,
+}
+/// A method signature with its documentation.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct DocumentedMethod {
+    /// The signature.
+    pub sig: Sig,
+    /// The method's documentation, or `None` if the info entry lacks the method.
+    pub entry: Option<MethEntry>,
+    /// Each parameter with its documentation, by index.
+    pub params: Vec<DocumentedArg>,
+    /// Each return value with its documentation, by index.
+    pub returns: Vec<DocumentedArg>,
+}
+/// An argument with its documentation.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DocumentedArg {
+    /// The argument.
+    pub arg: Arg,
+    /// Its documentation, or `None` if there is none for this index.
+    pub entry: Option<ParamEntry>,
+}
+/// Zips an interface with its info entry for documentation generation.
+///
+/// Documented indices past the end of a signature are dropped; use
+/// [`InfoEntry::validate_against`] to report them.
+pub fn document(iface: &Interface, info: &InfoEntry) -> DocumentedInterface {
+    let zip = |args: &[Arg], docs: Option<&BTreeMap<usize, ParamEntry>>| {
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| DocumentedArg {
+                arg: arg.clone(),
+                entry: docs.and_then(|d| d.get(&i)).cloned(),
+            })
+            .collect()
+    };
+    DocumentedInterface {
+        ann: iface.ann.clone(),
+        attrs: info.attrs.clone(),
+        methods: iface
+            .methods
+            .iter()
+            .map(|(name, sig)| {
+                let entry = info.methods.get(name);
+                let m = DocumentedMethod {
+                    sig: sig.clone(),
+                    entry: entry.cloned(),
+                    params: zip(&sig.params, entry.map(|e| &e.params)),
+                    returns: zip(&sig.rets, entry.map(|e| &e.returns)),
+                };
+                (name.clone(), m)
+            })
+            .collect(),
+        unknown: info
+            .methods
+            .iter()
+            .filter(|(name, _)| !iface.methods.contains_key(*name))
+            .map(|(name, e)| (name.clone(), e.clone()))
+            .collect(),
+    }
+}
+#[cfg(feature = "doc-attrs")]
+impl DocumentedInterface {
+    /// Returns the names of declared methods that have no documentation.
+    pub fn undocumented(&self) -> Vec<&str> {
+        self.methods
+            .iter()
+            .filter(|(_, m)| m.entry.is_none())
+            .map(|(n, _)| n.as_str())
+            .collect()
+    }
+}
+#[cfg(feature = "doc-attrs")]
+impl DocumentedMethod {
+    /// Returns the method's `doc` attribute.
+    pub fn doc(&self) -> Option<&str> {
+        self.entry.as_ref()?.doc()
+    }
+    /// Returns the method's `brief` attribute.
+    pub fn brief(&self) -> Option<&str> {
+        self.entry.as_ref()?.brief()
+    }
+    /// Returns the `name` attribute of parameter `i`.
+    pub fn param_name(&self, i: usize) -> Option<&str> {
+        self.params.get(i)?.entry.as_ref()?.name()
+    }
+}
+/// Pairs each parameter of `sig` with its `name` from `meth`, falling back to `arg<i>`.
+///
+/// This is the raw material for emitting a typed params struct per method.
+#[cfg(feature = "doc-attrs")]
+pub fn method_params_typed(sig: &Sig, meth: &MethEntry) -> Vec<(String, Arg)> {
+    sig.params
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let name = match meth.params.get(&i).and_then(|p| p.name()) {
+                Some(n) => n.to_owned(),
+                None => format!("arg{i}"),
+            };
+            (name, arg.clone())
+        })
+        .collect()
+}
+
+/// Documentation of an interface, with the summaries of the interfaces it references.
+#[cfg(feature = "doc-attrs")]
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct ExpandedDoc {
+    /// The documented interface.
+    pub rid: [u8; 32],
+    /// Its own info entry, empty if it has none.
+    pub entry: InfoEntry,
+    /// One entry per resource argument of a known type, in method order.
+    pub refs: Vec<InlineDoc>,
+}
+/// Summary of an interface referenced by a resource argument; see [`expand_inline_docs`].
+#[cfg(feature = "doc-attrs")]
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct InlineDoc {
+    /// Where the argument appears.
+    pub location: Location,
+    /// The referenced interface.
+    pub rid: [u8; 32],
+    /// Its `name` attribute, if documented.
+    pub name: Option<String>,
+    /// Its `brief` attribute, if documented.
+    pub brief: Option<String>,
+}
+/// Collects the docs of `root` and, for each `ResTy::Of` resource argument of its methods
+/// (including returns and arguments of function arguments), the `name` and `brief` of the
+/// referenced interface.
+///
+/// Returns `None` if `root` is not in `reg`.
+#[cfg(feature = "doc-attrs")]
+pub fn expand_inline_docs(info: &Info, reg: &Registry, root: &[u8; 32]) -> Option<ExpandedDoc> {
+    let iface = reg.get(root)?;
+    let mut refs = Vec::new();
+    iface.for_each_arg(&mut |location, arg| {
+        if let ArgTy::Resource {
+            ty: ResTy::Of(rid), ..
+        } = &arg.ty
+        {
+            let entry = info.interfaces.get(rid);
+            refs.push(InlineDoc {
+                location: location.clone(),
+                rid: *rid,
+                name: entry.and_then(|e| e.name()).map(|n| n.to_owned()),
+                brief: entry.and_then(|e| e.brief()).map(|b| b.to_owned()),
+            });
+        }
+    });
+    Some(ExpandedDoc {
+        rid: *root,
+        entry: info.interfaces.get(root).cloned().unwrap_or_default(),
+        refs,
+    })
+}
+
+/// Renders `iface` for documentation, writing resources by name instead of by id.
+///
+/// A resource `R<id>` is written as the `name` attribute of `id`'s entry in `info`,
+/// else the `name` annotation of the interface `reg` holds for `id`, else the first 16
+/// bytes of `id` in hex. `?` marks a nullable resource and `&` a borrowed one. The
+/// result is meant for reading and does not parse back.
+pub fn pretty_with_registry(iface: &Interface, reg: &Registry, info: &Info) -> String {
+    use core::fmt::Write;
+    fn name_of(attrs: &[Attr]) -> Option<&str> {
+        attrs.iter().find(|a| a.name == "name").map(|a| a.value.as_str())
+    }
+    fn args(out: &mut String, list: &[Arg], reg: &Registry, info: &Info) {
+        for (i, a) in list.iter().enumerate() {
+            if i != 0 {
+                out.push_str(", ");
+            }
+            for x in a.ann.iter() {
+                let _ = write!(out, "{x}");
+            }
+            match &a.ty {
+                crate::ArgTy::Resource {
+                    ty: crate::ResTy::Of(rid),
+                    nullable,
+                    take,
+                } => {
+                    match info
+                        .interfaces
+                        .get(rid)
+                        .and_then(|e| name_of(&e.attrs))
+                        .or_else(|| reg.get(rid).and_then(|i| name_of(&i.ann)))
+                    {
+                        Some(n) => out.push_str(n),
+                        None => out.push_str(&hex::encode(&rid[..16])),
+                    }
+                    if *nullable {
+                        out.push('?');
+                    }
+                    if !*take {
+                        out.push('&');
+                    }
+                }
+                crate::ArgTy::Func(s) => {
+                    out.push('^');
+                    sig(out, s, reg, info);
+                }
+                t => {
+                    let _ = write!(out, "{t}");
+                }
+            }
+        }
+    }
+    fn sig(out: &mut String, s: &Sig, reg: &Registry, info: &Info) {
+        for x in s.ann.iter() {
+            let _ = write!(out, "{x}");
+        }
+        out.push('(');
+        args(out, &s.params, reg, info);
+        out.push_str(") -> (");
+        args(out, &s.rets, reg, info);
+        out.push(')');
+    }
+    let mut out = String::new();
+    for x in iface.ann.iter() {
+        let _ = write!(out, "{x}");
+    }
+    out.push('{');
+    for (i, (name, s)) in iface.methods.iter().enumerate() {
+        if i != 0 {
+            out.push(';');
+        }
+        out.push_str(name);
+        sig(&mut out, s, reg, info);
+    }
+    out.push('}');
+    out
+}
+
 // Legacy parsing functions for backward compatibility
 pub fn parse_entry(input: &str) -> IResult<&str, InfoEntry> {
     InfoEntry::parse(input)
@@ -753,4 +1364,544 @@ mod tests {
             assert_eq!(info.name(), None);
         }
     }
+
+    #[test]
+    fn test_cross_check() {
+        use crate::registry::Registry;
+
+        let (_, iface) = crate::parse_interface("{add(I32,I32) -> (I32);sub(I32,I32) -> (I32)}").unwrap();
+        let mut reg = Registry::default();
+        let rid = reg.insert(iface);
+        let missing = [0xabu8; 32];
+
+        let mut entry = InfoEntry::default();
+        let mut add = MethEntry::default();
+        add.attrs.push(Attr {
+            name: "doc".to_owned(),
+            value: "Adds".to_owned(),
+        });
+        entry.methods.insert("add".to_owned(), add);
+        entry.methods.insert("mul".to_owned(), MethEntry::default());
+
+        let mut info = Info::default();
+        info.interfaces.insert(rid, entry);
+        info.interfaces.insert(missing, InfoEntry::default());
+
+        let issues = info.cross_check(&reg);
+        assert_eq!(issues.len(), 2);
+        assert!(issues.contains(&CrossCheckIssue::UnknownInterface(missing)));
+        assert!(issues.contains(&CrossCheckIssue::UnknownMethod {
+            rid,
+            method: "mul".to_owned(),
+        }));
+
+        let strict = info.cross_check_strict(&reg);
+        assert_eq!(strict.len(), 3);
+        assert!(strict.contains(&CrossCheckIssue::UndocumentedMethod {
+            rid,
+            method: "sub".to_owned(),
+        }));
+    }
+
+    #[cfg(feature = "doc-attrs")]
+    #[test]
+    fn test_interfaces_by_name() {
+        let named = |n: &str| InfoEntry {
+            attrs: alloc::vec![Attr::from_name(n)],
+            ..Default::default()
+        };
+        let mut info = Info::default();
+        info.interfaces.insert([1; 32], named("Zeta"));
+        info.interfaces.insert([2; 32], InfoEntry::default());
+        info.interfaces.insert([3; 32], named("Alpha"));
+        info.interfaces.insert([0; 32], named("Alpha"));
+
+        let order: Vec<[u8; 32]> = info.interfaces_by_name().into_iter().map(|(id, _)| *id).collect();
+        assert_eq!(order, alloc::vec![[0; 32], [3; 32], [1; 32], [2; 32]]);
+    }
+
+    #[test]
+    fn test_parse_entry_streaming() {
+        let first = format!("{}: [\n root [name=a [b] \\]\n]", "ab".repeat(32));
+        let src = format!("{first}\n{}: [\n", "cd".repeat(32));
+        for i in 0..first.len() {
+            assert!(
+                matches!(Info::parse_entry_streaming(&src[..i]), Err(nom::Err::Incomplete(_))),
+                "prefix {:?} should be incomplete",
+                &src[..i]
+            );
+        }
+        let (rest, (id, entry)) = Info::parse_entry_streaming(&src).unwrap();
+        assert_eq!(id, [0xab; 32]);
+        assert_eq!(entry.attrs[0].value, "a [b] \\");
+        assert!(matches!(Info::parse_entry_streaming(rest), Err(nom::Err::Incomplete(_))));
+
+        // Entries read one at a time make up the same info
+        let (_, whole) = Info::parse_typed(&first).unwrap();
+        assert_eq!(whole.interfaces.get(&id), Some(&entry));
+
+        // A non-hex id is reported before the entry arrives
+        assert!(matches!(Info::parse_entry_streaming("abcg"), Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn test_parsing_malformed_interface_id() {
+        // 63 hex characters
+        let short = format!("{}: [\n root [name=x]\n]", "a".repeat(63));
+        match Info::parse_typed(&short) {
+            Err(nom::Err::Failure(e)) => {
+                assert!(matches!(e, PitError::BadHexLength { expected: 64, .. }));
+                assert_eq!(e.offset_in(&short), 0);
+            }
+            other => panic!("expected id failure, got {other:?}"),
+        }
+
+        // 64 characters, one of which is not hex
+        let bad = format!("  {}g{}: [\n root [name=x]\n]", "a".repeat(31), "b".repeat(32));
+        match Info::parse_typed(&bad) {
+            Err(nom::Err::Failure(e)) => {
+                assert!(matches!(e, PitError::BadHexLength { expected: 64, .. }));
+                assert_eq!(e.offset_in(&bad), 2);
+            }
+            other => panic!("expected id failure, got {other:?}"),
+        }
+
+        // No id at all
+        let empty = "\n  : [\n root [name=x]\n]";
+        match Info::parse_typed(empty) {
+            Err(nom::Err::Failure(e)) => {
+                assert!(matches!(e, PitError::BadHexLength { expected: 64, .. }));
+                assert_eq!(e.offset_in(empty), 3);
+            }
+            other => panic!("expected id failure, got {other:?}"),
+        }
+
+        // A well-formed id still parses
+        let good = format!("{}: [\n root [name=x]\n]", "a".repeat(64));
+        let (rest, info) = Info::parse(&good).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(info.interfaces.len(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let id = "ab".repeat(32);
+        let src = format!(
+            "{id}: [\n root [doc=a]\n method get [brief=b]\n param get 0 [name=x]\n]"
+        );
+        let info = Info::parse(&src).unwrap().1;
+        let json = serde_json::to_string(&info).unwrap();
+        assert!(json.starts_with(&format!("{{\"interfaces\":{{\"{id}\":")));
+        let back: Info = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, info);
+    }
+
+    #[cfg(feature = "doc-attrs")]
+    #[test]
+    fn test_doc_for_locale() {
+        let attr = |name: &str, value: &str| Attr {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        };
+        let entry = InfoEntry {
+            attrs: alloc::vec![
+                attr("doc", "Hello"),
+                attr("doc.fr", "Bonjour"),
+                attr("name.fr", "Calculatrice"),
+                attr("doc.fr-CA", "Allo"),
+            ],
+            methods: BTreeMap::new(),
+        };
+        assert_eq!(entry.doc_for_locale("fr"), Some("Bonjour"));
+        assert_eq!(entry.doc_for_locale("fr-CA"), Some("Allo"));
+        assert_eq!(entry.doc_for_locale("de"), Some("Hello"));
+        assert_eq!(entry.name_for_locale("fr"), Some("Calculatrice"));
+        assert_eq!(entry.name_for_locale("en"), None);
+        assert_eq!(entry.brief_for_locale("fr"), None);
+    }
+
+    #[cfg(feature = "doc-attrs")]
+    #[test]
+    fn test_method_params_typed() {
+        let sig: Sig = "(I32,I32,F64) -> (I32)".parse().unwrap();
+        let entry = InfoEntry::parse(
+            "method add [name=Addition]\n param add 0 [name=left]\n param add 1 [name=right]",
+        )
+        .unwrap()
+        .1;
+        let typed = method_params_typed(&sig, &entry.methods["add"]);
+        assert_eq!(
+            typed,
+            [
+                ("left".to_owned(), Arg::i32()),
+                ("right".to_owned(), Arg::i32()),
+                ("arg2".to_owned(), Arg::f64()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "doc-attrs")]
+    #[test]
+    fn test_expand_inline_docs() {
+        use crate::{ArgSide, parse_interface};
+        let b = parse_interface("{len() -> (I64)}").unwrap().1;
+        let b_rid = b.rid();
+        let a = parse_interface(&format!(
+            "{{store(I32,R{b}) -> ();fetch() -> (R{b}n,Rthis)}}",
+            b = hex::encode(b_rid)
+        ))
+        .unwrap()
+        .1;
+        let reg: Registry = [a.clone(), b].into_iter().collect();
+        let a_rid = a.rid();
+        let info = Info::parse(&format!(
+            "{}: [\n root [name=Store]\n]\n{}: [\n root [name=Buffer]\n root [brief=A growable byte buffer]\n]",
+            hex::encode(a_rid),
+            hex::encode(b_rid)
+        ))
+        .unwrap()
+        .1;
+
+        let doc = expand_inline_docs(&info, &reg, &a_rid).unwrap();
+        assert_eq!(doc.entry.name(), Some("Store"));
+        let inline = |method: &str, side, i| InlineDoc {
+            location: Location {
+                method: method.to_owned(),
+                path: alloc::vec![(side, i)],
+            },
+            rid: b_rid,
+            name: Some("Buffer".to_owned()),
+            brief: Some("A growable byte buffer".to_owned()),
+        };
+        assert_eq!(
+            doc.refs,
+            [inline("fetch", ArgSide::Return, 0), inline("store", ArgSide::Param, 1)]
+        );
+        assert!(expand_inline_docs(&info, &reg, &[0; 32]).is_none());
+    }
+
+    #[test]
+    fn test_validate_against() {
+        let (_, iface) = crate::parse_interface("{get(I32) -> (I64)}").unwrap();
+        let (_, good) =
+            InfoEntry::parse("method get [doc=g]\nparam get 0 [doc=k]\nreturn get 0 [doc=v]\n")
+                .unwrap();
+        assert_eq!(good.validate_against(&iface), Ok(()));
+
+        let (_, stale) = InfoEntry::parse("param get 2 [doc=k]\nmethod gone [doc=x]\n").unwrap();
+        let errors = stale.validate_against(&iface).unwrap_err();
+        assert_eq!(
+            errors,
+            alloc::vec![
+                InfoError::IndexOutOfRange {
+                    method: "get".to_owned(),
+                    side: ArgSide::Param,
+                    index: 2,
+                    len: 1,
+                },
+                InfoError::UnknownMethod("gone".to_owned()),
+            ]
+        );
+        assert_eq!(
+            alloc::string::ToString::to_string(&errors[0]),
+            "info documents param 2 of `get`, which has 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_ident_method_names() {
+        let src = "method get_value [doc=g]\nparam ns.method 0 [doc=p]\nreturn $impl 1 [doc=r]\n";
+        let (rest, entry) = InfoEntry::parse(src).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(entry.methods["get_value"].attrs[0].value, "g");
+        assert_eq!(entry.methods["ns.method"].params[&0].attrs[0].value, "p");
+        assert_eq!(entry.methods["$impl"].returns[&1].attrs[0].value, "r");
+
+        let (_, iface) = crate::parse_interface("{get_value() -> ()}").unwrap();
+        let (_, entry) = InfoEntry::parse("method get_value [doc=g]\n").unwrap();
+        assert_eq!(entry.validate_against(&iface), Ok(()));
+    }
+
+    #[test]
+    fn test_info_display_roundtrip() {
+        let entry = |doc: &str, method: &str| {
+            InfoEntry::parse(&alloc::format!(
+                "root [doc={doc}]\nmethod {method} [doc=m]\nparam {method} 0 [name=x]\n"
+            ))
+            .unwrap()
+            .1
+        };
+        let info = Info {
+            interfaces: [([1u8; 32], entry("first", "get")), ([2u8; 32], entry("second", "put"))]
+                .into_iter()
+                .collect(),
+        };
+        let text = alloc::string::ToString::to_string(&info);
+        assert_eq!(text.matches(": [\n").count(), 2);
+        let (rest, parsed) = Info::parse(&text).unwrap();
+        assert_eq!(rest.trim(), "");
+        assert_eq!(parsed, info);
+    }
+
+    #[test]
+    fn test_document() {
+        let (_, iface) =
+            crate::parse_interface("[v=1]{add(I32, I32) -> (I32);neg(I32) -> (I32)}").unwrap();
+        let (_, info) = InfoEntry::parse(
+            "root [doc=math]\nmethod add [doc=Adds]\nparam add 0 [name=lhs]\nparam add 1 [name=rhs]\nmethod mul [doc=gone]\n",
+        )
+        .unwrap();
+        let view = document(&iface, &info);
+        assert_eq!(view.ann, iface.ann);
+        assert_eq!(view.attrs, info.attrs);
+        let add = &view.methods["add"];
+        assert_eq!(add.params.len(), 2);
+        assert_eq!(add.params[1].arg, Arg::i32());
+        assert_eq!(add.params[1].entry.as_ref().unwrap().attrs[0].value, "rhs");
+        assert_eq!(add.returns[0].entry, None);
+        assert_eq!(view.methods["neg"].entry, None);
+        assert!(view.unknown.contains_key("mul"));
+
+        #[cfg(feature = "doc-attrs")]
+        {
+            assert_eq!(add.doc(), Some("Adds"));
+            assert_eq!(add.param_name(0), Some("lhs"));
+            assert_eq!(add.param_name(2), None);
+            assert_eq!(view.undocumented(), alloc::vec!["neg"]);
+        }
+    }
+
+    #[test]
+    fn test_skeleton_for() {
+        let (_, iface) =
+            crate::parse_interface("{add(I32, I32) -> (I32);reset() -> ();split(I64) -> (I32, I32)}")
+                .unwrap();
+        let skel = InfoEntry::skeleton_for(&iface);
+        assert!(skel.attrs.is_empty());
+        let shape: Vec<(&str, usize, usize)> = skel
+            .methods
+            .iter()
+            .map(|(n, m)| (n.as_str(), m.params.len(), m.returns.len()))
+            .collect();
+        assert_eq!(shape, alloc::vec![("add", 2, 1), ("reset", 0, 0), ("split", 1, 2)]);
+        assert!(skel.methods.values().all(|m| m.attrs.is_empty()));
+        assert_eq!(skel.validate_against(&iface), Ok(()));
+    }
+
+    #[test]
+    fn test_pretty_with_registry() {
+        let (_, widget) = crate::parse_interface("{draw() -> ()}").unwrap();
+        let (_, gadget) = crate::parse_interface("[name=Gadget]{spin() -> ()}").unwrap();
+        let mut reg = Registry::default();
+        let widget_id = reg.insert(widget);
+        let gadget_id = reg.insert(gadget);
+        let other = [0x5a; 32];
+        let mut info = Info::default();
+        info.interfaces.insert(
+            widget_id,
+            InfoEntry::parse("root [name=Widget]\n").unwrap().1,
+        );
+        let (_, iface) = crate::parse_interface(&alloc::format!(
+            "{{show(R{}&, [doc=g]R{}n) -> (R{}, I32)}}",
+            hex::encode(widget_id),
+            hex::encode(gadget_id),
+            hex::encode(other)
+        ))
+        .unwrap();
+        assert_eq!(
+            pretty_with_registry(&iface, &reg, &info),
+            alloc::format!(
+                "{{show(Widget&, [doc=g]Gadget?) -> ({}, I32)}}",
+                "5a".repeat(16)
+            )
+        );
+    }
+
+    #[test]
+    fn test_prune_empty() {
+        let (_, iface) =
+            crate::parse_interface("{add(I32, I32) -> (I32);neg(I32) -> (I32)}").unwrap();
+        let mut entry = InfoEntry::skeleton_for(&iface);
+        let add = entry.methods.get_mut("add").unwrap();
+        add.params.get_mut(&1).unwrap().attrs.push(Attr {
+            name: "name".to_owned(),
+            value: "rhs".to_owned(),
+        });
+        let mut info = Info::default();
+        info.interfaces.insert([1; 32], entry);
+        info.interfaces.insert([2; 32], InfoEntry::default());
+        info.prune_empty();
+
+        let entry = &info.interfaces[&[1; 32]];
+        assert_eq!(entry.methods.keys().collect::<Vec<_>>(), ["add"]);
+        let add = &entry.methods["add"];
+        assert_eq!(add.params.keys().collect::<Vec<_>>(), [&1]);
+        assert!(add.returns.is_empty());
+        assert!(info.interfaces.contains_key(&[2; 32]));
+        assert_eq!(
+            alloc::string::ToString::to_string(entry),
+            "param add 1 [name=rhs]\n"
+        );
+    }
+
+    #[test]
+    fn test_multiline_doc() {
+        let src = "method add [doc=Adds two numbers.\n\nWraps on overflow.]\nparam add 0 [name=lhs]\n";
+        let (rest, entry) = InfoEntry::parse(src).unwrap();
+        assert_eq!(rest, "");
+        let add = &entry.methods["add"];
+        assert_eq!(add.attrs[0].value, "Adds two numbers.\n\nWraps on overflow.");
+        assert_eq!(add.params[&0].attrs[0].value, "lhs");
+
+        let rendered = alloc::string::ToString::to_string(&entry);
+        assert_eq!(rendered, src);
+        assert_eq!(InfoEntry::parse(&rendered).unwrap().1, entry);
+    }
+
+    #[test]
+    fn test_merge_for() {
+        use crate::registry::Registry;
+
+        let (_, iface) = crate::parse_interface("{add(I32,I32) -> (I32)}").unwrap();
+        let mut reg = Registry::default();
+        let rid = reg.insert(iface);
+        let doc = |method: &str| {
+            let mut entry = InfoEntry::default();
+            entry.methods.insert(
+                method.to_owned(),
+                MethEntry {
+                    attrs: alloc::vec![Attr {
+                        name: "doc".to_owned(),
+                        value: "Docs".to_owned(),
+                    }],
+                    ..Default::default()
+                },
+            );
+            let mut info = Info::default();
+            info.interfaces.insert(rid, entry);
+            info
+        };
+
+        let merged = doc("add").merge_for(&reg, doc("add")).unwrap();
+        assert_eq!(merged, doc("add"));
+
+        let err = doc("add").merge_for(&reg, doc("mul")).unwrap_err();
+        assert_eq!(
+            err,
+            alloc::vec![DanglingDoc {
+                rid,
+                method: "mul".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_info_line_parse() {
+        let attr = |name: &str, value: &str| Attr {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        };
+        assert_eq!(
+            InfoLine::parse("root [name=Files]\nmethod x [a=b]"),
+            Ok(("method x [a=b]", InfoLine::Root(attr("name", "Files"))))
+        );
+        assert_eq!(
+            InfoLine::parse("  method read [doc=Reads]"),
+            Ok(("", InfoLine::Method("read".to_owned(), attr("doc", "Reads"))))
+        );
+        assert_eq!(
+            InfoLine::parse("param read 1 [name=len]"),
+            Ok(("", InfoLine::Param("read".to_owned(), 1, attr("name", "len"))))
+        );
+        assert_eq!(
+            InfoLine::parse("return read 0 [unit=bytes]"),
+            Ok(("", InfoLine::Return("read".to_owned(), 0, attr("unit", "bytes"))))
+        );
+        assert!(InfoLine::parse("field read [x=y]").is_err());
+    }
+
+    #[test]
+    fn test_info_line_display() {
+        let attr = |name: &str, value: &str| Attr {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        };
+        let lines = [
+            InfoLine::Root(attr("name", "Files")),
+            InfoLine::Method("read".to_owned(), attr("doc", "Reads [some] bytes")),
+            InfoLine::Param("read".to_owned(), 12, attr("name", "len")),
+            InfoLine::Return("read".to_owned(), 0, attr("unit", "bytes")),
+        ];
+        let mut entry = alloc::string::String::new();
+        for line in lines.iter() {
+            let text = alloc::string::ToString::to_string(line);
+            assert_eq!(InfoLine::parse(&text), Ok(("", line.clone())));
+            entry.push_str(&text);
+            entry.push('\n');
+        }
+        assert_eq!(
+            alloc::string::ToString::to_string(&InfoEntry::parse(&entry).unwrap().1),
+            entry
+        );
+        assert_eq!(
+            alloc::string::ToString::to_string(&lines[2]),
+            "param read 12 [name=len]"
+        );
+    }
+
+    #[test]
+    fn test_info_line_index() {
+        let (_, entry) =
+            InfoEntry::parse("param f 10 [name=a]\nparam f 255 [name=b]\nreturn f 0 [name=c]\n")
+                .unwrap();
+        let f = &entry.methods["f"];
+        assert_eq!(f.params.keys().collect::<Vec<_>>(), [&10, &255]);
+        assert_eq!(f.returns.keys().collect::<Vec<_>>(), [&0]);
+
+        for (line, token) in [
+            ("param f 0x10 [name=a]", "0x10"),
+            ("return f 1a [name=a]", "1a"),
+            ("param f 99999999999999999999999 [name=a]", "99999999999999999999999"),
+        ] {
+            let err = PitError::from_nom(InfoLine::parse(line).unwrap_err());
+            assert!(matches!(err, PitError::InvalidIndex { .. }), "{line}: {err:?}");
+            assert_eq!(err.token_in(line), Some(token));
+        }
+
+        // The failure is not swallowed by the surrounding entry and info parsers
+        let src = alloc::format!("{}: [\nparam f x1 [name=a]\n]", "00".repeat(32));
+        let err = PitError::from_nom(Info::parse_typed(&src).unwrap_err());
+        assert_eq!(err.token_in(&src), Some("x1"));
+    }
+
+    #[test]
+    fn test_set_remove_attr() {
+        fn names(attrs: &[Attr]) -> Vec<&str> {
+            attrs.iter().map(|a| a.name.as_str()).collect()
+        }
+        let mut entry = ParamEntry::default();
+        entry.set_attr("name", "rhs");
+        entry.set_attr("doc", "Right operand");
+        entry.set_attr("since", "1.0");
+        assert_eq!(names(entry.attrs()), ["doc", "name", "since"]);
+
+        entry.set_attr("name", "other");
+        assert_eq!(names(entry.attrs()), ["doc", "name", "since"]);
+        assert_eq!(entry.attrs()[1].value, "other");
+
+        assert_eq!(entry.remove_attr("name").as_deref(), Some("other"));
+        assert_eq!(entry.remove_attr("name"), None);
+        assert_eq!(names(entry.attrs()), ["doc", "since"]);
+
+        let mut meth = MethEntry::default();
+        meth.set_attr("b", "2");
+        meth.set_attr("a", "1");
+        assert_eq!(names(meth.attrs()), ["a", "b"]);
+        let mut entry = InfoEntry::default();
+        entry.set_attr("z", "1");
+        entry.set_attr("m", "2");
+        assert_eq!(names(entry.attrs()), ["m", "z"]);
+        assert_eq!(entry.remove_attr("z").as_deref(), Some("1"));
+    }
 }
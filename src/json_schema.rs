@@ -0,0 +1,158 @@
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::{Arg, ArgTy, Attr, Interface, Sig};
+
+/// Writes `s` as a JSON string literal.
+fn string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes `,"description":...` from the `doc` annotation, else the `brief` one.
+#[cfg(feature = "doc-attrs")]
+fn description(out: &mut String, ann: &[Attr]) {
+    let desc = ann
+        .iter()
+        .find_map(Attr::as_doc)
+        .or_else(|| ann.iter().find_map(Attr::as_brief));
+    if let Some(d) = desc {
+        out.push_str(",\"description\":");
+        string(out, d);
+    }
+}
+#[cfg(not(feature = "doc-attrs"))]
+fn description(_: &mut String, _: &[Attr]) {}
+
+fn arg(out: &mut String, a: &Arg) {
+    match &a.ty {
+        ArgTy::I32 => out.push_str("{\"type\":\"integer\",\"format\":\"int32\""),
+        ArgTy::I64 => out.push_str("{\"type\":\"integer\",\"format\":\"int64\""),
+        ArgTy::F32 => out.push_str("{\"type\":\"number\",\"format\":\"float\""),
+        ArgTy::F64 => out.push_str("{\"type\":\"number\",\"format\":\"double\""),
+        ArgTy::V128 => out.push_str("{\"type\":\"string\",\"format\":\"v128\""),
+        ArgTy::I8 => out.push_str("{\"type\":\"integer\",\"format\":\"int8\""),
+        ArgTy::I16 => out.push_str("{\"type\":\"integer\",\"format\":\"int16\""),
+        ArgTy::U8 => out.push_str("{\"type\":\"integer\",\"format\":\"uint8\""),
+        ArgTy::U16 => out.push_str("{\"type\":\"integer\",\"format\":\"uint16\""),
+        ArgTy::Resource { nullable, .. } => {
+            if *nullable {
+                out.push_str("{\"type\":[\"string\",\"null\"],\"format\":\"resource-id\"");
+            } else {
+                out.push_str("{\"type\":\"string\",\"format\":\"resource-id\"");
+            }
+        }
+        ArgTy::Func(s) => {
+            sig(out, s);
+            out.pop();
+        }
+    }
+    description(out, &a.ann);
+    out.push('}');
+}
+
+fn args(out: &mut String, list: &[Arg]) {
+    let _ = write!(
+        out,
+        "{{\"type\":\"array\",\"minItems\":{0},\"maxItems\":{0},\"prefixItems\":[",
+        list.len()
+    );
+    for (i, a) in list.iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        arg(out, a);
+    }
+    out.push_str("]}");
+}
+
+fn sig(out: &mut String, s: &Sig) {
+    out.push_str("{\"type\":\"object\",\"properties\":{\"params\":");
+    args(out, &s.params);
+    out.push_str(",\"returns\":");
+    args(out, &s.rets);
+    out.push_str("},\"required\":[\"params\",\"returns\"]");
+    description(out, &s.ann);
+    out.push('}');
+}
+
+impl Interface {
+    /// Renders a JSON Schema (draft 2020-12) describing the interface.
+    ///
+    /// The schema is an object with one property per method. Each method is an object
+    /// with `params` and `returns` arrays whose `prefixItems` describe the arguments in
+    /// order. Integers map to `integer` and `F32`/`F64` to `number`, with the width as
+    /// `format` (`int8`, `int16`, `uint8`, `uint16`, `int32`, `int64`, `float`, `double`).
+    /// Resources are strings of `format` `resource-id`, also allowing `null` when
+    /// nullable, `V128` is a string of `format` `v128`, and a function argument is
+    /// described like a method. With `doc-attrs`, `doc` (or else `brief`) annotations
+    /// become `description` fields.
+    pub fn to_json_schema(&self) -> String {
+        let mut out = String::from(
+            "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"type\":\"object\"",
+        );
+        description(&mut out, &self.ann);
+        out.push_str(",\"properties\":{");
+        for (i, (name, s)) in self.methods.iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            string(&mut out, name);
+            out.push(':');
+            sig(&mut out, s);
+        }
+        out.push_str("}}");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_interface;
+
+    #[test]
+    fn test_to_json_schema() {
+        let (_, iface) = parse_interface(
+            "[doc=A \"counter\"]{add[brief=Adds]([doc=Left]I32,I64) -> (F64);get() -> (Rthisn);each(^(F32) -> ()) -> ()}",
+        )
+        .unwrap();
+        let schema: serde_json::Value = serde_json::from_str(&iface.to_json_schema()).unwrap();
+        let add = &schema["properties"]["add"];
+        assert_eq!(add["type"], "object");
+        let params = &add["properties"]["params"];
+        assert_eq!(params["maxItems"], 2);
+        assert_eq!(params["prefixItems"][0]["type"], "integer");
+        assert_eq!(params["prefixItems"][0]["format"], "int32");
+        assert_eq!(params["prefixItems"][1]["format"], "int64");
+        assert_eq!(add["properties"]["returns"]["prefixItems"][0]["format"], "double");
+
+        let ret = &schema["properties"]["get"]["properties"]["returns"]["prefixItems"][0];
+        assert_eq!(ret["format"], "resource-id");
+        assert_eq!(ret["type"][1], "null");
+
+        let func = &schema["properties"]["each"]["properties"]["params"]["prefixItems"][0];
+        assert_eq!(func["properties"]["params"]["prefixItems"][0]["format"], "float");
+
+        #[cfg(feature = "doc-attrs")]
+        {
+            assert_eq!(schema["description"], "A \"counter\"");
+            assert_eq!(add["description"], "Adds");
+            assert_eq!(params["prefixItems"][0]["description"], "Left");
+        }
+        #[cfg(not(feature = "doc-attrs"))]
+        assert!(add.get("description").is_none());
+    }
+}
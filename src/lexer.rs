@@ -0,0 +1,443 @@
+use crate::PitError;
+
+/// Byte range of a token within the tokenized input.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct Span {
+    /// Offset of the first byte.
+    pub start: usize,
+    /// Offset one past the last byte.
+    pub end: usize,
+}
+impl Span {
+    /// Returns the text covered by this span.
+    pub fn text<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.start..self.end]
+    }
+}
+
+/// Kind of a lexical token.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// `{`
+    LBrace,
+    /// `}`
+    RBrace,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `,`
+    Comma,
+    /// `;`
+    Semicolon,
+    /// `->`
+    Arrow,
+    /// `[`, opening an attribute.
+    AttrOpen,
+    /// `]`, closing an attribute.
+    AttrClose,
+    /// Attribute name, with escapes left in place.
+    AttrName,
+    /// `=` between an attribute name and its value.
+    AttrEq,
+    /// Attribute value, with escapes left in place.
+    AttrValue,
+    /// A method name.
+    Ident,
+    /// A primitive argument type, long (`I32`) or compact (`i`).
+    Primitive,
+    /// `^`, starting a function-typed argument.
+    FuncPrefix,
+    /// `R`, starting a resource argument.
+    ResourcePrefix,
+    /// The resource after `R`: `this`, `@{name}`, `~b64...~` or 64 hex digits.
+    ///
+    /// Absent for `ResTy::None`. A bare `~b64...~` or hex id in argument position, the
+    /// opt-in shorthand for an owned resource, is a `ResourceId` with no `ResourcePrefix`;
+    /// the tokenizer accepts it whether or not the parser would.
+    ResourceId,
+    /// `n`, marking a resource nullable.
+    Nullable,
+    /// `&`, marking a resource borrowed.
+    Borrow,
+}
+
+/// A token and where it was found.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct Token {
+    /// What was found.
+    pub kind: TokenKind,
+    /// Where it was found.
+    pub span: Span,
+}
+
+/// Splits interface text into tokens, skipping whitespace.
+///
+/// Tokens follow the grammar accepted by [`parse_interface`](crate::parse_interface), so
+/// `R` and primitive types are only recognized where an argument is expected. Iteration
+/// stops after the first error.
+pub fn tokenize(input: &str) -> impl Iterator<Item = Result<Token, PitError>> + '_ {
+    Lexer {
+        input,
+        pos: 0,
+        pending: [None; 4],
+        expect_arg: false,
+        depth: 0,
+        done: false,
+    }
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+    /// Tokens already lexed but not yet returned, in order.
+    pending: [Option<Token>; 4],
+    /// Whether the next non-attribute token is the head of an argument.
+    expect_arg: bool,
+    /// Parenthesis nesting depth.
+    depth: usize,
+    done: bool,
+}
+
+impl Lexer<'_> {
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+    fn error(&self, e: impl FnOnce(usize) -> PitError) -> PitError {
+        e(self.input.len() - self.pos)
+    }
+    fn take(&mut self, kind: TokenKind, len: usize) -> Token {
+        let span = Span {
+            start: self.pos,
+            end: self.pos + len,
+        };
+        self.pos += len;
+        Token { kind, span }
+    }
+    fn push(&mut self, t: Token) {
+        if let Some(slot) = self.pending.iter_mut().find(|p| p.is_none()) {
+            *slot = Some(t);
+        }
+    }
+    fn pop(&mut self) -> Option<Token> {
+        let t = self.pending[0].take()?;
+        self.pending.rotate_left(1);
+        Some(t)
+    }
+    /// Lexes a whole attribute, queueing everything after the `[`.
+    fn attr(&mut self) -> Result<Token, PitError> {
+        let open = self.take(TokenKind::AttrOpen, 1);
+        self.skip_ws();
+        let name_len = escaped_len(self.rest(), |c| c == '=' || c == ']');
+        let trimmed = self.rest()[..name_len].trim_end().len();
+        if trimmed != 0 {
+            let t = self.take(TokenKind::AttrName, trimmed);
+            self.push(t);
+        }
+        self.skip_ws();
+        if self.rest().starts_with('=') {
+            let t = self.take(TokenKind::AttrEq, 1);
+            self.push(t);
+            self.skip_ws();
+            let len = balanced_len(self.rest()).ok_or_else(|| {
+                self.error(|remaining| PitError::ExpectedChar {
+                    expected: ']',
+                    remaining,
+                })
+            })?;
+            if len != 0 {
+                let t = self.take(TokenKind::AttrValue, len);
+                self.push(t);
+            }
+        }
+        if !self.rest().starts_with(']') {
+            return Err(self.error(|remaining| PitError::ExpectedChar {
+                expected: ']',
+                remaining,
+            }));
+        }
+        let t = self.take(TokenKind::AttrClose, 1);
+        self.push(t);
+        Ok(open)
+    }
+    /// Lexes a resource argument, queueing everything after the `R`.
+    fn resource(&mut self) -> Token {
+        let prefix = self.take(TokenKind::ResourcePrefix, 1);
+        let id = resource_id_len(self.rest());
+        if id != 0 {
+            let t = self.take(TokenKind::ResourceId, id);
+            self.push(t);
+        }
+        if self.rest().starts_with('n') {
+            let t = self.take(TokenKind::Nullable, 1);
+            self.push(t);
+        }
+        if self.rest().starts_with('&') {
+            let t = self.take(TokenKind::Borrow, 1);
+            self.push(t);
+        }
+        prefix
+    }
+    fn arg_head(&mut self) -> Result<Token, PitError> {
+        let rest = self.rest();
+        if rest.starts_with('R') {
+            return Ok(self.resource());
+        }
+        if rest.starts_with('^') {
+            return Ok(self.take(TokenKind::FuncPrefix, 1));
+        }
+        let bare = resource_id_len(rest);
+        if bare != 0 && !rest.starts_with("this") && !rest.starts_with('@') {
+            return Ok(self.take(TokenKind::ResourceId, bare));
+        }
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        match &rest[..len] {
+            "i" | "l" | "f" | "d" | "I32" | "I64" | "F32" | "F64" | "V128" | "I8" | "I16"
+            | "U8" | "U16" => Ok(self.take(TokenKind::Primitive, len)),
+            _ => Err(self.error(|remaining| PitError::UnknownArgType { remaining })),
+        }
+    }
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+    fn next_token(&mut self) -> Option<Result<Token, PitError>> {
+        if let Some(t) = self.pop() {
+            return Some(Ok(t));
+        }
+        self.skip_ws();
+        let c = self.rest().chars().next()?;
+        let simple = match c {
+            '{' => Some(TokenKind::LBrace),
+            '}' => Some(TokenKind::RBrace),
+            ';' => Some(TokenKind::Semicolon),
+            _ => None,
+        };
+        if let Some(kind) = simple {
+            return Some(Ok(self.take(kind, 1)));
+        }
+        if c == '[' {
+            return Some(self.attr());
+        }
+        if c == ')' && self.depth != 0 {
+            self.depth -= 1;
+            self.expect_arg = false;
+            return Some(Ok(self.take(TokenKind::RParen, 1)));
+        }
+        if self.expect_arg {
+            self.expect_arg = false;
+            return Some(self.arg_head());
+        }
+        match c {
+            '(' => {
+                self.depth += 1;
+                self.expect_arg = true;
+                Some(Ok(self.take(TokenKind::LParen, 1)))
+            }
+            ',' if self.depth != 0 => {
+                self.expect_arg = true;
+                Some(Ok(self.take(TokenKind::Comma, 1)))
+            }
+            '-' if self.rest().starts_with("->") => Some(Ok(self.take(TokenKind::Arrow, 2))),
+            _ => match ident_len(self.rest()) {
+                0 => Some(Err(self.error(|remaining| PitError::Nom {
+                    kind: nom::error::ErrorKind::AlphaNumeric,
+                    remaining,
+                }))),
+                len => Some(Ok(self.take(TokenKind::Ident, len))),
+            },
+        }
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, PitError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let t = self.next_token();
+        if !matches!(t, Some(Ok(_))) {
+            self.done = true;
+        }
+        t
+    }
+}
+
+/// Length of the resource id at the start of `a`, or 0.
+fn resource_id_len(a: &str) -> usize {
+    if a.starts_with("this") {
+        4
+    } else if let Some(end) = a.strip_prefix("@{").and_then(|r| r.find('}')) {
+        2 + end + 1
+    } else if let Some(end) = a.strip_prefix("~b64").and_then(|r| r.find('~')) {
+        4 + end + 1
+    } else if a.len() >= 64 && a.as_bytes()[..64].iter().all(u8::is_ascii_hexdigit) {
+        64
+    } else {
+        0
+    }
+}
+
+/// Length of the [`ident`](crate::ident) at the start of `a`, or 0.
+fn ident_len(a: &str) -> usize {
+    a.find(|c: char| !c.is_alphanumeric() && !['_', '$', '.'].contains(&c))
+        .unwrap_or(a.len())
+}
+
+/// Length of the text before the first unescaped character matching `stop`.
+fn escaped_len(a: &str, stop: impl Fn(char) -> bool) -> usize {
+    let mut it = a.char_indices();
+    while let Some((i, c)) = it.next() {
+        if c == '\\' {
+            it.next();
+        } else if stop(c) {
+            return i;
+        }
+    }
+    a.len()
+}
+
+/// Length of the attribute value at the start of `a`, as read by
+/// [`parse_balanced`](crate::parse_balanced), or `None` if no closing `]` is found.
+fn balanced_len(a: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut it = a.char_indices().peekable();
+    while let Some((i, c)) = it.next() {
+        match c {
+            '\\' if it.peek().is_some_and(|(_, n)| ['[', ']', '\\'].contains(n)) => {
+                it.next();
+            }
+            '[' => depth += 1,
+            ']' if depth == 0 => return Some(i),
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+    use alloc::vec::Vec;
+
+    fn lex(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .map(|t| {
+                let t = t.unwrap();
+                (t.kind, t.span.text(input))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_interface() {
+        let rid = "ab".repeat(32);
+        let src =
+            alloc::format!("[doc=a [b]] {{Read(I32, R{rid}n&)->(^(i)->());\n close(Rthis) -> ()}}");
+        assert_eq!(
+            lex(&src),
+            [
+                (AttrOpen, "["),
+                (AttrName, "doc"),
+                (AttrEq, "="),
+                (AttrValue, "a [b]"),
+                (AttrClose, "]"),
+                (LBrace, "{"),
+                (Ident, "Read"),
+                (LParen, "("),
+                (Primitive, "I32"),
+                (Comma, ","),
+                (ResourcePrefix, "R"),
+                (ResourceId, rid.as_str()),
+                (Nullable, "n"),
+                (Borrow, "&"),
+                (RParen, ")"),
+                (Arrow, "->"),
+                (LParen, "("),
+                (FuncPrefix, "^"),
+                (LParen, "("),
+                (Primitive, "i"),
+                (RParen, ")"),
+                (Arrow, "->"),
+                (LParen, "("),
+                (RParen, ")"),
+                (RParen, ")"),
+                (Semicolon, ";"),
+                (Ident, "close"),
+                (LParen, "("),
+                (ResourcePrefix, "R"),
+                (ResourceId, "this"),
+                (RParen, ")"),
+                (Arrow, "->"),
+                (LParen, "("),
+                (RParen, ")"),
+                (RBrace, "}"),
+            ]
+        );
+        let spans: Vec<Span> = tokenize(&src).map(|t| t.unwrap().span).collect();
+        assert_eq!(spans[0], Span { start: 0, end: 1 });
+        assert_eq!(spans[3], Span { start: 5, end: 10 });
+        assert_eq!(spans[5], Span { start: 12, end: 13 });
+        assert!(spans.windows(2).all(|w| w[0].end <= w[1].start));
+    }
+
+    #[test]
+    fn test_tokenize_flag_and_none() {
+        assert_eq!(
+            lex("[pure]{f(R&)->()}"),
+            [
+                (AttrOpen, "["),
+                (AttrName, "pure"),
+                (AttrClose, "]"),
+                (LBrace, "{"),
+                (Ident, "f"),
+                (LParen, "("),
+                (ResourcePrefix, "R"),
+                (Borrow, "&"),
+                (RParen, ")"),
+                (Arrow, "->"),
+                (LParen, "("),
+                (RParen, ")"),
+                (RBrace, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_error_stops() {
+        let src = "{f(Q)->()}";
+        let toks: Vec<_> = tokenize(src).collect();
+        assert_eq!(toks.len(), 4);
+        let err = toks[3].unwrap_err();
+        assert_eq!(err, PitError::UnknownArgType { remaining: 7 });
+        assert_eq!(err.offset_in(src), 3);
+    }
+
+    #[test]
+    fn test_tokenize_bare_id() {
+        let rid = "fd".repeat(32);
+        let src = alloc::format!("{{f({rid}, f) -> ()}}");
+        assert_eq!(
+            lex(&src),
+            [
+                (LBrace, "{"),
+                (Ident, "f"),
+                (LParen, "("),
+                (ResourceId, rid.as_str()),
+                (Comma, ","),
+                (Primitive, "f"),
+                (RParen, ")"),
+                (Arrow, "->"),
+                (LParen, "("),
+                (RParen, ")"),
+                (RBrace, "}"),
+            ]
+        );
+    }
+}
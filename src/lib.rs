@@ -6,7 +6,8 @@ extern crate alloc;
 /// This crate is `no_std` and uses `alloc` for heap-allocated types.
 use alloc::{
     borrow::ToOwned,
-    collections::BTreeMap,
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
     format,
     string::{String, ToString},
     vec,
@@ -14,18 +15,21 @@ use alloc::{
 };
 use base64::Engine;
 use core::fmt::{self, Display};
+use core::str::FromStr;
 use core::{convert::identity as tuple, fmt::Formatter};
 use derive_more::Display;
 use nom::{
     AsChar, IResult, Input, Parser,
-    bytes::complete::{is_not, tag, take, take_while_m_n},
-    character::complete::{alpha1, char, multispace0, none_of, space0},
+    bytes::complete::{is_not, tag, take_while_m_n},
+    character::complete::{alpha1, anychar, char, multispace0, none_of, space0},
     combinator::opt,
-    error::Error,
     multi::{many0, separated_list0},
-    sequence::delimited,
+    sequence::{delimited, preceded},
+};
+use sha3::{
+    Digest, Sha3_256,
+    digest::{Output, Update},
 };
-use sha3::{Digest, Sha3_256};
 #[path = "generics.rs"]
 mod _generics;
 #[path = "pcode.rs"]
@@ -46,18 +50,49 @@ pub mod generics {
 use crate::util::WriteUpdate;
 /// Utility functions and types.
 pub mod util;
+/// Typed parse errors.
+pub mod error;
+pub use error::PitError;
+/// Tokenizer for interface text.
+pub mod lexer;
+pub use lexer::tokenize;
+/// Runs a [`PitError`] parser with nom's default error type, for the legacy signatures.
+pub(crate) fn legacy<'a, T>(
+    a: &'a str,
+    p: impl FnOnce(&'a str) -> IResult<&'a str, T, PitError>,
+) -> IResult<&'a str, T> {
+    p(a).map_err(|e| e.map(|e| e.into_nom_error(a)))
+}
+/// Matches the literal `t`, failing with [`PitError::ExpectedToken`].
+fn token<'a>(t: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, PitError> {
+    move |a: &'a str| match a.strip_prefix(t) {
+        Some(rest) => Ok((rest, &a[..t.len()])),
+        None => Err(nom::Err::Error(PitError::ExpectedToken {
+            expected: t,
+            remaining: a.len(),
+        })),
+    }
+}
 /// Parses an identifier from a string slice.
 ///
 /// Identifiers may contain alphanumeric characters, '_', '$', and '.'.
 /// Returns a tuple of the remaining input and the parsed identifier.
-pub fn ident(a: &str) -> IResult<&str, &str> {
+pub fn ident_typed(a: &str) -> IResult<&str, &str, PitError> {
     return a.split_at_position1_complete(
         |a| !a.is_alphanum() && !(['_', '$', '.'].into_iter().any(|x| x == a)),
         nom::error::ErrorKind::AlphaNumeric,
     );
 }
+/// [`ident_typed`] with nom's default error type.
+pub fn ident(a: &str) -> IResult<&str, &str> {
+    legacy(a, ident_typed)
+}
 /// Attribute key-value pair.
 /// Represents a key-value attribute, used for metadata and annotations throughout the interface system.
+///
+/// The fields can be set to anything; [`Attr::new`] additionally checks that the name
+/// follows the [`ident`] rule, so it renders without escapes.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct Attr {
     /// The attribute name.
@@ -65,17 +100,48 @@ pub struct Attr {
     /// The attribute value.
     pub value: String,
 }
+/// Error returned by [`Attr::new`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum AttrError {
+    /// The name is not a valid identifier.
+    InvalidName(String),
+}
+impl Display for AttrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrError::InvalidName(name) => write!(f, "invalid attribute name `{name}`"),
+        }
+    }
+}
+impl core::error::Error for AttrError {}
 
 /// Represents the arity (number and structure of parameters) for generics.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
 pub struct Arity {
     pub to_fill: BTreeMap<String, Arity>,
 }
+/// A change to one slot of an [`Arity`], from [`Arity::diff`].
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum ArityOp {
+    /// A slot only in the new arity.
+    Added { slot: String, arity: Arity },
+    /// A slot only in the old arity.
+    Removed { slot: String, arity: Arity },
+    /// A slot in both whose nested arity changed as described by `ops`.
+    Nested { slot: String, ops: Vec<ArityOp> },
+}
 /// Display implementation for Arity, formats as a generic parameter list.
+///
+/// Pairs are separated by a space, e.g. `<K <> V <T <>>>`, which [`Arity::parse`] reads back.
 impl Display for Arity {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "<")?;
-        for (a, b) in &self.to_fill {
+        for (i, (a, b)) in self.to_fill.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
             write!(f, "{a} {b}")?;
         }
         write!(f, ">")?;
@@ -93,14 +159,16 @@ impl Arity {
     /// Parses a generic parameter arity from a string.
     ///
     /// Returns a tuple of the remaining input and the parsed `Arity`.
-    pub fn parse(a: &str) -> IResult<&str, Self> {
-        let (a, c) = space0
-            .and_then(delimited(
+    pub fn parse_typed(a: &str) -> IResult<&str, Self, PitError> {
+        let (a, c) = preceded(
+            space0,
+            delimited(
                 tag("<"),
-                many0(space0.and_then((ident, space0.and_then(Arity::parse)))),
-                tag(">"),
-            ))
-            .parse(a)?;
+                many0(preceded(space0, (ident_typed, Arity::parse_typed))),
+                preceded(space0, tag(">")),
+            ),
+        )
+        .parse(a)?;
         return Ok((
             a,
             Arity {
@@ -108,9 +176,140 @@ impl Arity {
             },
         ));
     }
+    /// [`Arity::parse_typed`] with nom's default error type.
+    pub fn parse(a: &str) -> IResult<&str, Self> {
+        legacy(a, Arity::parse_typed)
+    }
+    /// Structurally matches two arities, returning the unified arity.
+    ///
+    /// Both must have the same slot names at every level; a slot that is nested on one side
+    /// but not the other is a depth mismatch. Returns `None` on any mismatch.
+    pub fn unify(&self, other: &Arity) -> Option<Arity> {
+        if self.to_fill.len() != other.to_fill.len() {
+            return None;
+        }
+        let to_fill = self
+            .to_fill
+            .iter()
+            .map(|(k, a)| Some((k.clone(), a.unify(other.to_fill.get(k)?)?)))
+            .collect::<Option<_>>()?;
+        Some(Arity { to_fill })
+    }
+    /// Lists the slot changes that turn `self` into `other`.
+    ///
+    /// Slots are matched by name, so a renamed slot shows up as a removal and an
+    /// addition. Ops follow slot name order.
+    pub fn diff(&self, other: &Arity) -> Vec<ArityOp> {
+        let mut ops = vec![];
+        let names: BTreeSet<&String> = self.to_fill.keys().chain(other.to_fill.keys()).collect();
+        for name in names {
+            match (self.to_fill.get(name), other.to_fill.get(name)) {
+                (Some(old), None) => ops.push(ArityOp::Removed {
+                    slot: name.clone(),
+                    arity: old.clone(),
+                }),
+                (None, Some(new)) => ops.push(ArityOp::Added {
+                    slot: name.clone(),
+                    arity: new.clone(),
+                }),
+                (Some(old), Some(new)) => {
+                    let nested = old.diff(new);
+                    if !nested.is_empty() {
+                        ops.push(ArityOp::Nested {
+                            slot: name.clone(),
+                            ops: nested,
+                        });
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        ops
+    }
+    /// Encodes the arity as canonical bytes, suitable as a map key.
+    ///
+    /// Each level is the LEB128 slot count followed by, per slot in name order, the
+    /// LEB128 name length, the UTF-8 name and the nested arity. Equal arities always give
+    /// equal bytes.
+    pub fn to_key_bytes(&self) -> Vec<u8> {
+        fn leb(out: &mut Vec<u8>, mut v: usize) {
+            while v >= 0x80 {
+                out.push(v as u8 | 0x80);
+                v >>= 7;
+            }
+            out.push(v as u8);
+        }
+        fn go(a: &Arity, out: &mut Vec<u8>) {
+            leb(out, a.to_fill.len());
+            for (name, nested) in &a.to_fill {
+                leb(out, name.len());
+                out.extend_from_slice(name.as_bytes());
+                go(nested, out);
+            }
+        }
+        let mut out = vec![];
+        go(self, &mut out);
+        out
+    }
+    /// Decodes bytes produced by [`Arity::to_key_bytes`].
+    ///
+    /// Returns `None` for malformed input, including trailing bytes.
+    pub fn from_key_bytes(bytes: &[u8]) -> Option<Arity> {
+        fn leb(b: &mut &[u8]) -> Option<usize> {
+            let mut v = 0usize;
+            for shift in (0..usize::BITS).step_by(7) {
+                let (&x, rest) = b.split_first()?;
+                *b = rest;
+                v |= usize::from(x & 0x7f).checked_shl(shift)?;
+                if x & 0x80 == 0 {
+                    return Some(v);
+                }
+            }
+            None
+        }
+        fn go(b: &mut &[u8]) -> Option<Arity> {
+            let mut to_fill = BTreeMap::new();
+            for _ in 0..leb(b)? {
+                let len = leb(b)?;
+                let name = b.get(..len)?;
+                *b = &b[len..];
+                let name = core::str::from_utf8(name).ok()?.to_owned();
+                to_fill.insert(name, go(b)?);
+            }
+            Some(Arity { to_fill })
+        }
+        let mut b = bytes;
+        let a = go(&mut b)?;
+        b.is_empty().then_some(a)
+    }
 }
 
 impl Attr {
+    /// Creates an attribute, rejecting names that are not valid identifiers.
+    ///
+    /// Such names, including any with `=`, `]` or whitespace, only survive parsing when
+    /// escaped under `escFmtVer`.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Result<Attr, AttrError> {
+        let attr = Attr {
+            name: name.into(),
+            value: value.into(),
+        };
+        if attr.is_valid() {
+            Ok(attr)
+        } else {
+            Err(AttrError::InvalidName(attr.name))
+        }
+    }
+    /// Returns whether the name is a valid identifier; see [`Attr::new`].
+    pub fn is_valid(&self) -> bool {
+        matches!(ident_typed(&self.name), Ok(("", _)))
+    }
+    /// Returns the part of the name before the first `.`, e.g. `llm` for `llm.context`.
+    ///
+    /// Names without a `.`, such as `wasmAbiVer`, have no namespace.
+    pub fn namespace(&self) -> Option<&str> {
+        self.name.split_once('.').map(|(ns, _)| ns)
+    }
     pub fn as_wasm_abi(&self) -> Option<usize> {
         self.as_ver("wasmAbiVer")
     }
@@ -136,6 +335,40 @@ impl Attr {
         }
     }
 
+    /// Parses the value as `true` or `false`.
+    pub fn as_bool(&self) -> Option<bool> {
+        self.value.parse().ok()
+    }
+    /// Parses the value as a decimal `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.value.parse().ok()
+    }
+    /// Parses the value as a decimal `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.value.parse().ok()
+    }
+    /// Creates an attribute with the value `true` or `false`.
+    pub fn from_bool(name: impl Into<String>, value: bool) -> Self {
+        Self {
+            name: name.into(),
+            value: value.to_string(),
+        }
+    }
+    /// Creates an attribute with a decimal `u64` value.
+    pub fn from_u64(name: impl Into<String>, value: u64) -> Self {
+        Self {
+            name: name.into(),
+            value: value.to_string(),
+        }
+    }
+    /// Creates an attribute with a decimal `i64` value.
+    pub fn from_i64(name: impl Into<String>, value: i64) -> Self {
+        Self {
+            name: name.into(),
+            value: value.to_string(),
+        }
+    }
+
     // Documentation attribute accessors (feature-gated)
 
     /// Returns the value if this is a `name` attribute (human-readable display name).
@@ -310,26 +543,83 @@ impl Attr {
     }
 }
 
+/// How [`merge_with`] resolves attributes that share a name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum MergePolicy {
+    /// The last value for a name wins; the result is sorted by name.
+    #[default]
+    LastWins,
+    /// The first value for a name wins; the result is sorted by name.
+    FirstWins,
+    /// Every attribute is kept, `a` then `b`, in their original order.
+    KeepAll,
+}
+
+/// Merges two attribute lists, last-wins by name. See [`merge_with`].
 pub fn merge(a: Vec<Attr>, b: Vec<Attr>) -> Vec<Attr> {
+    merge_with(a, b, MergePolicy::LastWins)
+}
+
+/// Merges two attribute lists, resolving repeated names according to `policy`.
+///
+/// Duplicates within `a` or `b` alone are resolved the same way as duplicates across them.
+pub fn merge_with(a: Vec<Attr>, b: Vec<Attr>, policy: MergePolicy) -> Vec<Attr> {
+    if policy == MergePolicy::KeepAll {
+        return a.into_iter().chain(b).collect();
+    }
     let mut m = BTreeMap::new();
     for x in a.into_iter().chain(b) {
-        m.insert(x.name, x.value);
+        match policy {
+            MergePolicy::LastWins => {
+                m.insert(x.name, x.value);
+            }
+            _ => {
+                m.entry(x.name).or_insert(x.value);
+            }
+        }
     }
     return m
         .into_iter()
         .map(|(a, b)| Attr { name: a, value: b })
         .collect();
 }
+/// Yields the attributes whose name starts with `ns.`, e.g. every `llm.*` attribute.
+///
+/// Nested names such as `llm.hint.short` are included.
+pub fn attrs_in_namespace<'a>(attrs: &'a [Attr], ns: &str) -> impl Iterator<Item = &'a Attr> {
+    attrs.iter().filter(move |a| {
+        a.name
+            .strip_prefix(ns)
+            .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
 
 /// Parses a balanced bracketed string, returning the content inside brackets.
 ///
 /// Returns a tuple of the remaining input and the parsed string.
-pub fn parse_balanced(mut a: &str) -> IResult<&str, String> {
+pub fn parse_balanced_typed(a: &str) -> IResult<&str, String, PitError> {
+    parse_balanced_with(a, &ParseOptions::default())
+}
+/// Parses a balanced bracketed string like [`parse_balanced_typed`], applying `opts`.
+///
+/// With [`ParseOptions::escapes`], `\[`, `\]`, and `\\` decode to the literal character and
+/// do not affect nesting; a backslash before any other character is kept as-is.
+pub fn parse_balanced_with<'a>(
+    mut a: &'a str,
+    opts: &ParseOptions,
+) -> IResult<&'a str, String, PitError> {
     let mut v = Vec::default();
     let mut i = 0;
     loop {
         let (b, x) = nom::character::complete::anychar(a)?;
         match x {
+            '\\' if opts.escapes && b.starts_with(['[', ']', '\\']) => {
+                let (b, e) = nom::character::complete::anychar(b)?;
+                a = b;
+                v.push(e);
+                continue;
+            }
             '[' => i += 1,
             ']' => {
                 if i == 0 {
@@ -343,92 +633,322 @@ pub fn parse_balanced(mut a: &str) -> IResult<&str, String> {
         v.push(x)
     }
 }
+/// [`parse_balanced_typed`] with nom's default error type.
+pub fn parse_balanced(a: &str) -> IResult<&str, String> {
+    legacy(a, parse_balanced_typed)
+}
+
+/// Writes an attribute value so that [`parse_balanced_with`] reads it back unchanged when
+/// [`ParseOptions::escapes`] is set.
+///
+/// Only unmatched brackets and backslashes that would otherwise start an escape are
+/// escaped, so values without either render exactly as they are stored. A leading
+/// whitespace character is escaped as well, as [`parse_attr`] skips whitespace after `=`.
+fn write_balanced(f: &mut dyn fmt::Write, value: &str) -> fmt::Result {
+    // A `[` has a matching `]` unless the depth never comes back down to where it was
+    // before it. The unmatched ones are where the depth counted back from the end reaches
+    // a new low; the first pass skips looking for them when there are none.
+    let (mut depth, mut low) = (0isize, 0isize);
+    for c in value.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => continue,
+        }
+        low = low.min(depth);
+    }
+    let mut unmatched = Vec::new();
+    if depth > low {
+        let (mut depth, mut low) = (0isize, 0isize);
+        for (i, c) in value.char_indices().rev() {
+            match c {
+                ']' => depth += 1,
+                '[' => depth -= 1,
+                _ => continue,
+            }
+            if depth < low {
+                low = depth;
+                unmatched.push(i);
+            }
+        }
+    }
+    // Open brackets that have a match later on.
+    let mut open = 0usize;
+    for (i, c) in value.char_indices() {
+        let escape = match c {
+            '[' => {
+                let matched = unmatched.last() != Some(&i);
+                if matched {
+                    open += 1;
+                } else {
+                    unmatched.pop();
+                }
+                !matched
+            }
+            ']' if open > 0 => {
+                open -= 1;
+                false
+            }
+            ']' => true,
+            '\\' => value[i + 1..]
+                .chars()
+                .next()
+                .is_none_or(|n| matches!(n, '[' | ']' | '\\') || (i == 0 && n.is_whitespace())),
+            c => i == 0 && c.is_whitespace(),
+        };
+        if escape {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    Ok(())
+}
 
-/// Parses an attribute from a string in the format `[name=value]`.
+/// Parses an attribute from a string in the format `[name=value]`, or `[name]` for a flag.
+///
+/// The name runs to the first `=` or `]`, backslashes included. The value follows the
+/// rules of [`parse_balanced`], and whitespace after `=` is skipped. A flag has an empty
+/// value, so `[name]` and `[name=]` parse to the same `Attr`.
 ///
 /// Returns a tuple of the remaining input and the parsed `Attr`.
-pub fn parse_attr(a: &str) -> IResult<&str, Attr> {
+pub fn parse_attr_typed(a: &str) -> IResult<&str, Attr, PitError> {
+    parse_attr_with(a, &ParseOptions::default())
+}
+/// Parses an attribute like [`parse_attr_typed`], applying `opts`.
+///
+/// With [`ParseOptions::escapes`], a backslash in the name escapes the following character,
+/// so `\=`, `\[`, `\]`, and `\\` can appear in names. The value then follows
+/// [`parse_balanced_with`], and escaping the first character keeps leading whitespace
+/// (`[doc=\ x]` has value ` x`).
+pub fn parse_attr_with<'a>(a: &'a str, opts: &ParseOptions) -> IResult<&'a str, Attr, PitError> {
     let (a, _) = multispace0(a)?;
     let (a, _) = char('[')(a)?;
     let (a, _) = multispace0(a)?;
-    let (a, name) = many0(none_of("=")).parse(a)?;
-    let (a, _) = multispace0(a)?;
-    let (a, _) = char('=')(a)?;
+    let (a, name) = if opts.escapes {
+        many0(preceded(char('\\'), anychar).or(none_of("=]"))).parse(a)?
+    } else {
+        many0(none_of("=]")).parse(a)?
+    };
     let (a, _) = multispace0(a)?;
-    let (a, value) = parse_balanced(a)?;
+    let value = |a| attr_value(a, opts);
+    let (a, value) = opt(preceded((char('='), multispace0), value)).parse(a)?;
     let (a, _) = char(']')(a)?;
     let (a, _) = multispace0(a)?;
     return Ok((
         a,
         Attr {
             name: name.into_iter().collect(),
-            value,
+            value: value.unwrap_or_default(),
         },
     ));
 }
+/// [`parse_attr_typed`] with nom's default error type.
+pub fn parse_attr(a: &str) -> IResult<&str, Attr> {
+    legacy(a, parse_attr_typed)
+}
+
+/// Parses an attribute value, where with escapes a backslash before leading whitespace
+/// keeps it.
+fn attr_value<'a>(a: &'a str, opts: &ParseOptions) -> IResult<&'a str, String, PitError> {
+    match a.strip_prefix('\\') {
+        Some(b) if opts.escapes && b.starts_with(char::is_whitespace) => {
+            parse_balanced_with(b, opts)
+        }
+        _ => parse_balanced_with(a, opts),
+    }
+}
 
 /// Parses a list of attributes from a string.
 ///
 /// Returns a tuple of the remaining input and a sorted vector of `Attr`.
-pub fn parse_attrs(a: &str) -> IResult<&str, Vec<Attr>> {
-    let (a, mut b) = many0(parse_attr).parse(a)?;
+pub fn parse_attrs_typed(a: &str) -> IResult<&str, Vec<Attr>, PitError> {
+    parse_attrs_with(a, &ParseOptions::default())
+}
+/// Parses a list of attributes like [`parse_attrs_typed`], applying `opts`.
+pub fn parse_attrs_with<'a>(
+    a: &'a str,
+    opts: &ParseOptions,
+) -> IResult<&'a str, Vec<Attr>, PitError> {
+    let (a, mut b) = many0(|a| parse_attr_with(a, opts)).parse(a)?;
     b.sort_by_key(|a| a.name.clone());
     Ok((a, b))
 }
+/// [`parse_attrs_typed`] with nom's default error type.
+pub fn parse_attrs(a: &str) -> IResult<&str, Vec<Attr>> {
+    legacy(a, parse_attrs_typed)
+}
 
-impl Display for Attr {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "[{}={}]", self.name, self.value)
+/// Error returned by [`Attr::validate_enum`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct EnumError {
+    /// The attribute name.
+    pub name: String,
+    /// The value that is not allowed.
+    pub value: String,
+    /// The allowed values.
+    pub allowed: Vec<String>,
+}
+impl Display for EnumError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` must be one of {}, not `{}`",
+            self.name,
+            self.allowed.join(", "),
+            self.value
+        )
+    }
+}
+impl core::error::Error for EnumError {}
+impl Attr {
+    /// Checks that, if this attribute is called `name`, its value is one of `allowed`.
+    ///
+    /// Attributes with other names always pass.
+    pub fn validate_enum(&self, name: &str, allowed: &[&str]) -> Result<(), EnumError> {
+        if self.name != name || allowed.contains(&self.value.as_str()) {
+            return Ok(());
+        }
+        Err(EnumError {
+            name: self.name.clone(),
+            value: self.value.clone(),
+            allowed: allowed.iter().map(|a| (*a).to_owned()).collect(),
+        })
+    }
+    /// Returns whether this is a presence-only flag, i.e. its value is empty.
+    pub fn is_flag(&self) -> bool {
+        self.value.is_empty()
+    }
+    /// Renders the attribute to a formatter.
+    ///
+    /// With `attrFmtVer >= 1` a flag renders as `[name]`; otherwise as `[name=]`. With
+    /// `escFmtVer >= 1` the name and value are escaped so they parse back unchanged;
+    /// otherwise they are written as stored.
+    fn render(&self, f: &mut Formatter, ctx: &RenderCtx) -> core::fmt::Result {
+        let escapes = ctx.esc_fmt_ver.unwrap_or_default() >= 1;
+        write!(f, "[")?;
+        if escapes {
+            for (i, c) in self.name.chars().enumerate() {
+                if matches!(c, '\\' | '=' | '[' | ']') || (i == 0 && c.is_whitespace()) {
+                    write!(f, "\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+        } else {
+            f.write_str(&self.name)?;
+        }
+        if self.is_flag() && ctx.attr_fmt_ver.unwrap_or_default() >= 1 {
+            return write!(f, "]");
+        }
+        write!(f, "=")?;
+        if escapes {
+            write_balanced(f, &self.value)?;
+        } else {
+            f.write_str(&self.value)?;
+        }
+        write!(f, "]")
     }
 }
 
 /// Represents a resource type, which may be absent, a specific resource, or a reference to "this".
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub enum ResTy {
     /// No resource.
     None,
     /// A resource identified by a 32-byte ID.
-    Of([u8; 32]),
+    Of(#[cfg_attr(feature = "serde", serde(with = "crate::util::hex_id"))] [u8; 32]),
     /// The current resource ("this").
     This,
+    /// A resource referenced by a symbolic name, written `@{name}`.
+    ///
+    /// Names are resolved to ids with [`ResTy::resolve`].
+    Named(String),
 }
 impl ResTy {
+    /// Returns whether this is [`ResTy::Of`] the all-zero id.
+    pub fn is_zero(&self) -> bool {
+        *self == ResTy::Of([0; 32])
+    }
     /// Renders the resource type to a formatter.
     ///
     /// This is comparable to `Display`, but allows custom formatting based on attributes.
-    fn render(
-        &self,
-        fmt: &mut Formatter,
-        gattrs: &(dyn Fn(&str) -> Option<usize> + '_),
-    ) -> core::fmt::Result {
+    fn render(&self, fmt: &mut Formatter, ctx: &RenderCtx) -> core::fmt::Result {
         match self {
             ResTy::None => Ok(()),
             ResTy::Of(v) => {
-                if gattrs("ridFmtVer").unwrap_or_default() >= 1 {
-                    write!(
-                        fmt,
-                        "~b64{}~",
-                        base64::engine::general_purpose::STANDARD_NO_PAD.encode(v)
-                    )
+                // Encoded on the stack so rendering never allocates.
+                if ctx.rid_fmt_ver.unwrap_or_default() >= 1 {
+                    let mut buf = [0u8; 43];
+                    let n = base64::engine::general_purpose::STANDARD_NO_PAD
+                        .encode_slice(v, &mut buf)
+                        .unwrap();
+                    write!(fmt, "~b64{}~", core::str::from_utf8(&buf[..n]).unwrap())
                 } else {
-                    write!(fmt, "{}", hex::encode(v))
+                    let mut buf = [0u8; 64];
+                    hex::encode_to_slice(v, &mut buf).unwrap();
+                    write!(fmt, "{}", core::str::from_utf8(&buf).unwrap())
                 }
             }
             ResTy::This => {
                 write!(fmt, "this")
             }
+            ResTy::Named(n) => {
+                write!(fmt, "@{{{n}}}")
+            }
+        }
+    }
+    /// Replaces a [`ResTy::Named`] with the id it maps to in `names`.
+    ///
+    /// Other variants are returned unchanged; returns `None` for an unknown name.
+    pub fn resolve(&self, names: &BTreeMap<String, [u8; 32]>) -> Option<ResTy> {
+        match self {
+            ResTy::Named(n) => names.get(n).map(|r| ResTy::Of(*r)),
+            r => Some(r.clone()),
+        }
+    }
+}
+/// Textual encoding of resource IDs inside interface text.
+///
+/// Unlike [`RidEncoding`], this only lists the forms interface text can hold.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum RidFmt {
+    /// 64 hex digits, used when `ridFmtVer` is absent.
+    #[default]
+    Hex,
+    /// `~b64<base64>~`, used when `ridFmtVer >= 1`.
+    Base64,
+}
+impl ResTy {
+    /// Renders the resource type with ids in the `prefer` format, however it was authored.
+    ///
+    /// Parsing either format yields the same `ResTy`, so this only changes the text.
+    pub fn normalized_display(&self, prefer: RidFmt) -> String {
+        struct With<'a>(&'a ResTy, RidFmt);
+        impl Display for With<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                let ctx = match self.1 {
+                    RidFmt::Hex => RenderCtx::default(),
+                    RidFmt::Base64 => RenderCtx::default().with("ridFmtVer", 1),
+                };
+                self.0.render(f, &ctx)
+            }
         }
+        With(self, prefer).to_string()
     }
 }
 /// Parses a resource type from a string.
 ///
 /// Returns a tuple of the remaining input and the parsed `ResTy`.
-pub fn parse_resty(a: &str) -> IResult<&str, ResTy> {
+pub fn parse_resty_typed(a: &str) -> IResult<&str, ResTy, PitError> {
     if let Some(a) = a.strip_prefix("this") {
         // let (a, k) = opt(tag("n"))(a)?;
         return Ok((a, ResTy::This));
     }
+    if let Some(a) = a.strip_prefix("@") {
+        let (a, n) = delimited(char('{'), ident_typed, char('}')).parse(a)?;
+        return Ok((a, ResTy::Named(n.to_owned())));
+    }
     if let Some((be, a)) = a.strip_prefix("~b64").and_then(|a| a.split_once("~")) {
         let mut b = [0u8; 32];
         if let Ok(v) = base64::engine::general_purpose::STANDARD_NO_PAD.decode_slice(be, &mut b)
@@ -437,20 +957,77 @@ pub fn parse_resty(a: &str) -> IResult<&str, ResTy> {
             return Ok((a, ResTy::Of(b)));
         }
     }
-    let (a, d) = opt(take_while_m_n(64, 64, |a: char| a.is_digit(16))).parse(a)?;
+    let (rest, d) = opt(take_while_m_n(64, 64, |a: char| a.is_digit(16))).parse(a)?;
     return Ok((
-        a,
+        rest,
         match d {
-            Some(d) => {
-                let mut b = [0u8; 32];
-                hex::decode_to_slice(d, &mut b).unwrap();
-                ResTy::Of(b)
-            }
+            Some(d) => ResTy::Of(decode_rid(a, d)?),
             None => ResTy::None,
         },
     ));
 }
+/// [`parse_resty_typed`] with nom's default error type.
+pub fn parse_resty(a: &str) -> IResult<&str, ResTy> {
+    legacy(a, parse_resty_typed)
+}
+/// Decodes the hex digits `hex`, taken from the start of `input`, into an id.
+///
+/// Fails at `input` with a `HexDigit` error instead of panicking if `hex` is not exactly
+/// 64 hex digits.
+fn decode_rid(input: &str, hex: &str) -> Result<[u8; 32], nom::Err<PitError>> {
+    let mut b = [0u8; 32];
+    hex::decode_to_slice(hex, &mut b).map_err(|_| {
+        nom::Err::Failure(PitError::Nom {
+            kind: nom::error::ErrorKind::HexDigit,
+            remaining: input.len(),
+        })
+    })?;
+    Ok(b)
+}
+/// Options for the `_with` parsers.
+///
+/// The default is the standard grammar, which every other parser uses. `bare_ids` and
+/// `strict_ids` accept or reject more input but never change what a successful parse
+/// returns for text both settings accept. `escapes` does change it, and is what an
+/// interface declaring `escFmtVer` is parsed with.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct ParseOptions {
+    /// Accept a bare id (64 hex digits or `~b64...~`) in argument position as shorthand
+    /// for the owned resource `R<id>`.
+    pub bare_ids: bool,
+    /// Reject the all-zero resource id with [`PitError::ZeroResourceId`].
+    pub strict_ids: bool,
+    /// Read backslash escapes in attribute names and values (see [`parse_attr_with`]).
+    ///
+    /// [`parse_interface_with`] sets this itself when the interface's own annotations
+    /// include `escFmtVer >= 1`, the version under which rendering writes them.
+    pub escapes: bool,
+}
+/// Parses a resource type like [`parse_resty_typed`], applying `opts`.
+pub fn parse_resty_with<'a>(a: &'a str, opts: &ParseOptions) -> IResult<&'a str, ResTy, PitError> {
+    let (rest, r) = parse_resty_typed(a)?;
+    if opts.strict_ids && r.is_zero() {
+        return Err(nom::Err::Failure(PitError::ZeroResourceId {
+            remaining: a.len(),
+        }));
+    }
+    Ok((rest, r))
+}
+/// Parses a resource type like [`parse_resty`], but rejects the all-zero id.
+///
+/// An all-zero id is almost always a placeholder for "no resource", which is written by
+/// omitting the id; this fails with [`PitError::ZeroResourceId`] at the start of the id.
+/// [`ParseOptions::strict_ids`] applies the same check to whole arguments, signatures
+/// and interfaces.
+pub fn parse_resty_strict(a: &str) -> IResult<&str, ResTy, PitError> {
+    let opts = ParseOptions {
+        strict_ids: true,
+        ..Default::default()
+    };
+    parse_resty_with(a, &opts)
+}
 /// Wrapper struct for argument types that can have attributes.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct Arg {
     /// The core argument type.
@@ -461,6 +1038,7 @@ pub struct Arg {
 
 /// Core argument types for methods, including primitives and resources.
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub enum ArgTy {
     /// 32-bit integer argument.
@@ -480,42 +1058,29 @@ pub enum ArgTy {
         /// Whether the resource is taken (ownership).
         take: bool,
     },
-    // Func(Sig),
+    /// Function argument, rendered as `^` followed by the signature.
+    Func(Box<Sig>),
+    /// 128-bit SIMD vector argument.
+    V128,
+    /// Signed 8-bit integer, lowered to a core `i32`.
+    I8,
+    /// Signed 16-bit integer, lowered to a core `i32`.
+    I16,
+    /// Unsigned 8-bit integer, lowered to a core `i32`.
+    U8,
+    /// Unsigned 16-bit integer, lowered to a core `i32`.
+    U16,
 }
 impl Arg {
     /// Renders the argument type to a formatter.
     ///
     /// This is comparable to `Display`, but allows custom formatting based on attributes.
-    fn render(
-        &self,
-        fmt: &mut Formatter,
-        gattrs: &(dyn Fn(&str) -> Option<usize> + '_),
-    ) -> core::fmt::Result {
+    fn render(&self, fmt: &mut Formatter, ctx: &RenderCtx) -> core::fmt::Result {
         // Render attributes first
         for a in &self.ann {
-            write!(fmt, "{a}")?;
-        }
-        
-        match &self.ty {
-            ArgTy::I32 => write!(fmt, "I32"),
-            ArgTy::I64 => write!(fmt, "I64"),
-            ArgTy::F32 => write!(fmt, "F32"),
-            ArgTy::F64 => write!(fmt, "F64"),
-            ArgTy::Resource {
-                ty,
-                nullable,
-                take,
-            } => {
-                write!(fmt, "R")?;
-                ty.render(fmt, gattrs)?;
-                write!(
-                    fmt,
-                    "{}{}",
-                    if *nullable { "n" } else { "" },
-                    if *take { "" } else { "&" }
-                )
-            }
+            a.render(fmt, ctx)?;
         }
+        self.ty.render(fmt, ctx)
     }
     
     /// Create a new Arg from an ArgTy with no attributes.
@@ -545,29 +1110,52 @@ impl Arg {
     pub fn resource(ty: ResTy, nullable: bool, take: bool) -> Self {
         Self::new(ArgTy::Resource { ty, nullable, take })
     }
+
+    /// Returns whether this is a borrowed resource (rendered with `&`).
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.ty, ArgTy::Resource { take: false, .. })
+    }
+
+    /// Returns whether this is an owned (taken) resource.
+    pub fn is_owned(&self) -> bool {
+        matches!(self.ty, ArgTy::Resource { take: true, .. })
+    }
+
+    /// Sets whether a resource argument is taken; other arguments are returned unchanged.
+    pub fn with_take(mut self, take: bool) -> Self {
+        if let ArgTy::Resource { take: t, .. } = &mut self.ty {
+            *t = take;
+        }
+        self
+    }
+
+    /// Sets whether a resource argument is nullable; other arguments are returned unchanged.
+    pub fn with_nullable(mut self, nullable: bool) -> Self {
+        if let ArgTy::Resource { nullable: n, .. } = &mut self.ty {
+            *n = nullable;
+        }
+        self
+    }
 }
 
 impl ArgTy {
     /// Renders the core argument type to a formatter.
     ///
     /// This is comparable to `Display`, but allows custom formatting based on attributes.
-    fn render(
-        &self,
-        fmt: &mut Formatter,
-        gattrs: &(dyn Fn(&str) -> Option<usize> + '_),
-    ) -> core::fmt::Result {
+    fn render(&self, fmt: &mut Formatter, ctx: &RenderCtx) -> core::fmt::Result {
+        let compact = RenderOptions::from_ctx(ctx).primitive_style == PrimitiveStyle::Compact;
         match self {
-            ArgTy::I32 => write!(fmt, "I32"),
-            ArgTy::I64 => write!(fmt, "I64"),
-            ArgTy::F32 => write!(fmt, "F32"),
-            ArgTy::F64 => write!(fmt, "F64"),
+            ArgTy::I32 => write!(fmt, "{}", if compact { "i" } else { "I32" }),
+            ArgTy::I64 => write!(fmt, "{}", if compact { "l" } else { "I64" }),
+            ArgTy::F32 => write!(fmt, "{}", if compact { "f" } else { "F32" }),
+            ArgTy::F64 => write!(fmt, "{}", if compact { "d" } else { "F64" }),
             ArgTy::Resource {
                 ty,
                 nullable,
                 take,
             } => {
                 write!(fmt, "R")?;
-                ty.render(fmt, gattrs)?;
+                ty.render(fmt, ctx)?;
                 write!(
                     fmt,
                     "{}{}",
@@ -575,6 +1163,47 @@ impl ArgTy {
                     if *take { "" } else { "&" }
                 )
             }
+            ArgTy::Func(sig) => {
+                write!(fmt, "^")?;
+                sig.render(fmt, ctx)
+            }
+            ArgTy::V128 => write!(fmt, "V128"),
+            ArgTy::I8 => write!(fmt, "I8"),
+            ArgTy::I16 => write!(fmt, "I16"),
+            ArgTy::U8 => write!(fmt, "U8"),
+            ArgTy::U16 => write!(fmt, "U16"),
+        }
+    }
+
+    /// Returns whether this is a numeric primitive (not a resource or function).
+    pub fn is_primitive(&self) -> bool {
+        matches!(
+            self,
+            ArgTy::I32
+                | ArgTy::I64
+                | ArgTy::F32
+                | ArgTy::F64
+                | ArgTy::V128
+                | ArgTy::I8
+                | ArgTy::I16
+                | ArgTy::U8
+                | ArgTy::U16
+        )
+    }
+
+    /// Returns the value kind of this type; see [`Sig::call_descriptor`].
+    ///
+    /// Sub-word integers are passed as [`ArgKind::I32`]: signed ones sign-extended, unsigned
+    /// ones zero-extended.
+    pub fn kind(&self) -> ArgKind {
+        match self {
+            ArgTy::I32 | ArgTy::I8 | ArgTy::I16 | ArgTy::U8 | ArgTy::U16 => ArgKind::I32,
+            ArgTy::I64 => ArgKind::I64,
+            ArgTy::F32 => ArgKind::F32,
+            ArgTy::F64 => ArgKind::F64,
+            ArgTy::V128 => ArgKind::V128,
+            ArgTy::Resource { ty, .. } => ArgKind::ExternRef(ty.clone()),
+            ArgTy::Func(sig) => ArgKind::FuncRef(Box::new(sig.call_descriptor())),
         }
     }
 
@@ -591,10 +1220,39 @@ impl ArgTy {
 /// Parses an argument type from a string, including annotations and resource details.
 ///
 /// Returns a tuple of the remaining input and the parsed `Arg`.
-pub fn parse_arg(a: &str) -> IResult<&str, Arg> {
-    let (a, ann) = parse_attrs(a)?;
+pub fn parse_arg_typed(a: &str) -> IResult<&str, Arg, PitError> {
+    parse_arg_with(a, &ParseOptions::default())
+}
+/// How deeply function arguments (`^(...) -> (...)`) may nest inside one another.
+///
+/// The parsers recurse once per level, so deeper input fails with [`PitError::TooDeep`]
+/// instead of overflowing the stack.
+pub const MAX_FUNC_DEPTH: usize = 64;
+/// Parses an argument like [`parse_arg_typed`], applying `opts`.
+pub fn parse_arg_with<'a>(a: &'a str, opts: &ParseOptions) -> IResult<&'a str, Arg, PitError> {
+    parse_arg_at(a, opts, 0)
+}
+/// Parses an argument nested inside `depth` function arguments.
+fn parse_arg_at<'a>(
+    a: &'a str,
+    opts: &ParseOptions,
+    depth: usize,
+) -> IResult<&'a str, Arg, PitError> {
+    let (a, ann) = parse_attrs_with(a, opts)?;
     let (a, _) = multispace0(a)?;
-    // let (c,b) = take(1usize)(a)?;
+    if let Some(b) = a.strip_prefix("^") {
+        if depth == MAX_FUNC_DEPTH {
+            return Err(nom::Err::Failure(PitError::TooDeep { remaining: a.len() }));
+        }
+        let (a, sig) = parse_sig_at(b, opts, depth + 1)?;
+        return Ok((
+            a,
+            Arg {
+                ty: ArgTy::Func(Box::new(sig)),
+                ann,
+            },
+        ));
+    }
     match a.strip_prefix("R") {
         Some(b) => {
             // if let Some(a) = b.strip_prefix("this"){
@@ -607,10 +1265,10 @@ pub fn parse_arg(a: &str) -> IResult<&str, Arg> {
             //         },
             //     ));
             // }
-            let (a, d) = parse_resty(b)?;
+            let (a, d) = parse_resty_with(b, opts)?;
             let (a, k) = opt(tag("n")).parse(a)?;
             let (a, take) = opt(tag("&")).parse(a)?;
-            return Ok((
+            Ok((
                 a,
                 Arg {
                     ty: ArgTy::Resource {
@@ -620,29 +1278,116 @@ pub fn parse_arg(a: &str) -> IResult<&str, Arg> {
                     },
                     ann,
                 },
-            ));
+            ))
         }
-        // "(" => {
-        //     let (a, x) = parse_sig(a)?;
-        //     return Ok((a, Arg::Func(x)));
-        // }
         None => {
-            let (a, c) = take(3usize)(a)?;
-            let ty = match c {
-                "I32" => ArgTy::I32,
-                "I64" => ArgTy::I64,
-                "F32" => ArgTy::F32,
-                "F64" => ArgTy::F64,
-                _ => return Err(nom::Err::Error(Error::new(a, nom::error::ErrorKind::Tag))),
+            // Shorthand for an owned resource: a bare id without the `R`, if enabled. This
+            // is checked before compact primitives, as `f` and `d` are hex digits.
+            // Rendering always writes the `R`, so the shorthand never reaches the
+            // canonical form.
+            let bare_hex = a.len() >= 64
+                && a.as_bytes()[..64].iter().all(u8::is_ascii_hexdigit)
+                && !a.as_bytes().get(64).is_some_and(u8::is_ascii_hexdigit);
+            if opts.bare_ids
+                && (bare_hex || a.starts_with("~b64"))
+                && let (b, ty @ ResTy::Of(_)) = parse_resty_with(a, opts)?
+            {
+                return Ok((
+                    b,
+                    Arg {
+                        ty: ArgTy::Resource {
+                            ty,
+                            nullable: false,
+                            take: true,
+                        },
+                        ann,
+                    },
+                ));
+            }
+            // Primitives are whole alphanumeric words, in the verbose or the compact
+            // single-character spelling (see `PrimitiveStyle`).
+            let len = a
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(a.len());
+            let ty = match &a[..len] {
+                "I32" | "i" => ArgTy::I32,
+                "I64" | "l" => ArgTy::I64,
+                "F32" | "f" => ArgTy::F32,
+                "F64" | "d" => ArgTy::F64,
+                "V128" => ArgTy::V128,
+                "I8" => ArgTy::I8,
+                "I16" => ArgTy::I16,
+                "U8" => ArgTy::U8,
+                "U16" => ArgTy::U16,
+                _ => {
+                    return Err(nom::Err::Error(PitError::UnknownArgType {
+                        remaining: a.len(),
+                    }));
+                }
             };
-            return Ok((a, Arg { ty, ann }));
+            Ok((&a[len..], Arg { ty, ann }))
         }
     }
-    todo!()
+}
+/// [`parse_arg_typed`] with nom's default error type.
+pub fn parse_arg(a: &str) -> IResult<&str, Arg> {
+    legacy(a, parse_arg_typed)
 }
 
-/// Represents a method signature, including annotations, parameters, and return values.
+/// Which list of a signature an argument belongs to.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub enum ArgSide {
+    /// A parameter.
+    Param,
+    /// A return value.
+    Return,
+}
+/// Position of an argument within an interface.
+///
+/// `path` has one entry per level of nesting: the first indexes into the method's own
+/// signature, each following one into the function argument selected by the previous.
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct Location {
+    /// The method containing the argument.
+    pub method: String,
+    /// Side and index at each nesting level.
+    pub path: Vec<(ArgSide, usize)>,
+}
+/// Display implementation for Location, formats as e.g. `method.param[0].return[1]`.
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.method)?;
+        for (side, i) in &self.path {
+            match side {
+                ArgSide::Param => write!(f, ".param[{i}]")?,
+                ArgSide::Return => write!(f, ".return[{i}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where an attribute is attached, from [`Interface::all_attrs`].
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub enum AttrLocation {
+    /// The interface itself.
+    Interface,
+    /// A signature: the method's own when `path` is empty, otherwise that of the
+    /// function-typed argument at `path`.
+    Sig(Location),
+    /// The argument at the location.
+    Arg(Location),
+    /// The root of an info entry, from [`Interface::all_attrs_with`].
+    Info,
+    /// The info entry of the named method.
+    InfoMethod(String),
+    /// The info entry of a method's parameter or return value; `path` has one step.
+    InfoArg(Location),
+}
+
+/// Represents a method signature, including annotations, parameters, and return values.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Default, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct Sig {
     /// Annotations for the signature.
     pub ann: Vec<Attr>,
@@ -655,41 +1400,118 @@ impl Sig {
     /// Renders the method signature to a formatter.
     ///
     /// This is comparable to `Display`, but allows custom formatting based on attributes.
-    fn render(
-        &self,
-        fmt: &mut Formatter,
-        gattrs: &(dyn Fn(&str) -> Option<usize> + '_),
-    ) -> core::fmt::Result {
+    fn render(&self, fmt: &mut Formatter, ctx: &RenderCtx) -> core::fmt::Result {
         for a in self.ann.iter() {
-            write!(fmt, "{a}")?;
+            a.render(fmt, ctx)?;
         }
+        self.render_args(fmt, ctx)
+    }
+    /// Renders the parameter and return lists, without the annotations.
+    fn render_args(&self, fmt: &mut Formatter, ctx: &RenderCtx) -> core::fmt::Result {
         write!(fmt, "(")?;
         for (i, p) in self.params.iter().enumerate() {
             if i != 0 {
                 write!(fmt, ",")?;
             }
-            p.render(fmt, gattrs)?;
+            p.render(fmt, ctx)?;
         }
         write!(fmt, ") -> (")?;
         for (i, p) in self.rets.iter().enumerate() {
             if i != 0 {
                 write!(fmt, ",")?;
             }
-            p.render(fmt, gattrs)?;
+            p.render(fmt, ctx)?;
         }
         write!(fmt, ")")
     }
+    /// Returns whether the signature is annotated as pure, i.e. free of side effects.
+    ///
+    /// `[pure=true]`, `[pure=1]` and the flag `[pure]` count; without one of them a
+    /// method is assumed effectful.
+    pub fn is_pure(&self) -> bool {
+        self.ann
+            .iter()
+            .any(|a| a.name == "pure" && matches!(a.value.as_str(), "true" | "1" | ""))
+    }
+    /// Reports each resource type used both owned and borrowed by this signature.
+    ///
+    /// Parameters and returns are checked together; nested function arguments and
+    /// `ResTy::None` are not.
+    pub fn check_resource_consistency(&self) -> Vec<ResourceConflict> {
+        let mut uses: BTreeMap<&ResTy, ResourceConflict> = BTreeMap::new();
+        for (side, args) in [(ArgSide::Param, &self.params), (ArgSide::Return, &self.rets)] {
+            for (i, a) in args.iter().enumerate() {
+                let ArgTy::Resource { ty, take, .. } = &a.ty else {
+                    continue;
+                };
+                if *ty == ResTy::None {
+                    continue;
+                }
+                let c = uses.entry(ty).or_insert_with(|| ResourceConflict {
+                    ty: ty.clone(),
+                    owned: vec![],
+                    borrowed: vec![],
+                });
+                if *take { &mut c.owned } else { &mut c.borrowed }.push((side, i));
+            }
+        }
+        uses.into_values()
+            .filter(|c| !c.owned.is_empty() && !c.borrowed.is_empty())
+            .collect()
+    }
+    /// Returns the value kinds a dynamic dispatcher needs to call a method with this signature.
+    pub fn call_descriptor(&self) -> CallDescriptor {
+        CallDescriptor {
+            params: self.params.iter().map(|a| a.ty.kind()).collect(),
+            rets: self.rets.iter().map(|a| a.ty.kind()).collect(),
+        }
+    }
 }
-/// Parses a method signature from a string, including parameters, return values, and annotations.
+/// Value kind of an argument, with annotations, nullability and ownership dropped.
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum ArgKind {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    /// An opaque host reference to a resource of the given type.
+    ExternRef(ResTy),
+    /// A callable reference with the given signature.
+    FuncRef(Box<CallDescriptor>),
+}
+/// Flat description of a signature for dynamic dispatch; see [`Sig::call_descriptor`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct CallDescriptor {
+    /// Kinds of the parameters, in order.
+    pub params: Vec<ArgKind>,
+    /// Kinds of the return values, in order.
+    pub rets: Vec<ArgKind>,
+}
+/// Parses a method signature from a string, including parameters, return values, and annotations.
 ///
 /// Returns a tuple of the remaining input and the parsed `Sig`.
-pub fn parse_sig(a: &str) -> IResult<&str, Sig> {
-    let (a, b) = parse_attrs(a)?;
+pub fn parse_sig_typed(a: &str) -> IResult<&str, Sig, PitError> {
+    parse_sig_with(a, &ParseOptions::default())
+}
+/// Parses a signature like [`parse_sig_typed`], applying `opts` to every argument.
+pub fn parse_sig_with<'a>(a: &'a str, opts: &ParseOptions) -> IResult<&'a str, Sig, PitError> {
+    parse_sig_at(a, opts, 0)
+}
+/// Parses a signature nested inside `depth` function arguments.
+fn parse_sig_at<'a>(
+    a: &'a str,
+    opts: &ParseOptions,
+    depth: usize,
+) -> IResult<&'a str, Sig, PitError> {
+    let (a, b) = parse_attrs_with(a, opts)?;
     let (a, _) = multispace0(a)?;
-    let mut d = delimited(char('('), separated_list0(char(','), parse_arg), char(')'));
+    let arg = |a| parse_arg_at(a, opts, depth);
+    let mut d = delimited(char('('), separated_list0(char(','), arg), char(')'));
     let (a, params) = d.parse(a)?;
     let (a, _) = multispace0(a)?;
-    let (a, _) = tag("->")(a)?;
+    let (a, _) = token("->")(a)?;
     let (a, _) = multispace0(a)?;
     let (a, rets) = d.parse(a)?;
     return Ok((
@@ -701,8 +1523,13 @@ pub fn parse_sig(a: &str) -> IResult<&str, Sig> {
         },
     ));
 }
+/// [`parse_sig_typed`] with nom's default error type.
+pub fn parse_sig(a: &str) -> IResult<&str, Sig> {
+    legacy(a, parse_sig_typed)
+}
 /// Represents an interface, containing methods and interface-level annotations.
-#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Default, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct Interface {
     /// Methods in the interface, keyed by name.
     pub methods: BTreeMap<String, Sig>,
@@ -713,23 +1540,17 @@ impl Interface {
     /// Renders the interface to a formatter.
     ///
     /// This is comparable to `Display`, but allows custom formatting based on attributes.
-    fn render(
-        &self,
-        f: &mut Formatter,
-        gattrs: &(dyn Fn(&str) -> Option<usize> + '_),
-    ) -> core::fmt::Result {
+    fn render(&self, f: &mut Formatter, ctx: &RenderCtx) -> core::fmt::Result {
         for a in self.ann.iter() {
-            write!(f, "{a}")?;
+            a.render(f, ctx)?;
         }
         write!(f, "{}", "{")?;
-        let mut x = self.methods.iter().collect::<Vec<_>>();
-        x.sort_by_key(|a| a.0);
-        for (i, (a, b)) in x.into_iter().enumerate() {
+        for (i, (a, b)) in self.methods.iter().enumerate() {
             if i != 0 {
                 write!(f, ";")?;
             }
             write!(f, "{}", a)?;
-            b.render(f, gattrs)?;
+            b.render(f, ctx)?;
         }
         return write!(f, "{}", "}");
     }
@@ -737,9 +1558,24 @@ impl Interface {
 /// Parses an interface from a string, including methods and interface-level annotations.
 ///
 /// Returns a tuple of the remaining input and the parsed `Interface`.
-pub fn parse_interface(a: &str) -> IResult<&str, Interface> {
-    pub fn go(a: &str) -> IResult<&str, Interface> {
-        let (a, s) = separated_list0(char(';'), tuple((multispace0, ident, parse_sig))).parse(a)?;
+pub fn parse_interface_typed(a: &str) -> IResult<&str, Interface, PitError> {
+    parse_interface_with(a, &ParseOptions::default())
+}
+/// Parses an interface like [`parse_interface_typed`], applying `opts` to every argument.
+pub fn parse_interface_with<'a>(
+    a: &'a str,
+    opts: &ParseOptions,
+) -> IResult<&'a str, Interface, PitError> {
+    let (a, _) = multispace0(a)?;
+    let opts = &ParseOptions {
+        escapes: opts.escapes || declares_escapes(a),
+        ..*opts
+    };
+    let go = |a| -> IResult<&str, Interface, PitError> {
+        let sig = |a| parse_sig_with(a, opts);
+        let (a, s) = separated_list0(char(';'), tuple((multispace0, ident_typed, sig))).parse(a)?;
+        // A trailing `;` is accepted so line-oriented forms can terminate every method.
+        let (a, _) = opt(char(';')).parse(a)?;
         let (a, _) = multispace0(a)?;
         return Ok((
             a,
@@ -748,158 +1584,3572 @@ pub fn parse_interface(a: &str) -> IResult<&str, Interface> {
                 ann: vec![],
             },
         ));
-    }
-    let (a, _) = multispace0(a)?;
-    let (a, b) = parse_attrs(a)?;
+    };
+    let (a, b) = parse_attrs_with(a, opts)?;
     let (a, mut c) = delimited(char('{'), go, char('}')).parse(a)?;
     c.ann = b;
     return Ok((a, c));
 }
+/// Whether the annotations at the start of `a` set `escFmtVer >= 1`.
+///
+/// They are read with escapes, as rendering writes them once the version is set; text
+/// without the version has no `escFmtVer` to find however its backslashes are read.
+fn declares_escapes(a: &str) -> bool {
+    let escapes = ParseOptions {
+        escapes: true,
+        ..ParseOptions::default()
+    };
+    parse_attrs_with(a, &escapes).is_ok_and(|(_, ann)| {
+        RenderCtx::from_ann(&ann).esc_fmt_ver.unwrap_or_default() >= 1
+    })
+}
+/// Whether the annotations at the start of `a`, read with escapes, may continue: nothing
+/// but annotations follows them yet.
+fn annotations_open(a: &str) -> bool {
+    let escapes = ParseOptions {
+        escapes: true,
+        ..ParseOptions::default()
+    };
+    parse_attrs_with(a, &escapes).is_ok_and(|(rest, _)| {
+        let rest = rest.trim_start();
+        rest.is_empty() || rest.starts_with('[')
+    })
+}
+/// [`parse_interface_typed`] with nom's default error type.
+pub fn parse_interface(a: &str) -> IResult<&str, Interface> {
+    legacy(a, parse_interface_typed)
+}
+/// Parses an interface like [`parse_interface`], also returning the annotation names
+/// not in `known`.
+///
+/// Annotations at every level are checked: the interface, each signature (including
+/// function-typed arguments) and each argument. Unknown annotations are kept in `ann` as
+/// usual; the returned names are sorted and deduplicated.
+pub fn parse_interface_collecting_unknown<'a>(
+    input: &'a str,
+    known: &BTreeSet<&str>,
+) -> IResult<&'a str, (Interface, Vec<String>), PitError> {
+    let (rest, iface) = parse_interface_typed(input)?;
+    let mut unknown = BTreeSet::new();
+    let mut check = |ann: &[Attr]| {
+        for a in ann {
+            if !known.contains(a.name.as_str()) {
+                unknown.insert(a.name.clone());
+            }
+        }
+    };
+    check(&iface.ann);
+    for sig in iface.methods.values() {
+        check(&sig.ann);
+    }
+    iface.for_each_arg(&mut |_, a| {
+        check(&a.ann);
+        if let ArgTy::Func(sig) = &a.ty {
+            check(&sig.ann);
+        }
+    });
+    Ok((rest, (iface, unknown.into_iter().collect())))
+}
+/// Chainable builder for a [`Sig`].
+#[derive(Clone, Default, Debug)]
+pub struct SigBuilder {
+    sig: Sig,
+}
+impl SigBuilder {
+    /// Starts an empty `() -> ()` signature.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Appends a parameter.
+    pub fn param(mut self, a: Arg) -> Self {
+        self.sig.params.push(a);
+        self
+    }
+    /// Appends a return value.
+    pub fn ret(mut self, a: Arg) -> Self {
+        self.sig.rets.push(a);
+        self
+    }
+    /// Adds a signature annotation, keeping annotations sorted as the parser does.
+    pub fn ann(mut self, attr: Attr) -> Self {
+        self.sig.ann.push(attr);
+        self.sig.ann.sort_by_key(|a| a.name.clone());
+        self
+    }
+    /// Appends an `I32` parameter.
+    pub fn i32_param(self) -> Self {
+        self.param(Arg::i32())
+    }
+    /// Appends an `I64` parameter.
+    pub fn i64_param(self) -> Self {
+        self.param(Arg::i64())
+    }
+    /// Appends an `F32` parameter.
+    pub fn f32_param(self) -> Self {
+        self.param(Arg::f32())
+    }
+    /// Appends an `F64` parameter.
+    pub fn f64_param(self) -> Self {
+        self.param(Arg::f64())
+    }
+    /// Appends a resource parameter.
+    pub fn resource_param(self, ty: ResTy, nullable: bool, take: bool) -> Self {
+        self.param(Arg::resource(ty, nullable, take))
+    }
+    /// Returns the finished signature.
+    pub fn build(self) -> Sig {
+        self.sig
+    }
+}
+/// Chainable builder for an [`Interface`].
+#[derive(Clone, Default, Debug)]
+pub struct InterfaceBuilder {
+    iface: Interface,
+}
+impl InterfaceBuilder {
+    /// Starts an interface with no methods or annotations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a method, replacing any earlier one of the same name.
+    pub fn method(mut self, name: impl Into<String>, sig: SigBuilder) -> Self {
+        self.iface.methods.insert(name.into(), sig.build());
+        self
+    }
+    /// Adds an interface annotation, keeping annotations sorted as the parser does.
+    pub fn attr(self, attr: Attr) -> Self {
+        Self {
+            iface: self.iface.with_annotation(attr),
+        }
+    }
+    /// Returns the finished interface.
+    pub fn build(self) -> Interface {
+        self.iface
+    }
+}
+/// A file holding either an interface or info entries; see [`parse_document`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Document {
+    /// An interface, optionally annotated: `[..]{...}`.
+    Interface(Interface),
+    /// One or more info entries: `<hex id>: [...]`.
+    Info(info::Info),
+}
+/// Parses either an interface or info entries, telling them apart by their start.
+///
+/// Input starting with an alphanumeric token and a `:` is read as [`info::Info`], with
+/// the same hard failure as [`info::Info::parse`] for a malformed id. Anything else is
+/// read as an interface.
+pub fn parse_document(a: &str) -> IResult<&str, Document, PitError> {
+    let start = a.trim_start();
+    let token_len = start
+        .find(|c: char| !c.is_alphanumeric())
+        .unwrap_or(start.len());
+    if token_len > 0 && start[token_len..].trim_start().starts_with(':') {
+        let (a, i) = info::Info::parse_typed(a)?;
+        return Ok((a, Document::Info(i)));
+    }
+    let (a, i) = parse_interface_typed(a)?;
+    Ok((a, Document::Interface(i)))
+}
 macro_rules! display {
     ($($t:ty),*) => {
         const _: () = {$(impl Display for $t{
             fn fmt(&self, f: &mut Formatter) -> core::fmt::Result{
-                self.render(f,&|_|None)
+                self.render(f,&RenderCtx::default())
             }
         })*};
     };
 }
-display!(Sig, ResTy, Arg, ArgTy);
-impl Display for Interface {
+display!(Attr, Sig, ResTy, Arg, ArgTy);
+/// Copies `ann` sorted by name, keeping annotations with the same name in order.
+fn sorted_by_name(ann: &[Attr]) -> Vec<Attr> {
+    let mut ann = ann.to_vec();
+    ann.sort_by(|a, b| a.name.cmp(&b.name));
+    ann
+}
+fn is_sorted_by_name(ann: &[Attr]) -> bool {
+    ann.is_sorted_by(|a, b| a.name <= b.name)
+}
+impl Sig {
+    /// The signature with its annotations, and those of its arguments, sorted by name.
+    fn canonicalize(&self) -> Sig {
+        fn arg(a: &Arg) -> Arg {
+            Arg {
+                ty: match &a.ty {
+                    ArgTy::Func(s) => ArgTy::Func(Box::new(s.canonicalize())),
+                    t => t.clone(),
+                },
+                ann: sorted_by_name(&a.ann),
+            }
+        }
+        Sig {
+            ann: sorted_by_name(&self.ann),
+            params: self.params.iter().map(arg).collect(),
+            rets: self.rets.iter().map(arg).collect(),
+        }
+    }
+    fn is_canonical(&self) -> bool {
+        is_sorted_by_name(&self.ann)
+            && self.params.iter().chain(self.rets.iter()).all(|a| {
+                is_sorted_by_name(&a.ann)
+                    && match &a.ty {
+                        ArgTy::Func(s) => s.is_canonical(),
+                        _ => true,
+                    }
+            })
+    }
+}
+/// Returns the 4-byte dispatch selector of a method: the first 4 bytes of the SHA3-256 of
+/// `name` followed by the canonical rendering of `sig`.
+pub fn method_selector(name: &str, sig: &Sig) -> [u8; 4] {
+    use core::fmt::Write;
+    let mut h = Sha3_256::default();
+    let mut w = WriteUpdate { wrapped: &mut h };
+    if sig.is_canonical() {
+        write!(w, "{name}{sig}").unwrap();
+    } else {
+        write!(w, "{name}{}", sig.canonicalize()).unwrap();
+    }
+    truncate_rid(&h.finalize().into())
+}
+/// Error returned by [`Interface::migrate_abi`].
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum MigrationError {
+    /// The target version is older than the interface's current one.
+    Downgrade { from: usize, to: usize },
+    /// No migration to this version is known.
+    Unsupported { to: usize },
+}
+impl Display for MigrationError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        self.render(f, &|ab| self.ann.iter().find_map(|a| a.as_ver(ab)))
+        match self {
+            MigrationError::Downgrade { from, to } => {
+                write!(f, "cannot downgrade wasm ABI from {from} to {to}")
+            }
+            MigrationError::Unsupported { to } => write!(f, "no migration to wasm ABI {to}"),
+        }
     }
 }
-impl Interface {
+impl core::error::Error for MigrationError {}
+/// Two methods of an interface with the same selector; see [`Interface::selectors`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct SelectorCollision {
+    /// The shared selector.
+    pub selector: [u8; 4],
+    /// The method names sharing it, in name order.
+    pub methods: (String, String),
+}
+impl Display for SelectorCollision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "methods `{}` and `{}` share selector {}",
+            self.methods.0,
+            self.methods.1,
+            hex::encode(self.selector)
+        )
+    }
+}
+impl core::error::Error for SelectorCollision {}
+/// Returns the first `N` bytes of `rid`.
+fn truncate_rid<const N: usize>(rid: &[u8; 32]) -> [u8; N] {
+    let mut out = [0u8; N];
+    out.copy_from_slice(&rid[..N]);
+    out
+}
+/// Groups the indices of `rids` by their first `N` bytes, keeping groups of two or more.
+fn truncated_collisions<const N: usize>(
+    rids: impl Iterator<Item = [u8; 32]>,
+) -> Vec<([u8; N], Vec<usize>)> {
+    let mut by_prefix: BTreeMap<[u8; N], Vec<usize>> = BTreeMap::new();
+    for (i, rid) in rids.enumerate() {
+        by_prefix.entry(truncate_rid(&rid)).or_default().push(i);
+    }
+    by_prefix.into_iter().filter(|(_, v)| v.len() > 1).collect()
+}
+/// Reports every short id shared by more than one of `ifaces`, with the indices sharing it.
+///
+/// Tools addressing interfaces by [`Interface::short_id`] should fall back to full rids for
+/// the reported interfaces. The same interface listed twice is reported too.
+pub fn short_id_collisions(ifaces: &[&Interface]) -> Vec<([u8; 16], Vec<usize>)> {
+    truncated_collisions(ifaces.iter().map(|i| i.rid()))
+}
+/// Text encodings for a resource ID.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum RidEncoding {
+    /// Lowercase hex, 64 characters; the form used by `rid_str`.
+    Hex,
+    /// Unpadded lowercase RFC 4648 base32, 52 characters. Decoding is case-insensitive.
+    Base32,
+    /// Unpadded standard base64, 43 characters; the form used with `ridFmtVer=1`.
+    Base64NoPad,
+}
+impl RidEncoding {
+    /// All supported encodings.
+    pub const ALL: [RidEncoding; 3] = [
+        RidEncoding::Hex,
+        RidEncoding::Base32,
+        RidEncoding::Base64NoPad,
+    ];
+    /// Encodes `rid`.
+    pub fn encode(self, rid: &[u8; 32]) -> String {
+        match self {
+            RidEncoding::Hex => hex::encode(rid),
+            RidEncoding::Base32 => data_encoding::BASE32_NOPAD
+                .encode(rid)
+                .to_ascii_lowercase(),
+            RidEncoding::Base64NoPad => {
+                base64::engine::general_purpose::STANDARD_NO_PAD.encode(rid)
+            }
+        }
+    }
+    /// Decodes a rid written in this encoding.
+    pub fn decode(self, s: &str) -> Option<[u8; 32]> {
+        let mut rid = [0u8; 32];
+        match self {
+            RidEncoding::Hex => hex::decode_to_slice(s, &mut rid).ok()?,
+            RidEncoding::Base32 => {
+                let upper = s.to_ascii_uppercase();
+                let b = data_encoding::BASE32_NOPAD;
+                if b.decode_len(upper.len()).ok()? != 32 {
+                    return None;
+                }
+                b.decode_mut(upper.as_bytes(), &mut rid).ok()?;
+            }
+            RidEncoding::Base64NoPad => {
+                let n = base64::engine::general_purpose::STANDARD_NO_PAD
+                    .decode_slice(s, &mut rid)
+                    .ok()?;
+                if n != 32 {
+                    return None;
+                }
+            }
+        }
+        Some(rid)
+    }
+    /// Decodes a rid in any supported encoding.
+    ///
+    /// The encodings have distinct lengths, so at most one can match.
+    pub fn decode_any(s: &str) -> Option<[u8; 32]> {
+        RidEncoding::ALL.into_iter().find_map(|e| e.decode(s))
+    }
+}
+/// An immutable [`Interface`] paired with its resource ID, computed once on construction.
+///
+/// Use this where the rid is needed repeatedly; `Interface::rid` re-renders and rehashes
+/// the whole interface on every call.
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct InternedInterface {
+    rid: [u8; 32],
+    iface: Interface,
+}
+impl InternedInterface {
+    /// Computes the rid of `iface` and stores it alongside.
+    pub fn new(iface: Interface) -> Self {
+        InternedInterface {
+            rid: iface.rid(),
+            iface,
+        }
+    }
+    /// Returns the wrapped interface.
+    pub fn as_interface(&self) -> &Interface {
+        &self.iface
+    }
+    /// Returns the cached resource ID.
     pub fn rid(&self) -> [u8; 32] {
-        // use core::io::Write;
-        use core::fmt::Write;
-        let mut s = Sha3_256::default();
-        write!(WriteUpdate { wrapped: &mut s }, "{self}").unwrap();
-        return s.finalize().try_into().unwrap();
+        self.rid
     }
-    pub fn rid_str(&self) -> String {
-        return hex::encode(self.rid());
+    /// Unwraps the interface, discarding the cached rid.
+    pub fn into_interface(self) -> Interface {
+        self.iface
     }
 }
-pub mod info;
-pub fn retuple(a: Vec<Arg>) -> Interface {
-    Interface {
-        methods: a
-            .into_iter()
-            .enumerate()
-            .map(|(a, b)| {
-                (
-                    format!("v{a}"),
-                    Sig {
-                        ann: vec![],
-                        params: vec![],
-                        rets: vec![b],
-                    },
-                )
-            })
-            .collect(),
-        ann: vec![],
+impl From<Interface> for InternedInterface {
+    fn from(iface: Interface) -> Self {
+        InternedInterface::new(iface)
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_primitive_with_attributes() {
-        // Test creating primitive types with attributes
-        let i32_with_attr = ArgTy::I32.with_attrs(vec![
-            Attr {
-                name: "name".to_owned(),
-                value: "counter".to_owned(),
-            }
-        ]);
-        
-        // Verify the structure
-        assert_eq!(i32_with_attr.ann.len(), 1);
-        assert_eq!(i32_with_attr.ann[0].name, "name");
-        assert_eq!(i32_with_attr.ann[0].value, "counter");
-        assert!(matches!(i32_with_attr.ty, ArgTy::I32));
-        
-        // Test display formatting includes attributes
-        let formatted = format!("{}", i32_with_attr);
-        assert!(formatted.contains("[name=counter]"));
-        assert!(formatted.contains("I32"));
+impl Display for Interface {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.render(f, &RenderCtx::from_interface(self))
     }
-
-    #[test]
-    fn test_parsing_primitive_with_attributes() {
-        // Test parsing attributes on primitive types
-        let parsed_i32 = parse_arg("[name=test_counter]I32").unwrap().1;
-        let parsed_f64 = parse_arg("[doc=floating_point][precision=high]F64").unwrap().1;
-        
-        // Verify I32 parsing
-        assert_eq!(parsed_i32.ann.len(), 1);
-        assert_eq!(parsed_i32.ann[0].name, "name");
-        assert_eq!(parsed_i32.ann[0].value, "test_counter");
-        assert!(matches!(parsed_i32.ty, ArgTy::I32));
-        
-        // Verify F64 parsing with multiple attributes
-        assert_eq!(parsed_f64.ann.len(), 2);
-        // Attributes should be sorted by name
-        assert_eq!(parsed_f64.ann[0].name, "doc");
-        assert_eq!(parsed_f64.ann[0].value, "floating_point");
-        assert_eq!(parsed_f64.ann[1].name, "precision");
-        assert_eq!(parsed_f64.ann[1].value, "high");
-        assert!(matches!(parsed_f64.ty, ArgTy::F64));
+}
+/// How primitive argument types are spelled when rendering.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum PrimitiveStyle {
+    /// `I32`, `I64`, `F32`, `F64`.
+    #[default]
+    Verbose,
+    /// `i`, `l`, `f`, `d`. Other primitives keep their verbose spelling.
+    Compact,
+}
+/// Options controlling the textual form produced by rendering.
+///
+/// The canonical form (used by `Display` and `rid`) derives these options from the
+/// interface's own version attributes: `primFmtVer >= 1` selects
+/// [`PrimitiveStyle::Compact`]. The parser accepts both primitive styles regardless.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct RenderOptions {
+    /// Spelling of primitive argument types.
+    pub primitive_style: PrimitiveStyle,
+}
+impl RenderOptions {
+    /// Derives rendering options from version attributes.
+    fn from_ctx(ctx: &RenderCtx) -> Self {
+        Self {
+            primitive_style: if ctx.prim_fmt_ver.unwrap_or_default() >= 1 {
+                PrimitiveStyle::Compact
+            } else {
+                PrimitiveStyle::Verbose
+            },
+        }
     }
-
-    #[test]
-    fn test_resource_attributes_still_work() {
-        // Test that resource types still work with attributes
-        let parsed_resource = parse_arg("[doc=test_resource]Rthisn").unwrap().1;
-        
-        assert_eq!(parsed_resource.ann.len(), 1);
-        assert_eq!(parsed_resource.ann[0].name, "doc");
-        assert_eq!(parsed_resource.ann[0].value, "test_resource");
-        
-        if let ArgTy::Resource { ty, nullable, take } = parsed_resource.ty {
-            assert!(matches!(ty, ResTy::This));
-            assert!(nullable);
-            assert!(take);
-        } else {
-            panic!("Expected Resource type");
+}
+/// Format versions consulted while rendering.
+///
+/// Holds one field per version attribute (see [`VERSION_ATTRS`]), with the value as read by
+/// [`Attr::as_ver`], i.e. one more than the written hex, so an absent key reads as 0.
+/// `Display` for [`Interface`] uses [`RenderCtx::from_interface`]. A syntax change gated on
+/// a new version key only needs to read it here to keep existing rids stable.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RenderCtx {
+    /// `wasmAbiVer`.
+    pub wasm_abi_ver: Option<usize>,
+    /// `ridFmtVer`.
+    pub rid_fmt_ver: Option<usize>,
+    /// `primFmtVer`.
+    pub prim_fmt_ver: Option<usize>,
+    /// `attrFmtVer`.
+    pub attr_fmt_ver: Option<usize>,
+    /// `escFmtVer`.
+    pub esc_fmt_ver: Option<usize>,
+}
+impl RenderCtx {
+    /// Collects the version attributes set on `iface`.
+    ///
+    /// As when looking them up directly, the first parseable value of a repeated key wins.
+    pub fn from_interface(iface: &Interface) -> Self {
+        Self::from_ann(&iface.ann)
+    }
+    /// Collects the version attributes in the interface annotations `ann`.
+    fn from_ann(ann: &[Attr]) -> Self {
+        let mut ctx = Self::default();
+        for key in VERSION_ATTRS {
+            if let Some(v) = ann.iter().find_map(|a| a.as_ver(key)) {
+                ctx = ctx.with(key, v);
+            }
         }
+        ctx
     }
-
-    #[test]
-    fn test_no_attributes_still_works() {
-        // Test that types without attributes still parse correctly
-        let parsed_i64 = parse_arg("I64").unwrap().1;
-        
-        assert_eq!(parsed_i64.ann.len(), 0);
-        assert!(matches!(parsed_i64.ty, ArgTy::I64));
+    /// Returns a copy with `key` set to `ver`, in [`Attr::as_ver`] units.
+    ///
+    /// Keys other than the version attributes are ignored.
+    pub fn with(mut self, key: &str, ver: usize) -> Self {
+        let slot = match key {
+            "wasmAbiVer" => &mut self.wasm_abi_ver,
+            "ridFmtVer" => &mut self.rid_fmt_ver,
+            "primFmtVer" => &mut self.prim_fmt_ver,
+            "attrFmtVer" => &mut self.attr_fmt_ver,
+            "escFmtVer" => &mut self.esc_fmt_ver,
+            _ => return self,
+        };
+        *slot = Some(ver);
+        self
     }
-    
-    #[test]
-    fn test_convenience_methods() {
-        // Test convenience constructors
-        let i32_arg = Arg::i32();
-        assert!(matches!(i32_arg.ty, ArgTy::I32));
-        assert_eq!(i32_arg.ann.len(), 0);
-        
-        let f64_with_attr = Arg::f64().with_attr(Attr {
-            name: "precision".to_owned(),
-            value: "double".to_owned(),
-        });
-        assert!(matches!(f64_with_attr.ty, ArgTy::F64));
-        assert_eq!(f64_with_attr.ann.len(), 1);
-        assert_eq!(f64_with_attr.ann[0].name, "precision");
-        
-        let resource_arg = Arg::resource(ResTy::This, true, false);
-        if let ArgTy::Resource { ty, nullable, take } = resource_arg.ty {
-            assert!(matches!(ty, ResTy::This));
-            assert!(nullable);
-            assert!(!take);
-        } else {
-            panic!("Expected Resource type");
+    /// Returns the version for `key`, if set.
+    pub fn get(&self, key: &str) -> Option<usize> {
+        match key {
+            "wasmAbiVer" => self.wasm_abi_ver,
+            "ridFmtVer" => self.rid_fmt_ver,
+            "primFmtVer" => self.prim_fmt_ver,
+            "attrFmtVer" => self.attr_fmt_ver,
+            "escFmtVer" => self.esc_fmt_ver,
+            _ => None,
         }
     }
 }
+impl Interface {
+    /// Renders the interface in a line-oriented form suited to version control.
+    ///
+    /// Each interface annotation and each method annotation gets its own line, and every
+    /// method ends with `;`, so a small edit only touches the lines of the affected method.
+    /// Methods are in name order and primitives and rids are spelled as in the canonical
+    /// form. The result parses back with `Interface::from_str`.
+    pub fn to_git_form(&self) -> String {
+        struct Git<'a>(&'a Interface);
+        impl Display for Git<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                let iface = self.0;
+                let ctx = RenderCtx::from_interface(iface);
+                for a in &iface.ann {
+                    a.render(f, &ctx)?;
+                    writeln!(f)?;
+                }
+                writeln!(f, "{{")?;
+                for (name, sig) in &iface.methods {
+                    write!(f, "\t{name}")?;
+                    if !sig.ann.is_empty() {
+                        writeln!(f)?;
+                        for a in &sig.ann {
+                            write!(f, "\t\t")?;
+                            a.render(f, &ctx)?;
+                            writeln!(f)?;
+                        }
+                        write!(f, "\t\t")?;
+                    }
+                    sig.render_args(f, &ctx)?;
+                    writeln!(f, ";")?;
+                }
+                writeln!(f, "}}")
+            }
+        }
+        Git(self).to_string()
+    }
+    /// Renders the interface into any `fmt::Write` sink.
+    ///
+    /// `gattrs` supplies the version attributes (see [`VERSION_ATTRS`]) that select the
+    /// textual form; `Display` passes the interface's own, via [`Attr::as_ver`]. The output
+    /// is streamed into `w` rather than built as a `String`, which suits `no_std` callers
+    /// writing into a fixed buffer. See [`Interface::render_with`] for the general form.
+    pub fn render_into(
+        &self,
+        w: &mut dyn fmt::Write,
+        gattrs: &(dyn Fn(&str) -> Option<usize> + '_),
+    ) -> fmt::Result {
+        let mut ctx = RenderCtx::default();
+        for key in VERSION_ATTRS {
+            if let Some(v) = gattrs(key) {
+                ctx = ctx.with(key, v);
+            }
+        }
+        self.render_with(w, &ctx)
+    }
+    /// Renders the interface into `w` with the format versions in `ctx`.
+    ///
+    /// With [`RenderCtx::from_interface`] this writes exactly the `Display` form.
+    pub fn render_with(&self, w: &mut dyn fmt::Write, ctx: &RenderCtx) -> fmt::Result {
+        struct With<'a>(&'a Interface, &'a RenderCtx);
+        impl Display for With<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                self.0.render(f, self.1)
+            }
+        }
+        write!(w, "{}", With(self, ctx))
+    }
+    /// Renders the interface with the given options instead of those implied by its annotations.
+    ///
+    /// The result parses back to the same interface, but only the `Display` form is
+    /// canonical for hashing.
+    pub fn to_string_with(&self, opts: RenderOptions) -> String {
+        struct With<'a>(&'a Interface, RenderOptions);
+        impl Display for With<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                let mut ctx = RenderCtx::from_interface(self.0);
+                ctx.prim_fmt_ver = match self.1.primitive_style {
+                    PrimitiveStyle::Verbose => None,
+                    PrimitiveStyle::Compact => Some(1),
+                };
+                self.0.render(f, &ctx)
+            }
+        }
+        With(self, opts).to_string()
+    }
+}
+/// Runs `parser` over the whole of `s`, allowing only trailing whitespace.
+fn parse_all<'a, T>(
+    s: &'a str,
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, T, PitError>,
+) -> Result<T, PitError> {
+    let (rest, v) = parser(s).map_err(PitError::from_nom)?;
+    let rest = rest.trim_start();
+    if !rest.is_empty() {
+        return Err(PitError::TrailingInput {
+            remaining: rest.len(),
+        });
+    }
+    Ok(v)
+}
+/// Runs `parser` on `a` once `end` says the item at the start of `a` is complete.
+///
+/// Annotation brackets are matched, with `\` escapes if `escapes` is set, and skipped;
+/// `end` sees every other character plus each `]` that closes an outermost bracket, and
+/// returns `true` once the item is complete or the character cannot belong to it. An
+/// unmatched `]` runs `parser` straight away. Until then the input is
+/// [`nom::Err::Incomplete`].
+pub(crate) fn streaming<'a, T>(
+    a: &'a str,
+    parser: impl FnOnce(&'a str) -> IResult<&'a str, T, PitError>,
+    escapes: bool,
+    mut end: impl FnMut(char) -> bool,
+) -> IResult<&'a str, T, PitError> {
+    let mut brackets = 0usize;
+    let mut chars = a.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if escapes && brackets > 0 => {
+                chars.next();
+            }
+            '[' => brackets += 1,
+            ']' if brackets > 1 => brackets -= 1,
+            ']' if brackets == 1 => {
+                brackets = 0;
+                if end(c) {
+                    return parser(a);
+                }
+            }
+            _ if brackets > 0 => {}
+            ']' => return parser(a),
+            _ if end(c) => return parser(a),
+            _ => {}
+        }
+    }
+    Err(nom::Err::Incomplete(nom::Needed::Unknown))
+}
+/// Finds the end of a streamed signature, the `)` closing its return list, for [`streaming`].
+#[derive(Default)]
+struct SigEnd {
+    parens: usize,
+    lists: usize,
+}
+impl SigEnd {
+    fn end(&mut self, c: char) -> bool {
+        match c {
+            '(' => self.parens += 1,
+            ')' if self.parens > 0 => {
+                self.parens -= 1;
+                if self.parens == 0 {
+                    self.lists += 1;
+                    return self.lists == 2;
+                }
+            }
+            _ if self.parens > 0 => {}
+            '-' | '>' if self.lists == 1 => {}
+            ']' => {}
+            _ => return !c.is_whitespace(),
+        }
+        false
+    }
+}
+impl Sig {
+    /// Parses a signature from input that may still be arriving, like
+    /// [`Interface::parse_streaming`].
+    ///
+    /// Returns `Err(nom::Err::Incomplete(_))` until the `)` closing the return list is
+    /// present, then parses it like [`parse_sig`].
+    pub fn parse_streaming(a: &str) -> IResult<&str, Sig, PitError> {
+        let mut sig = SigEnd::default();
+        streaming(a, parse_sig_typed, false, |c| sig.end(c))
+    }
+}
+impl Arg {
+    /// Parses an argument from input that may still be arriving, like
+    /// [`Interface::parse_streaming`].
+    ///
+    /// A function argument is complete once its signature is. Any other type is a single
+    /// word, which is only known to be complete once a character that cannot extend it
+    /// (such as `,` or `)`) follows, so `I32` alone is still incomplete.
+    pub fn parse_streaming(a: &str) -> IResult<&str, Arg, PitError> {
+        enum End {
+            Start,
+            Word,
+            Func(SigEnd),
+        }
+        let word = |c: char| c.is_ascii_alphanumeric() || "_$.@{}~+/&".contains(c);
+        let mut state = End::Start;
+        streaming(a, parse_arg_typed, false, |c| match &mut state {
+            End::Start if c == '^' => {
+                state = End::Func(SigEnd::default());
+                false
+            }
+            End::Start if word(c) => {
+                state = End::Word;
+                false
+            }
+            End::Start => c != ']' && !c.is_whitespace(),
+            End::Word => !word(c),
+            End::Func(sig) => sig.end(c),
+        })
+    }
+}
+impl FromStr for Interface {
+    type Err = PitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(s, parse_interface_typed)
+    }
+}
+impl FromStr for Sig {
+    type Err = PitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(s, parse_sig_typed)
+    }
+}
+impl FromStr for Arg {
+    type Err = PitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(s, parse_arg_typed)
+    }
+}
+impl FromStr for ResTy {
+    type Err = PitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(s, parse_resty_typed)
+    }
+}
+impl FromStr for Attr {
+    type Err = PitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(s, parse_attr_typed)
+    }
+}
+impl FromStr for Arity {
+    type Err = PitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(s, Arity::parse_typed)
+    }
+}
+impl Interface {
+    /// Parses an interface from the start of `s`, returning it with the unconsumed rest.
+    ///
+    /// Unlike [`FromStr`], trailing input is not an error, so interfaces can be read out of
+    /// a larger stream. Whitespace after the closing `}` is left in the rest.
+    pub fn parse_prefix(s: &str) -> Result<(Interface, &str), PitError> {
+        let (rest, iface) = parse_interface_typed(s).map_err(PitError::from_nom)?;
+        Ok((iface, rest))
+    }
+    /// Parses a whole interface like [`FromStr`], with the given [`ParseOptions`].
+    pub fn parse_with(s: &str, opts: &ParseOptions) -> Result<Interface, PitError> {
+        parse_all(s, |a| parse_interface_with(a, opts))
+    }
+    /// Parses an interface from input that may still be arriving, e.g. over a socket.
+    ///
+    /// Returns `Err(nom::Err::Incomplete(_))` until the interface's closing `}` is present,
+    /// then parses it like [`parse_interface`], so the caller can append more bytes and
+    /// retry. Brackets inside annotations are matched (with `\` escapes under `escFmtVer`)
+    /// and cannot end the interface early. Input that cannot start an interface is reported
+    /// as an error straight away; errors inside an unfinished interface surface once it is
+    /// complete.
+    pub fn parse_streaming(a: &str) -> IResult<&str, Interface, PitError> {
+        let escapes = declares_escapes(a.trim_start());
+        let mut body = false;
+        let r = streaming(a, parse_interface_typed, escapes, |c| match c {
+            '{' if !body => {
+                body = true;
+                false
+            }
+            '}' => true,
+            _ => !body && c != ']' && !c.is_whitespace(),
+        });
+        match r {
+            // Until the annotations end, an `escFmtVer` still to come could change how the
+            // ones so far read, so an error may yet go away.
+            Err(nom::Err::Error(_) | nom::Err::Failure(_))
+                if !escapes && annotations_open(a.trim_start()) =>
+            {
+                Err(nom::Err::Incomplete(nom::Needed::Unknown))
+            }
+            r => r,
+        }
+    }
+    /// Parses an interface like [`parse_interface`] and computes its rid.
+    ///
+    /// The rid is of the canonical form, not of the source text, so it cannot be folded
+    /// into the parse itself. Instead the parsed interface is rendered once, straight into
+    /// the hasher: the cost is one parse plus one render, with no intermediate `String`
+    /// and no second pass over the input.
+    pub fn parse_and_rid(input: &str) -> IResult<&str, (Interface, [u8; 32]), PitError> {
+        let (rest, iface) = parse_interface_typed(input)?;
+        let rid = iface.rid();
+        Ok((rest, (iface, rid)))
+    }
+    pub fn rid(&self) -> [u8; 32] {
+        return self.hash_with::<Sha3_256>().into();
+    }
+    /// Streams the canonical form of the interface into `u`.
+    ///
+    /// This is the rendering of [`Interface::canonicalize`]; it only allocates when some
+    /// annotation list is out of order.
+    pub fn hash_into(&self, u: &mut dyn Update) {
+        use core::fmt::Write;
+        if self.is_canonical() {
+            write!(WriteUpdate { wrapped: u }, "{self}").unwrap();
+        } else {
+            write!(WriteUpdate { wrapped: u }, "{}", self.canonicalize()).unwrap();
+        }
+    }
+    /// Hashes the canonical form of the interface with the digest `D`.
+    ///
+    /// This is the resource ID for deployments that content-address with another digest;
+    /// `rid` is `hash_with::<Sha3_256>()`.
+    pub fn hash_with<D: Digest + Update>(&self) -> Output<D> {
+        let mut d = D::new();
+        self.hash_into(&mut d);
+        d.finalize()
+    }
+    pub fn rid_str(&self) -> String {
+        return hex::encode(self.rid());
+    }
+    /// Returns the interface's `wasmAbiVer` (as read by [`Attr::as_wasm_abi`]), or 0 if absent.
+    pub fn wasm_abi(&self) -> usize {
+        self.ann
+            .iter()
+            .find_map(|a| a.as_wasm_abi())
+            .unwrap_or_default()
+    }
+    /// Rewrites the interface for the ABI version `to_ver`, one version step at a time,
+    /// and updates its `wasmAbiVer` annotation.
+    ///
+    /// Downgrades are rejected. On error the interface is left unchanged.
+    pub fn migrate_abi(&mut self, to_ver: usize) -> Result<(), MigrationError> {
+        let from = self.wasm_abi();
+        if to_ver < from {
+            return Err(MigrationError::Downgrade { from, to: to_ver });
+        }
+        let mut out = self.clone();
+        for ver in from..to_ver {
+            out.migrate_abi_step(ver)?;
+        }
+        out.ann.retain(|a| a.name != "wasmAbiVer");
+        out.ann.extend(Attr::from_wasm_abi(to_ver));
+        out.ann.sort_by_key(|a| a.name.clone());
+        *self = out;
+        Ok(())
+    }
+    /// Applies the structural changes from ABI version `from` to `from + 1`.
+    fn migrate_abi_step(&mut self, from: usize) -> Result<(), MigrationError> {
+        match from {
+            // Version 1 only introduces the annotation.
+            0 => Ok(()),
+            _ => Err(MigrationError::Unsupported { to: from + 1 }),
+        }
+    }
+    /// Maps each method's [`method_selector`] to its name.
+    ///
+    /// Fails on the first pair of methods whose selectors collide.
+    pub fn selectors(&self) -> Result<BTreeMap<[u8; 4], String>, SelectorCollision> {
+        self.selectors_by(method_selector)
+    }
+    fn selectors_by(
+        &self,
+        selector: impl Fn(&str, &Sig) -> [u8; 4],
+    ) -> Result<BTreeMap<[u8; 4], String>, SelectorCollision> {
+        let mut out = BTreeMap::new();
+        for (name, sig) in &self.methods {
+            let sel = selector(name, sig);
+            if let Some(prev) = out.insert(sel, name.clone()) {
+                return Err(SelectorCollision {
+                    selector: sel,
+                    methods: (prev, name.clone()),
+                });
+            }
+        }
+        Ok(out)
+    }
+    /// Returns the first 16 bytes of the resource ID.
+    ///
+    /// Short ids are not guaranteed unique; see [`short_id_collisions`].
+    pub fn short_id(&self) -> [u8; 16] {
+        truncate_rid(&self.rid())
+    }
+    /// Returns the resource ID as unpadded lowercase base32.
+    pub fn rid_base32(&self) -> String {
+        self.rid_encoded(RidEncoding::Base32)
+    }
+    /// Returns the resource ID in the given encoding.
+    pub fn rid_encoded(&self, encoding: RidEncoding) -> String {
+        encoding.encode(&self.rid())
+    }
+    /// Calls `f` on every argument in the interface, including those nested in function arguments.
+    fn for_each_arg(&self, f: &mut dyn FnMut(&Location, &Arg)) {
+        fn go(sig: &Sig, loc: &mut Location, f: &mut dyn FnMut(&Location, &Arg)) {
+            for (side, args) in [(ArgSide::Param, &sig.params), (ArgSide::Return, &sig.rets)] {
+                for (i, a) in args.iter().enumerate() {
+                    loc.path.push((side, i));
+                    f(loc, a);
+                    if let ArgTy::Func(inner) = &a.ty {
+                        go(inner, loc, f);
+                    }
+                    loc.path.pop();
+                }
+            }
+        }
+        for (name, sig) in self.methods.iter() {
+            let mut loc = Location {
+                method: name.clone(),
+                path: vec![],
+            };
+            go(sig, &mut loc, f);
+        }
+    }
+    /// Returns the ids of the interfaces this one refers to through `ResTy::Of`.
+    ///
+    /// Every resource argument is scanned, including inside function arguments; `this`,
+    /// untyped and named resources are skipped. Meant for building dependency graphs.
+    pub fn referenced_rids(&self) -> BTreeSet<[u8; 32]> {
+        let mut rids = BTreeSet::new();
+        self.for_each_arg(&mut |_, a| {
+            if let ArgTy::Resource {
+                ty: ResTy::Of(rid), ..
+            } = &a.ty
+            {
+                rids.insert(*rid);
+            }
+        });
+        rids
+    }
+    /// Fails with the location of every resource argument whose type is `ResTy::None`.
+    ///
+    /// Untyped resources are almost always authoring mistakes, so this is meant as a strict
+    /// import gate.
+    pub fn reject_none_resources(&self) -> Result<(), Vec<Location>> {
+        let mut bad = vec![];
+        self.for_each_arg(&mut |loc, a| {
+            if let ArgTy::Resource { ty: ResTy::None, .. } = a.ty {
+                bad.push(loc.clone());
+            }
+        });
+        if bad.is_empty() {
+            Ok(())
+        } else {
+            Err(bad)
+        }
+    }
+    /// Returns the names of methods whose parameters and returns are all numeric primitives.
+    pub fn primitive_methods(&self) -> Vec<&str> {
+        self.methods
+            .iter()
+            .filter(|(_, s)| s.params.iter().chain(&s.rets).all(|a| a.ty.is_primitive()))
+            .map(|(n, _)| n.as_str())
+            .collect()
+    }
+    /// Compares this interface (the old version) with `other` (the new version).
+    ///
+    /// Arguments are compared by position, and annotations are compared regardless of
+    /// order.
+    pub fn diff(&self, other: &Interface) -> InterfaceDiff {
+        let mut d = InterfaceDiff {
+            ann_changed: !same_ann(&self.ann, &other.ann),
+            ..Default::default()
+        };
+        for (name, old) in self.methods.iter() {
+            match other.methods.get(name) {
+                None => d.removed.push(name.clone()),
+                Some(new) => {
+                    let m = MethodDiff {
+                        params: diff_args(&old.params, &new.params),
+                        rets: diff_args(&old.rets, &new.rets),
+                        ann_changed: !same_ann(&old.ann, &new.ann),
+                    };
+                    if !m.is_empty() {
+                        d.changed.insert(name.clone(), m);
+                    }
+                }
+            }
+        }
+        d.added = other
+            .methods
+            .keys()
+            .filter(|n| !self.methods.contains_key(*n))
+            .cloned()
+            .collect();
+        d
+    }
+    /// Applies a `[resourceDefault=borrow]` interface annotation to the resource arguments.
+    ///
+    /// The grammar has no implicit ownership: a resource without a `&` suffix is taken. So
+    /// under this annotation a taken resource counts as under-specified unless it carries a
+    /// `[take]` flag, and is turned into a borrow. Borrowed resources are already explicit
+    /// and are left alone, as is everything when the annotation is absent or `take`. The
+    /// annotation is kept, so applying the defaults again changes nothing.
+    pub fn apply_resource_defaults(&mut self) {
+        fn go(args: &mut [Arg]) {
+            for a in args {
+                match &mut a.ty {
+                    ArgTy::Resource { take, .. } if !a.ann.iter().any(|x| x.name == "take") => {
+                        *take = false;
+                    }
+                    ArgTy::Func(sig) => {
+                        go(&mut sig.params);
+                        go(&mut sig.rets);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if !self
+            .ann
+            .iter()
+            .any(|a| a.name == "resourceDefault" && a.value == "borrow")
+        {
+            return;
+        }
+        for sig in self.methods.values_mut() {
+            go(&mut sig.params);
+            go(&mut sig.rets);
+        }
+    }
+    /// Checks that this interface can replace `older` without breaking its callers.
+    ///
+    /// Every method of `older` must still exist with the same number of parameters and
+    /// returns, each of the same type; annotations are ignored. The one allowed change is
+    /// in resource nullability: a parameter may become nullable, and a return may stop
+    /// being nullable. The opposite transitions are reported as
+    /// [`Incompatibility::NullabilityNarrowed`].
+    pub fn is_compatible_with(&self, older: &Interface) -> Result<(), Vec<Incompatibility>> {
+        let mut out = vec![];
+        for (name, old) in older.methods.iter() {
+            let Some(new) = self.methods.get(name) else {
+                out.push(Incompatibility::MissingMethod(name.clone()));
+                continue;
+            };
+            for (side, o, n) in [
+                (ArgSide::Param, &old.params, &new.params),
+                (ArgSide::Return, &old.rets, &new.rets),
+            ] {
+                if o.len() != n.len() {
+                    out.push(Incompatibility::CountChanged {
+                        method: name.clone(),
+                        side,
+                        old: o.len(),
+                        new: n.len(),
+                    });
+                    continue;
+                }
+                for (index, (o, n)) in o.iter().zip(n).enumerate() {
+                    let resource = |a: &Arg| match &a.ty {
+                        ArgTy::Resource {
+                            ty,
+                            nullable,
+                            take,
+                        } => Some((ty.clone(), *take, *nullable)),
+                        _ => None,
+                    };
+                    match (resource(o), resource(n)) {
+                        (Some((ot, otake, onull)), Some((nt, ntake, nnull)))
+                            if ot == nt && otake == ntake =>
+                        {
+                            let narrowed = match side {
+                                ArgSide::Param => onull && !nnull,
+                                ArgSide::Return => !onull && nnull,
+                            };
+                            if narrowed {
+                                out.push(Incompatibility::NullabilityNarrowed {
+                                    method: name.clone(),
+                                    side,
+                                    index,
+                                });
+                            }
+                        }
+                        _ if o.ty == n.ty => {}
+                        _ => out.push(Incompatibility::TypeChanged {
+                            method: name.clone(),
+                            side,
+                            index,
+                            old: o.ty.clone(),
+                            new: n.ty.clone(),
+                        }),
+                    }
+                }
+            }
+        }
+        if out.is_empty() {
+            Ok(())
+        } else {
+            Err(out)
+        }
+    }
+    /// Combines the methods and annotations of two interfaces.
+    ///
+    /// Annotations are combined with [`merge`], so `other` wins on a name clash. A method
+    /// present in both must have the same signature in each; otherwise the first such
+    /// method is reported.
+    pub fn merge(mut self, other: Interface) -> Result<Interface, MergeConflict> {
+        for (name, sig) in other.methods {
+            match self.methods.get(&name) {
+                Some(ours) if *ours != sig => {
+                    return Err(MergeConflict {
+                        ours: Box::new(ours.clone()),
+                        method: name,
+                        theirs: Box::new(sig),
+                    });
+                }
+                _ => {
+                    self.methods.insert(name, sig);
+                }
+            }
+        }
+        self.ann = merge(self.ann, other.ann);
+        Ok(self)
+    }
+    /// Returns a clone with every annotation removed, at every level.
+    ///
+    /// This includes the version attributes, so the clone renders with the defaults.
+    pub fn strip_annotations(&self) -> Interface {
+        fn sig(s: &Sig) -> Sig {
+            Sig {
+                ann: vec![],
+                params: s.params.iter().map(arg).collect(),
+                rets: s.rets.iter().map(arg).collect(),
+            }
+        }
+        fn arg(a: &Arg) -> Arg {
+            Arg::new(match &a.ty {
+                ArgTy::Func(s) => ArgTy::Func(Box::new(sig(s))),
+                t => t.clone(),
+            })
+        }
+        Interface {
+            methods: self
+                .methods
+                .iter()
+                .map(|(n, s)| (n.clone(), sig(s)))
+                .collect(),
+            ann: vec![],
+        }
+    }
+    /// Returns a copy with every annotation list sorted by name.
+    ///
+    /// Covers the interface, each signature and each argument, including inside function
+    /// arguments. The sort is stable, so annotations sharing a name keep their relative
+    /// order. Whitespace is not stored, so the rendering of the result is already in
+    /// canonical form; two interfaces that differ only in the order of differently named
+    /// annotations canonicalize to the same value. [`Interface::rid`] hashes the canonical
+    /// form.
+    pub fn canonicalize(&self) -> Interface {
+        Interface {
+            methods: self
+                .methods
+                .iter()
+                .map(|(n, s)| (n.clone(), s.canonicalize()))
+                .collect(),
+            ann: sorted_by_name(&self.ann),
+        }
+    }
+    /// Whether every annotation list is already in [`Interface::canonicalize`] order.
+    fn is_canonical(&self) -> bool {
+        is_sorted_by_name(&self.ann) && self.methods.values().all(Sig::is_canonical)
+    }
+    /// Compares method names and argument types only, ignoring annotations at every level.
+    pub fn structurally_eq(&self, other: &Interface) -> bool {
+        self.strip_annotations() == other.strip_annotations()
+    }
+    /// Returns the names of the methods whose signature [is pure](Sig::is_pure).
+    pub fn pure_methods(&self) -> BTreeSet<String> {
+        self.methods
+            .iter()
+            .filter(|(_, s)| s.is_pure())
+            .map(|(n, _)| n.clone())
+            .collect()
+    }
+    /// Returns the longest dotted namespace shared by every method name, without the
+    /// trailing `.`.
+    ///
+    /// Only whole segments count, and a method's last segment is its own name, so
+    /// `db.get` and `db.put` share `db` while `db` and `db.get` share nothing.
+    pub fn common_method_prefix(&self) -> Option<String> {
+        let mut names = self.methods.keys().map(|n| {
+            let mut segs: Vec<&str> = n.split('.').collect();
+            segs.pop();
+            segs
+        });
+        let mut prefix = names.next()?;
+        for segs in names {
+            let shared = prefix.iter().zip(&segs).take_while(|(a, b)| a == b).count();
+            prefix.truncate(shared);
+        }
+        if prefix.is_empty() {
+            None
+        } else {
+            Some(prefix.join("."))
+        }
+    }
+    /// Returns `(method, index, param)` for every parameter, methods in name order.
+    ///
+    /// Only the methods' own parameters are visited, not those of function arguments.
+    pub fn iter_params(&self) -> impl Iterator<Item = (&str, usize, &Arg)> {
+        self.methods
+            .iter()
+            .flat_map(|(n, s)| s.params.iter().enumerate().map(move |(i, a)| (n.as_str(), i, a)))
+    }
+    /// Like [`Interface::iter_params`], for return values.
+    pub fn iter_returns(&self) -> impl Iterator<Item = (&str, usize, &Arg)> {
+        self.methods
+            .iter()
+            .flat_map(|(n, s)| s.rets.iter().enumerate().map(move |(i, a)| (n.as_str(), i, a)))
+    }
+    /// Returns every attribute in the interface with where it is attached.
+    ///
+    /// Interface annotations come first, then each method in name order, depth first.
+    pub fn all_attrs(&self) -> impl Iterator<Item = (AttrLocation, &Attr)> {
+        fn go<'a>(sig: &'a Sig, loc: &mut Location, out: &mut Vec<(AttrLocation, &'a Attr)>) {
+            out.extend(sig.ann.iter().map(|a| (AttrLocation::Sig(loc.clone()), a)));
+            for (side, args) in [(ArgSide::Param, &sig.params), (ArgSide::Return, &sig.rets)] {
+                for (i, a) in args.iter().enumerate() {
+                    loc.path.push((side, i));
+                    out.extend(a.ann.iter().map(|x| (AttrLocation::Arg(loc.clone()), x)));
+                    if let ArgTy::Func(inner) = &a.ty {
+                        go(inner, loc, out);
+                    }
+                    loc.path.pop();
+                }
+            }
+        }
+        let mut out: Vec<_> = self.ann.iter().map(|a| (AttrLocation::Interface, a)).collect();
+        for (name, sig) in self.methods.iter() {
+            let mut loc = Location {
+                method: name.clone(),
+                path: vec![],
+            };
+            go(sig, &mut loc, &mut out);
+        }
+        out.into_iter()
+    }
+    /// Like [`Interface::all_attrs`], followed by every attribute in `info`, the info entry
+    /// describing this interface.
+    ///
+    /// The entry's own attributes come first, then each method in name order: its own
+    /// attributes, then its params, then its returns, each by index.
+    pub fn all_attrs_with<'a>(
+        &'a self,
+        info: &'a info::InfoEntry,
+    ) -> impl Iterator<Item = (AttrLocation, &'a Attr)> {
+        let mut out: Vec<_> = self.all_attrs().collect();
+        out.extend(info.attrs.iter().map(|a| (AttrLocation::Info, a)));
+        for (name, m) in info.methods.iter() {
+            out.extend(m.attrs.iter().map(|a| (AttrLocation::InfoMethod(name.clone()), a)));
+            for (side, entries) in [(ArgSide::Param, &m.params), (ArgSide::Return, &m.returns)] {
+                for (i, p) in entries.iter() {
+                    let loc = Location {
+                        method: name.clone(),
+                        path: vec![(side, *i)],
+                    };
+                    out.extend(p.attrs.iter().map(|a| (AttrLocation::InfoArg(loc.clone()), a)));
+                }
+            }
+        }
+        out.into_iter()
+    }
+    /// Returns the interface with `attr` added to its annotations, keeping them sorted.
+    pub fn with_annotation(mut self, attr: Attr) -> Interface {
+        self.ann.push(attr);
+        self.ann.sort_by_key(|a| a.name.clone());
+        self
+    }
+    /// Returns the interface without any annotation named `name`.
+    pub fn without_annotation(mut self, name: &str) -> Interface {
+        self.ann.retain(|a| a.name != name);
+        self
+    }
+    /// Runs every available check over the interface, collecting all problems found.
+    ///
+    /// Checks for `ResTy::None` resources, named resources whose name is not a valid
+    /// identifier, repeated or conflicting version attributes, version values that are
+    /// not hex numbers, and method names that are not valid identifiers.
+    pub fn validate(&self) -> Result<(), Vec<Problem>> {
+        let mut problems = vec![];
+        if let Err(locs) = self.reject_none_resources() {
+            problems.extend(locs.into_iter().map(Problem::NoneResource));
+        }
+        self.for_each_arg(&mut |loc, a| {
+            if let ArgTy::Resource {
+                ty: ResTy::Named(name),
+                ..
+            } = &a.ty
+                && !matches!(ident_typed(name), Ok(("", _)))
+            {
+                problems.push(Problem::InvalidResourceName(loc.clone(), name.clone()));
+            }
+        });
+        for key in VERSION_ATTRS {
+            let values: Vec<&str> = self
+                .ann
+                .iter()
+                .filter(|a| a.name == *key)
+                .map(|a| a.value.as_str())
+                .collect();
+            if values.len() > 1 {
+                problems.push(if values.iter().all(|v| *v == values[0]) {
+                    Problem::DuplicateVersionAttr(key.to_string())
+                } else {
+                    Problem::ConflictingVersionAttr {
+                        name: key.to_string(),
+                        values: values.iter().map(|v| v.to_string()).collect(),
+                    }
+                });
+            }
+            // A value `as_ver` cannot read is silently treated as version 0 when rendering.
+            for a in self.ann.iter().filter(|a| a.name == *key) {
+                if a.as_ver(key).is_none() {
+                    problems.push(Problem::InvalidVersionValue {
+                        name: key.to_string(),
+                        value: a.value.clone(),
+                    });
+                }
+            }
+        }
+        for name in self.methods.keys() {
+            if !matches!(ident_typed(name), Ok(("", _))) {
+                problems.push(Problem::InvalidMethodName(name.clone()));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+    /// Returns whether `this` appears anywhere, including inside function arguments.
+    pub fn uses_this(&self) -> bool {
+        let mut found = false;
+        self.for_each_arg(&mut |_, a| {
+            found |= matches!(a.ty, ArgTy::Resource { ty: ResTy::This, .. });
+        });
+        found
+    }
+    /// Returns whether the interface looks like a resource interface: some method takes or
+    /// returns `this` directly, not only inside a function argument.
+    pub fn is_resource_interface(&self) -> bool {
+        self.methods.values().any(|s| {
+            s.params
+                .iter()
+                .chain(s.rets.iter())
+                .any(|a| matches!(a.ty, ArgTy::Resource { ty: ResTy::This, .. }))
+        })
+    }
+    /// Like [`Interface::validate`], additionally rejecting `this` in an interface that is
+    /// not a resource interface (see [`Interface::is_resource_interface`]).
+    ///
+    /// There `this` only occurs inside function arguments, where it has no resource to
+    /// refer to; each such use is reported as [`Problem::ThisOutsideResource`].
+    pub fn validate_strict(&self) -> Result<(), Vec<Problem>> {
+        let mut problems = self.validate().err().unwrap_or_default();
+        if !self.is_resource_interface() {
+            self.for_each_arg(&mut |loc, a| {
+                if let ArgTy::Resource { ty: ResTy::This, .. } = a.ty {
+                    problems.push(Problem::ThisOutsideResource(loc.clone()));
+                }
+            });
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+    /// Counts the methods and the shapes of their signatures; see [`InterfaceSummary`].
+    pub fn summary(&self) -> InterfaceSummary {
+        let mut out = InterfaceSummary {
+            methods: self.methods.len(),
+            is_resource_interface: self.is_resource_interface(),
+            ..Default::default()
+        };
+        for sig in self.methods.values() {
+            out.params += sig.params.len();
+            out.returns += sig.rets.len();
+            for a in sig.params.iter().chain(sig.rets.iter()) {
+                match &a.ty {
+                    ArgTy::Resource { .. } => out.resources += 1,
+                    ArgTy::Func(_) => out.funcs += 1,
+                    _ => out.primitives += 1,
+                }
+            }
+        }
+        out
+    }
+    /// Like [`Interface::validate`], additionally checking that `info` only documents
+    /// methods, parameters, and returns the interface declares.
+    pub fn validate_with_info(&self, info: &info::InfoEntry) -> Result<(), Vec<Problem>> {
+        let mut problems = self.validate().err().unwrap_or_default();
+        for e in info.validate_against(self).err().unwrap_or_default() {
+            problems.push(match e {
+                info::InfoError::UnknownMethod(name) => Problem::UnknownInfoMethod(name),
+                info::InfoError::IndexOutOfRange {
+                    method,
+                    side,
+                    index,
+                    ..
+                } => Problem::InfoIndexOutOfRange {
+                    method,
+                    side,
+                    index,
+                },
+            });
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+    /// Panics listing every problem reported by [`Interface::validate`].
+    ///
+    /// Intended as a single gate in tests.
+    #[track_caller]
+    pub fn assert_valid(&self) {
+        if let Err(problems) = self.validate() {
+            let list: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            panic!("invalid interface: {}", list.join("; "));
+        }
+    }
+}
+/// Whether two annotation lists hold the same attributes, in any order.
+fn same_ann(a: &[Attr], b: &[Attr]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
+}
+fn diff_args(old: &[Arg], new: &[Arg]) -> Vec<ArgDiff> {
+    let mut out = vec![];
+    for index in 0..old.len().max(new.len()) {
+        match (old.get(index), new.get(index)) {
+            (Some(o), Some(n)) => {
+                let ty_changed = o.ty != n.ty;
+                let ann_changed = !same_ann(&o.ann, &n.ann);
+                if ty_changed || ann_changed {
+                    out.push(ArgDiff::Changed {
+                        index,
+                        old: o.clone(),
+                        new: n.clone(),
+                        ty_changed,
+                        ann_changed,
+                    });
+                }
+            }
+            (Some(o), None) => out.push(ArgDiff::Removed {
+                index,
+                arg: o.clone(),
+            }),
+            (None, Some(n)) => out.push(ArgDiff::Added {
+                index,
+                arg: n.clone(),
+            }),
+            (None, None) => {}
+        }
+    }
+    out
+}
+/// Counts describing an interface, from [`Interface::summary`].
+///
+/// Only the methods' own parameters and returns are counted; a function argument counts
+/// once, in `funcs`, without its inner signature.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct InterfaceSummary {
+    /// Number of methods.
+    pub methods: usize,
+    /// Parameters across all methods.
+    pub params: usize,
+    /// Return values across all methods.
+    pub returns: usize,
+    /// Parameters and returns that are resources.
+    pub resources: usize,
+    /// Parameters and returns of a primitive type.
+    pub primitives: usize,
+    /// Parameters and returns that are function arguments.
+    pub funcs: usize,
+    /// Whether some method takes or returns `this`; see [`Interface::is_resource_interface`].
+    pub is_resource_interface: bool,
+}
+/// Differences between two versions of an interface, from [`Interface::diff`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct InterfaceDiff {
+    /// Methods only in the new version.
+    pub added: Vec<String>,
+    /// Methods only in the old version.
+    pub removed: Vec<String>,
+    /// Methods in both versions whose signatures differ.
+    pub changed: BTreeMap<String, MethodDiff>,
+    /// Whether the interface-level annotations differ.
+    pub ann_changed: bool,
+}
+impl InterfaceDiff {
+    /// Returns `true` if the two interfaces are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && !self.ann_changed
+    }
+}
+/// Differences between two versions of a method signature.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct MethodDiff {
+    /// Parameter differences, by position.
+    pub params: Vec<ArgDiff>,
+    /// Return differences, by position.
+    pub rets: Vec<ArgDiff>,
+    /// Whether the signature annotations differ.
+    pub ann_changed: bool,
+}
+impl MethodDiff {
+    /// Returns `true` if the two signatures are the same.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty() && self.rets.is_empty() && !self.ann_changed
+    }
+}
+/// A difference at one argument position.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ArgDiff {
+    /// An argument only in the new version.
+    Added { index: usize, arg: Arg },
+    /// An argument only in the old version.
+    Removed { index: usize, arg: Arg },
+    /// An argument whose type or annotations differ.
+    Changed {
+        index: usize,
+        old: Arg,
+        new: Arg,
+        ty_changed: bool,
+        ann_changed: bool,
+    },
+}
+/// A resource type that a signature both takes and borrows, from
+/// [`Sig::check_resource_consistency`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct ResourceConflict {
+    /// The resource type.
+    pub ty: ResTy,
+    /// Positions where it is owned (taken).
+    pub owned: Vec<(ArgSide, usize)>,
+    /// Positions where it is borrowed.
+    pub borrowed: Vec<(ArgSide, usize)>,
+}
+/// Error returned by [`Interface::merge`] when both sides declare a method differently.
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct MergeConflict {
+    /// The conflicting method.
+    pub method: String,
+    /// Its signature in the interface being merged into.
+    pub ours: Box<Sig>,
+    /// Its signature in the interface being merged in.
+    pub theirs: Box<Sig>,
+}
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "method `{}` is declared as `{}` and `{}`",
+            self.method, self.ours, self.theirs
+        )
+    }
+}
+impl core::error::Error for MergeConflict {}
+/// A breaking change reported by [`Interface::is_compatible_with`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum Incompatibility {
+    /// A method of the older interface is missing.
+    MissingMethod(String),
+    /// A method's parameter or return count changed.
+    CountChanged {
+        method: String,
+        side: ArgSide,
+        old: usize,
+        new: usize,
+    },
+    /// An argument's type changed.
+    TypeChanged {
+        method: String,
+        side: ArgSide,
+        index: usize,
+        old: ArgTy,
+        new: ArgTy,
+    },
+    /// A nullable resource parameter became non-nullable, or a non-nullable resource
+    /// return became nullable.
+    NullabilityNarrowed {
+        method: String,
+        side: ArgSide,
+        index: usize,
+    },
+}
+/// Version attributes that affect rendering, and so must appear at most once.
+const VERSION_ATTRS: &[&str] =
+    &["wasmAbiVer", "ridFmtVer", "primFmtVer", "attrFmtVer", "escFmtVer"];
+/// A problem reported by [`Interface::validate`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum Problem {
+    /// A resource argument with type `ResTy::None`.
+    NoneResource(Location),
+    /// A version attribute repeated with the same value.
+    DuplicateVersionAttr(String),
+    /// A version attribute repeated with differing values.
+    ConflictingVersionAttr { name: String, values: Vec<String> },
+    /// A version attribute whose value is not a hex number.
+    InvalidVersionValue { name: String, value: String },
+    /// A named resource whose name is not a valid identifier.
+    InvalidResourceName(Location, String),
+    /// A method name that is not a valid identifier.
+    InvalidMethodName(String),
+    /// Info documentation for a method the interface does not declare.
+    UnknownInfoMethod(String),
+    /// Info documentation for a parameter or return index past the end of the signature.
+    InfoIndexOutOfRange {
+        method: String,
+        side: ArgSide,
+        index: usize,
+    },
+    /// `this` used in an interface that is not a resource interface (strict mode only).
+    ThisOutsideResource(Location),
+}
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NoneResource(loc) => write!(f, "untyped resource at {loc}"),
+            Problem::DuplicateVersionAttr(name) => write!(f, "duplicate `{name}` attribute"),
+            Problem::ConflictingVersionAttr { name, values } => {
+                write!(f, "conflicting `{name}` values: {}", values.join(", "))
+            }
+            Problem::InvalidVersionValue { name, value } => {
+                write!(f, "`{name}` value `{value}` is not a hex number")
+            }
+            Problem::InvalidResourceName(loc, name) => {
+                write!(f, "invalid resource name `{name}` at {loc}")
+            }
+            Problem::InvalidMethodName(name) => write!(f, "invalid method name `{name}`"),
+            Problem::UnknownInfoMethod(name) => write!(f, "info documents unknown method `{name}`"),
+            Problem::InfoIndexOutOfRange {
+                method,
+                side,
+                index,
+            } => match side {
+                ArgSide::Param => write!(f, "info documents missing param {index} of `{method}`"),
+                ArgSide::Return => write!(f, "info documents missing return {index} of `{method}`"),
+            },
+            Problem::ThisOutsideResource(loc) => {
+                write!(f, "`this` at {loc}, but no method takes or returns `this`")
+            }
+        }
+    }
+}
+pub mod info;
+pub mod registry;
+/// WIT export for the WebAssembly Component Model.
+#[cfg(feature = "wit")]
+pub mod wit;
+/// JSON Schema export.
+#[cfg(feature = "json-schema")]
+pub mod json_schema;
+
+#[cfg(feature = "testing")]
+pub mod fuzz;
+pub fn retuple(a: Vec<Arg>) -> Interface {
+    Interface {
+        methods: a
+            .into_iter()
+            .enumerate()
+            .map(|(a, b)| {
+                (
+                    format!("v{a}"),
+                    Sig {
+                        ann: vec![],
+                        params: vec![],
+                        rets: vec![b],
+                    },
+                )
+            })
+            .collect(),
+        ann: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse options with escapes, as under `escFmtVer`.
+    const ESC: ParseOptions = ParseOptions {
+        bare_ids: false,
+        strict_ids: false,
+        escapes: true,
+    };
+
+    /// Renders `a` as an interface declaring `escFmtVer` would.
+    fn escaped(a: &Attr) -> String {
+        struct Esc<'a>(&'a Attr);
+        impl Display for Esc<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                self.0.render(f, &RenderCtx::default().with("escFmtVer", 1))
+            }
+        }
+        Esc(a).to_string()
+    }
+
+    #[test]
+    fn test_primitive_with_attributes() {
+        // Test creating primitive types with attributes
+        let i32_with_attr = ArgTy::I32.with_attrs(vec![
+            Attr {
+                name: "name".to_owned(),
+                value: "counter".to_owned(),
+            }
+        ]);
+        
+        // Verify the structure
+        assert_eq!(i32_with_attr.ann.len(), 1);
+        assert_eq!(i32_with_attr.ann[0].name, "name");
+        assert_eq!(i32_with_attr.ann[0].value, "counter");
+        assert!(matches!(i32_with_attr.ty, ArgTy::I32));
+        
+        // Test display formatting includes attributes
+        let formatted = format!("{}", i32_with_attr);
+        assert!(formatted.contains("[name=counter]"));
+        assert!(formatted.contains("I32"));
+    }
+
+    #[test]
+    fn test_parsing_primitive_with_attributes() {
+        // Test parsing attributes on primitive types
+        let parsed_i32 = parse_arg("[name=test_counter]I32").unwrap().1;
+        let parsed_f64 = parse_arg("[doc=floating_point][precision=high]F64").unwrap().1;
+        
+        // Verify I32 parsing
+        assert_eq!(parsed_i32.ann.len(), 1);
+        assert_eq!(parsed_i32.ann[0].name, "name");
+        assert_eq!(parsed_i32.ann[0].value, "test_counter");
+        assert!(matches!(parsed_i32.ty, ArgTy::I32));
+        
+        // Verify F64 parsing with multiple attributes
+        assert_eq!(parsed_f64.ann.len(), 2);
+        // Attributes should be sorted by name
+        assert_eq!(parsed_f64.ann[0].name, "doc");
+        assert_eq!(parsed_f64.ann[0].value, "floating_point");
+        assert_eq!(parsed_f64.ann[1].name, "precision");
+        assert_eq!(parsed_f64.ann[1].value, "high");
+        assert!(matches!(parsed_f64.ty, ArgTy::F64));
+    }
+
+    #[test]
+    fn test_resource_attributes_still_work() {
+        // Test that resource types still work with attributes
+        let parsed_resource = parse_arg("[doc=test_resource]Rthisn").unwrap().1;
+        
+        assert_eq!(parsed_resource.ann.len(), 1);
+        assert_eq!(parsed_resource.ann[0].name, "doc");
+        assert_eq!(parsed_resource.ann[0].value, "test_resource");
+        
+        if let ArgTy::Resource { ty, nullable, take } = parsed_resource.ty {
+            assert!(matches!(ty, ResTy::This));
+            assert!(nullable);
+            assert!(take);
+        } else {
+            panic!("Expected Resource type");
+        }
+    }
+
+    #[test]
+    fn test_no_attributes_still_works() {
+        // Test that types without attributes still parse correctly
+        let parsed_i64 = parse_arg("I64").unwrap().1;
+        
+        assert_eq!(parsed_i64.ann.len(), 0);
+        assert!(matches!(parsed_i64.ty, ArgTy::I64));
+    }
+    
+    #[test]
+    fn test_convenience_methods() {
+        // Test convenience constructors
+        let i32_arg = Arg::i32();
+        assert!(matches!(i32_arg.ty, ArgTy::I32));
+        assert_eq!(i32_arg.ann.len(), 0);
+        
+        let f64_with_attr = Arg::f64().with_attr(Attr {
+            name: "precision".to_owned(),
+            value: "double".to_owned(),
+        });
+        assert!(matches!(f64_with_attr.ty, ArgTy::F64));
+        assert_eq!(f64_with_attr.ann.len(), 1);
+        assert_eq!(f64_with_attr.ann[0].name, "precision");
+        
+        let resource_arg = Arg::resource(ResTy::This, true, false);
+        if let ArgTy::Resource { ty, nullable, take } = resource_arg.ty {
+            assert!(matches!(ty, ResTy::This));
+            assert!(nullable);
+            assert!(!take);
+        } else {
+            panic!("Expected Resource type");
+        }
+    }
+
+    #[test]
+    fn test_compact_primitives() {
+        let src = "{add(I32,I64) -> (F32,F64)}";
+        let (_, verbose) = parse_interface(src).unwrap();
+        assert_eq!(verbose.to_string(), "{add(I32,I64) -> (F32,F64)}");
+
+        // The verbose rid is the hash of the unchanged canonical text
+        let mut h = Sha3_256::default();
+        Digest::update(&mut h, src.as_bytes());
+        let expected: [u8; 32] = h.finalize().into();
+        assert_eq!(verbose.rid(), expected);
+
+        // Transmission form selected via options parses back to the same interface
+        let terse = verbose.to_string_with(RenderOptions {
+            primitive_style: PrimitiveStyle::Compact,
+        });
+        assert_eq!(terse, "{add(i,l) -> (f,d)}");
+        assert_eq!(parse_interface(&terse).unwrap().1, verbose);
+
+        // Version-gated canonical compact form
+        let (_, compact) = parse_interface("[primFmtVer=0]{add(i,l) -> (f,d)}").unwrap();
+        assert_eq!(compact.to_string(), "[primFmtVer=0]{add(i,l) -> (f,d)}");
+        assert_eq!(compact.methods, verbose.methods);
+        assert_eq!(parse_interface(&compact.to_string()).unwrap().1, compact);
+        assert_ne!(compact.rid(), verbose.rid());
+    }
+
+    #[test]
+    fn test_func_args() {
+        let src = "{map(^[pure=1](I32) -> (I32),Rthis&) -> (^(^(F64) -> ()) -> (I64))}";
+        let (rest, iface) = parse_interface(src).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(iface.to_string(), src);
+        assert_eq!(parse_interface(&iface.to_string()).unwrap().1, iface);
+
+        let map = &iface.methods["map"];
+        let ArgTy::Func(cb) = &map.params[0].ty else {
+            panic!("expected function argument");
+        };
+        assert!(map.params[0].ann.is_empty());
+        assert_eq!(cb.ann[0].name, "pure");
+        assert!(matches!(cb.params[0].ty, ArgTy::I32));
+
+        let ArgTy::Func(outer) = &map.rets[0].ty else {
+            panic!("expected function return");
+        };
+        assert!(matches!(&outer.params[0].ty, ArgTy::Func(inner) if inner.rets.is_empty()));
+
+        // Argument annotations stay distinct from the signature's own annotations
+        let (_, arg) = parse_arg("[name=cb]^[pure=1]() -> ()").unwrap();
+        assert_eq!(arg.ann[0].name, "name");
+        assert_eq!(arg.to_string(), "[name=cb]^[pure=1]() -> ()");
+    }
+
+    #[test]
+    fn test_v128() {
+        let (rest, sig) = parse_sig("(V128)->(V128)").unwrap();
+        assert!(rest.is_empty());
+        assert!(matches!(sig.params[0].ty, ArgTy::V128));
+        assert!(matches!(sig.rets[0].ty, ArgTy::V128));
+        assert_eq!(sig.to_string(), "(V128) -> (V128)");
+        assert_eq!(parse_sig(&sig.to_string()).unwrap().1, sig);
+
+        // Mixed with the three-character primitives
+        let (_, sig) = parse_sig("(I32,V128,F64)->()").unwrap();
+        assert_eq!(sig.to_string(), "(I32,V128,F64) -> ()");
+    }
+
+    #[test]
+    fn test_primitive_methods() {
+        let (_, iface) = parse_interface(
+            "{add(I32,I32) -> (I32);close(Rthis) -> ();get() -> (Rthis&);scale(F64,V128) -> (F32);tick() -> ()}",
+        )
+        .unwrap();
+        assert_eq!(iface.primitive_methods(), vec!["add", "scale", "tick"]);
+    }
+
+    #[test]
+    fn test_reject_none_resources() {
+        let (_, clean) = parse_interface("{get(Rthis&) -> (I32)}").unwrap();
+        assert_eq!(clean.reject_none_resources(), Ok(()));
+
+        let (_, bad) = parse_interface("{get(I32,R&) -> ();map(^(Rn) -> ()) -> ()}").unwrap();
+        let locs = bad.reject_none_resources().unwrap_err();
+        assert_eq!(
+            locs,
+            vec![
+                Location {
+                    method: "get".to_owned(),
+                    path: vec![(ArgSide::Param, 1)],
+                },
+                Location {
+                    method: "map".to_owned(),
+                    path: vec![(ArgSide::Param, 0), (ArgSide::Param, 0)],
+                },
+            ]
+        );
+        assert_eq!(locs[1].to_string(), "map.param[0].param[0]");
+    }
+
+    #[test]
+    fn test_escaped_attr_values() {
+        let attr = |v: &str| Attr {
+            name: "doc".to_owned(),
+            value: v.to_owned(),
+        };
+        let roundtrip = |a: &Attr| {
+            let s = escaped(a);
+            let (rest, b) = parse_attr_with(&s, &ESC).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(&b, a);
+        };
+
+        // Balanced content is rendered untouched
+        assert_eq!(escaped(&attr("array[0]")), "[doc=array[0]]");
+        roundtrip(&attr("array[0]"));
+
+        // Stray brackets are escaped
+        assert_eq!(escaped(&attr("a]b")), "[doc=a\\]b]");
+        assert_eq!(escaped(&attr("[x")), "[doc=\\[x]");
+        roundtrip(&attr("a]b"));
+        roundtrip(&attr("[x"));
+        roundtrip(&attr("]["));
+
+        // Backslashes
+        assert_eq!(escaped(&attr("C:\\dir")), "[doc=C:\\dir]");
+        assert_eq!(escaped(&attr("end\\")), "[doc=end\\\\]");
+        roundtrip(&attr("C:\\dir"));
+        roundtrip(&attr("end\\"));
+        roundtrip(&attr("\\\\"));
+        roundtrip(&attr("\\]"));
+
+        // Mixed balanced and escaped content
+        roundtrip(&attr("f[a[1]] ] \\[ [x"));
+        assert_eq!(escaped(&attr("[[]")), "[doc=\\[[]]");
+        assert_eq!(escaped(&attr("[]][")), "[doc=[]\\]\\[]");
+        let (_, parsed) = parse_attr_with("[doc=a\\]b[c]\\\\]", &ESC).unwrap();
+        assert_eq!(parsed.value, "a]b[c]\\");
+
+        // Every short bracket string escapes exactly the `[` that no later `]` brings the
+        // depth back down for, and the `]` left over after matching
+        for n in 0..4usize.pow(7) {
+            let v: String = (0..7).map(|k| ['[', ']', 'a', '['][n / 4usize.pow(k) % 4]).collect();
+            let mut expected = String::from("[doc=");
+            let mut open = 0;
+            for (i, c) in v.char_indices() {
+                let mut depth = 0;
+                let escape = match c {
+                    '[' => !v[i..].chars().any(|c| {
+                        depth += (c == '[') as i32 - (c == ']') as i32;
+                        depth == 0
+                    }),
+                    _ => false,
+                } || (c == ']' && open == 0);
+                open = match c {
+                    '[' if !escape => open + 1,
+                    ']' if !escape => open - 1,
+                    _ => open,
+                };
+                if escape {
+                    expected.push('\\');
+                }
+                expected.push(c);
+            }
+            expected.push(']');
+            assert_eq!(escaped(&attr(&v)), expected, "{v:?}");
+            roundtrip(&attr(&v));
+        }
+    }
+
+    #[test]
+    fn test_escapes_need_version() {
+        // Without `escFmtVer` a backslash is literal, as it always was, so interfaces
+        // written before escapes keep their meaning and rid
+        let src = r"{f([doc=end\]I32,[doc=x\[y]]I64,[doc=a\\b]F32,[doc=\ x]F64) -> ()}";
+        let iface: Interface = src.parse().unwrap();
+        let docs = |i: &Interface| -> Vec<String> {
+            i.methods["f"].params.iter().map(|a| a.ann[0].value.clone()).collect()
+        };
+        assert_eq!(docs(&iface), [r"end\", r"x\[y]", r"a\\b", r"\ x"]);
+        assert_eq!(iface.to_string(), src);
+        assert_eq!(
+            iface.rid_str(),
+            "c5d3a4fbb1e21c1de7dde6e8bbe65ab651a32a978ee8cf01a92ea3a3a9af160a"
+        );
+        assert_eq!(parse_attr(r"[doc=a\]").unwrap().1.value, r"a\");
+
+        // With it, the same backslashes escape, and values render escaped
+        let src = r"[escFmtVer=0]{f([doc=end\\]I32,[doc=x\[y]I64,[doc=a\]b]F32) -> ()}";
+        let esc: Interface = src.parse().unwrap();
+        assert_eq!(docs(&esc), [r"end\", "x[y", "a]b"]);
+        assert_eq!(esc.to_string(), src);
+        assert_eq!(Interface::parse_with(src, &ESC).unwrap(), esc);
+
+        // The version is found after annotations that only read right with escapes
+        let src = r"[doc=a\]b][escFmtVer=0]{}";
+        let esc: Interface = src.parse().unwrap();
+        assert_eq!(esc.ann[0].value, "a]b");
+        assert_eq!(esc.to_string(), src);
+    }
+
+    #[test]
+    fn test_escaped_attr_names() {
+        for (name, rendered) in [
+            ("a=b", "[a\\=b=v]"),
+            ("a]b", "[a\\]b=v]"),
+            ("a[b", "[a\\[b=v]"),
+            ("a\\b", "[a\\\\b=v]"),
+            (" lead", "[\\ lead=v]"),
+            ("plain.name", "[plain.name=v]"),
+        ] {
+            let attr = Attr {
+                name: name.to_owned(),
+                value: "v".to_owned(),
+            };
+            assert_eq!(escaped(&attr), rendered);
+            let (rest, parsed) = parse_attr_with(rendered, &ESC).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(parsed, attr);
+        }
+
+        // Escaped names and values combine
+        let attr = Attr {
+            name: "k=[".to_owned(),
+            value: "]".to_owned(),
+        };
+        assert_eq!(parse_attr_with(&escaped(&attr), &ESC).unwrap().1, attr);
+    }
+
+    #[test]
+    fn test_name_escapes_need_version() {
+        // Without `escFmtVer` a backslash in a name is literal and the name ends at the
+        // first `=`, so existing rids are unchanged
+        let src = r"[a\b=1][c\=d]{f([x\\y=z]I32) -> ()}";
+        let iface: Interface = src.parse().unwrap();
+        assert_eq!(iface.ann[0].name, r"a\b");
+        assert_eq!((&*iface.ann[1].name, &*iface.ann[1].value), (r"c\", "d"));
+        assert_eq!(iface.methods["f"].params[0].ann[0].name, r"x\\y");
+        assert_eq!(iface.to_string(), src);
+        assert_eq!(
+            iface.rid_str(),
+            "0140cd86ca06beafef4b0fe13a640f6c1d641742121ab84a369421cbf1fde78b"
+        );
+
+        // With it, the same text escapes
+        let src = r"[a\=b=1][escFmtVer=0]{}";
+        let esc: Interface = src.parse().unwrap();
+        assert_eq!(esc.ann[0].name, "a=b");
+        assert_eq!(esc.to_string(), src);
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let src = "{get(I32) -> (F64)}";
+        let iface: Interface = src.parse().unwrap();
+        assert_eq!(iface, parse_interface(src).unwrap().1);
+        assert!("(I32) -> ()\n".parse::<Sig>().is_ok());
+
+        let e = "{get(I32) -> (F64)} extra".parse::<Interface>().unwrap_err();
+        assert_eq!(e, PitError::TrailingInput { remaining: 5 });
+        assert_eq!(e.offset_in("{get(I32) -> (F64)} extra"), 20);
+
+        let e = "(I32) (F64)".parse::<Sig>().unwrap_err();
+        assert!(matches!(e, PitError::ExpectedToken { expected: "->", .. }));
+        assert_eq!(e.offset_in("(I32) (F64)"), 6);
+
+        let e = parse_arg_typed("X32").unwrap_err();
+        assert_eq!(e, nom::Err::Error(PitError::UnknownArgType { remaining: 3 }));
+
+        let e = "[a=b".parse::<Interface>().unwrap_err();
+        assert_eq!(
+            e,
+            PitError::ExpectedChar {
+                expected: '{',
+                remaining: 4
+            }
+        );
+        assert_eq!(e.to_string(), "expected `{`");
+    }
+
+    #[test]
+    fn test_func_depth_limit() {
+        let nested = |n: usize| {
+            alloc::format!("({}I32{}) -> ()", "^(".repeat(n), ") -> ()".repeat(n))
+        };
+        let sig: Sig = nested(MAX_FUNC_DEPTH).parse().unwrap();
+        assert!(matches!(sig.params[0].ty, ArgTy::Func(_)));
+
+        // One level more fails at the `^` that goes too deep
+        let src = nested(MAX_FUNC_DEPTH + 1);
+        let e = src.parse::<Sig>().unwrap_err();
+        assert!(matches!(e, PitError::TooDeep { .. }));
+        assert_eq!(e.offset_in(&src), 2 * MAX_FUNC_DEPTH + 1);
+        assert_eq!(e.to_string(), "function arguments nested too deeply");
+
+        // Far deeper input is rejected rather than overflowing the stack
+        let src = "^(".repeat(200_000) + "I32" + &") -> ()".repeat(200_000);
+        let e = parse_arg_typed(&src).unwrap_err();
+        assert!(matches!(e, nom::Err::Failure(PitError::TooDeep { .. })));
+        let iface = alloc::format!("{{f({src}) -> ()}}");
+        assert!(parse_interface(&iface).is_err());
+    }
+
+    #[test]
+    fn test_validate_collects_problems() {
+        let (_, good) = parse_interface("[ridFmtVer=0]{get(Rthis&) -> (I32)}").unwrap();
+        good.assert_valid();
+
+        let mut bad = parse_interface(
+            "[ridFmtVer=0][ridFmtVer=1][wasmAbiVer=0][wasmAbiVer=0]{get(R) -> (I32)}",
+        )
+        .unwrap()
+        .1;
+        bad.methods.insert("bad name".to_owned(), Sig {
+            ann: vec![],
+            params: vec![],
+            rets: vec![],
+        });
+        let problems = bad.validate().unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::NoneResource(Location {
+                    method: "get".to_owned(),
+                    path: vec![(ArgSide::Param, 0)],
+                }),
+                Problem::DuplicateVersionAttr("wasmAbiVer".to_owned()),
+                Problem::ConflictingVersionAttr {
+                    name: "ridFmtVer".to_owned(),
+                    values: vec!["0".to_owned(), "1".to_owned()],
+                },
+                Problem::InvalidMethodName("bad name".to_owned()),
+            ]
+        );
+
+        let (_, entry) = info::InfoEntry::parse(
+            "method get [doc=x]\nparam get 1 [doc=y]\nreturn get 0 [doc=z]\nmethod put [doc=w]\n",
+        )
+        .unwrap();
+        let problems = good.validate_with_info(&entry).unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::InfoIndexOutOfRange {
+                    method: "get".to_owned(),
+                    side: ArgSide::Param,
+                    index: 1,
+                },
+                Problem::UnknownInfoMethod("put".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "untyped resource at get.param[0]")]
+    fn test_assert_valid_panics() {
+        parse_interface("{get(R) -> ()}").unwrap().1.assert_valid();
+    }
+
+    #[test]
+    fn test_from_str_roundtrip() {
+        fn roundtrip<T: FromStr<Err = PitError> + Display + PartialEq + core::fmt::Debug>(x: &T) {
+            assert_eq!(&x.to_string().parse::<T>().unwrap(), x);
+        }
+
+        let rid = [0x5au8; 32];
+        roundtrip(&"[api=foo]{get([ver=1]Rthisn) -> (F64);set(I32) -> ()}".parse::<Interface>().unwrap());
+        roundtrip(&"[async=true](I32,R&) -> (V128)".parse::<Sig>().unwrap());
+        roundtrip(&Arg::resource(ResTy::Of(rid), true, false).with_attr(Attr {
+            name: "doc".to_owned(),
+            value: "a[b]".to_owned(),
+        }));
+        roundtrip(&ResTy::Of(rid));
+        roundtrip(&ResTy::This);
+        roundtrip(&ResTy::None);
+        roundtrip(&Attr {
+            name: "x".to_owned(),
+            value: "y[]".to_owned(),
+        });
+        let arity: Arity = "<T <> U <V <>>>".parse().unwrap();
+        assert!(arity.to_fill["T"].to_fill.is_empty());
+        assert!(arity.to_fill["U"].to_fill.contains_key("V"));
+        roundtrip(&arity);
+
+        // Trailing whitespace is accepted, anything else is not
+        assert!("I32 \n".parse::<Arg>().is_ok());
+        assert!(matches!("I32,".parse::<Arg>(), Err(PitError::TrailingInput { remaining: 1 })));
+        assert!("[a=b] [c=d]".parse::<Attr>().is_err());
+    }
+
+    #[test]
+    fn test_attr_leading_whitespace() {
+        for value in [" x", "\n\tx ", "\\ x", " "] {
+            let a = Attr {
+                name: "doc".to_owned(),
+                value: value.to_owned(),
+            };
+            assert_eq!(parse_attr_with(&escaped(&a), &ESC).unwrap().1, a, "{a}");
+        }
+        assert_eq!(
+            escaped(&Attr {
+                name: "doc".to_owned(),
+                value: " x".to_owned(),
+            }),
+            "[doc=\\ x]"
+        );
+        // Unescaped whitespace after `=` is still skipped.
+        assert_eq!(parse_attr_with("[doc= x]", &ESC).unwrap().1.value, "x");
+        assert_eq!(parse_attr_with("[doc=\\ x]", &ESC).unwrap().1.value, " x");
+        // Without escapes the backslash is part of the value.
+        assert_eq!("[doc=\\ x]".parse::<Attr>().unwrap().value, "\\ x");
+    }
+
+    #[test]
+    fn test_hash_with() {
+        let (_, iface) = parse_interface("[ridFmtVer=0]{get(I32) -> (F64)}").unwrap();
+        assert_eq!(<[u8; 32]>::from(iface.hash_with::<Sha3_256>()), iface.rid());
+        for src in ["{get(I32) -> (F64)}", "[ridFmtVer=1]{f(Rthis&,^(I64) -> ()) -> ()}"] {
+            let iface = parse_interface(src).unwrap().1;
+            let generic: [u8; 32] = iface.hash_with::<Sha3_256>().into();
+            assert_eq!(generic, iface.rid());
+            assert_ne!(iface.hash_with::<sha3::Keccak256>().as_slice(), &generic);
+        }
+
+        // Any `Update` sink sees exactly the canonical bytes
+        #[derive(Default)]
+        struct Collect(Vec<u8>);
+        impl Update for Collect {
+            fn update(&mut self, data: &[u8]) {
+                self.0.extend_from_slice(data);
+            }
+        }
+        let mut c = Collect::default();
+        iface.hash_into(&mut c);
+        assert_eq!(c.0, iface.to_string().into_bytes());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let rid = [0xabu8; 32];
+        let src = format!(
+            "[name=demo]{{get(I32) -> (R{}n,^(F64) -> ());close(Rthis&) -> ()}}",
+            hex::encode(rid)
+        );
+        let iface: Interface = src.parse().unwrap();
+        let json = serde_json::to_string(&iface).unwrap();
+        assert!(json.contains(&format!("\"{}\"", hex::encode(rid))));
+        let back: Interface = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, iface);
+    }
+
+    #[test]
+    fn test_collecting_unknown() {
+        let src = "[name=x][color=red]{get[pure=1][speed=fast](^[shape=round](I32) -> (),[doc=d]I32) -> ([weight=3]I64)}";
+        let known = BTreeSet::from(["name", "pure", "doc"]);
+        let (rest, (iface, unknown)) = parse_interface_collecting_unknown(src, &known).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(iface, parse_interface(src).unwrap().1);
+        assert_eq!(unknown, ["color", "shape", "speed", "weight"]);
+        let (_, (_, none)) =
+            parse_interface_collecting_unknown("[name=x]{get() -> ()}", &known).unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_interned_rid() {
+        let iface = parse_interface("{get(I32) -> (F64)}").unwrap().1;
+        let interned = InternedInterface::new(iface.clone());
+        assert_eq!(interned.rid(), iface.rid());
+        assert_eq!(interned.as_interface(), &iface);
+        // A stale rid is returned as-is: repeated calls read the cache rather than rehash.
+        let stale = InternedInterface {
+            rid: [7; 32],
+            iface: iface.clone(),
+        };
+        for _ in 0..10_000 {
+            assert_eq!(stale.rid(), [7; 32]);
+        }
+        assert_eq!(stale.into_interface(), iface);
+    }
+
+    #[test]
+    fn test_call_descriptor() {
+        let rid = [0x42u8; 32];
+        let src = format!(
+            "{{add(I32,I64) -> (F64);bind([doc=x]R{}n&,Rthis,^(F32) -> (V128)) -> ()}}",
+            hex::encode(rid)
+        );
+        let iface = parse_interface(&src).unwrap().1;
+        let add = iface.methods["add"].call_descriptor();
+        assert_eq!(add.params, [ArgKind::I32, ArgKind::I64]);
+        assert_eq!(add.rets, [ArgKind::F64]);
+        let bind = iface.methods["bind"].call_descriptor();
+        assert_eq!(
+            bind.params,
+            [
+                ArgKind::ExternRef(ResTy::Of(rid)),
+                ArgKind::ExternRef(ResTy::This),
+                ArgKind::FuncRef(Box::new(CallDescriptor {
+                    params: vec![ArgKind::F32],
+                    rets: vec![ArgKind::V128],
+                })),
+            ]
+        );
+        assert!(bind.rets.is_empty());
+    }
+
+    #[test]
+    fn test_rid_encodings() {
+        let iface = parse_interface("{get(I32) -> (F64)}").unwrap().1;
+        let rid = iface.rid();
+        assert_eq!(iface.rid_encoded(RidEncoding::Hex), iface.rid_str());
+        let b32 = iface.rid_base32();
+        assert_eq!(b32.len(), 52);
+        assert!(b32.bytes().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+        assert_eq!(RidEncoding::Base32.decode(&b32.to_ascii_uppercase()), Some(rid));
+        for e in RidEncoding::ALL {
+            let s = iface.rid_encoded(e);
+            assert_eq!(e.decode(&s), Some(rid));
+            assert_eq!(RidEncoding::decode_any(&s), Some(rid));
+            assert_eq!(e.decode(&s[1..]), None);
+        }
+        assert_eq!(RidEncoding::decode_any("not a rid"), None);
+    }
+
+    #[test]
+    fn test_git_form() {
+        let src = "[b=2][a=1]{add[pure=1][doc=sum](I32,I32) -> (I32);get(Rthis&) -> (F64);put([unit=ms]I64) -> ()}";
+        let iface = parse_interface(src).unwrap().1;
+        let git = iface.to_git_form();
+        assert_eq!(
+            git,
+            "[a=1]\n[b=2]\n{\n\tadd\n\t\t[doc=sum]\n\t\t[pure=1]\n\t\t(I32,I32) -> (I32);\n\tget(Rthis&) -> (F64);\n\tput([unit=ms]I64) -> ();\n}\n"
+        );
+        assert_eq!(git.parse::<Interface>().unwrap(), iface);
+
+        let mut edited = iface.clone();
+        edited.methods.get_mut("get").unwrap().rets[0] = Arg::i64();
+        let edited = edited.to_git_form();
+        let changed: Vec<_> = git
+            .lines()
+            .zip(edited.lines())
+            .filter(|(a, b)| a != b)
+            .collect();
+        assert_eq!(git.lines().count(), edited.lines().count());
+        assert_eq!(changed, [("\tget(Rthis&) -> (F64);", "\tget(Rthis&) -> (I64);")]);
+    }
+
+    #[test]
+    fn test_short_id_collisions() {
+        let ifaces: Vec<Interface> = (0..300)
+            .map(|i| parse_interface(&format!("[n={i}]{{get() -> ()}}")).unwrap().1)
+            .collect();
+        let refs: Vec<&Interface> = ifaces.iter().collect();
+        assert_eq!(refs[0].short_id(), refs[0].rid()[..16]);
+        assert!(short_id_collisions(&refs).is_empty());
+
+        // With one-byte ids, 300 interfaces must share some prefix.
+        let tiny = truncated_collisions::<1>(refs.iter().map(|i| i.rid()));
+        assert!(!tiny.is_empty());
+        for (prefix, idx) in &tiny {
+            assert!(idx.len() > 1);
+            assert!(idx.iter().all(|&i| refs[i].rid()[0] == prefix[0]));
+        }
+
+        let dup = short_id_collisions(&[refs[3], refs[7], refs[3]]);
+        assert_eq!(dup, [(refs[3].short_id(), vec![0, 2])]);
+    }
+
+    #[test]
+    fn test_flag_attrs() {
+        let flag = parse_attr("[deprecated]").unwrap().1;
+        assert!(flag.is_flag());
+        assert_eq!(flag.name, "deprecated");
+        assert_eq!(parse_attr("[deprecated=]").unwrap().1, flag);
+        assert!(!parse_attr("[x=1]").unwrap().1.is_flag());
+        // Without the version gate a flag keeps its explicit `=`.
+        assert_eq!(flag.to_string(), "[deprecated=]");
+
+        let old = parse_interface("{get[deprecated]() -> ()}").unwrap().1;
+        assert_eq!(old.to_string(), "{get[deprecated=]() -> ()}");
+        let new = parse_interface("[attrFmtVer=0]{get[deprecated]([x]I32) -> ()}").unwrap().1;
+        assert_eq!(new.to_string(), "[attrFmtVer=0]{get[deprecated]([x]I32) -> ()}");
+        assert_eq!(parse_interface(&new.to_string()).unwrap().1, new);
+        assert_eq!(new.to_git_form().parse::<Interface>().unwrap(), new);
+    }
+
+    #[test]
+    fn test_arity_key_bytes() {
+        let nested: Arity = "<T<U<V<>>>W<>>".parse().unwrap();
+        for a in [Arity::default(), nested.clone(), "<X<>>".parse().unwrap()] {
+            let bytes = a.to_key_bytes();
+            assert_eq!(Arity::from_key_bytes(&bytes), Some(a));
+        }
+        assert_eq!(
+            nested.to_key_bytes(),
+            [2, 1, b'T', 1, 1, b'U', 1, 1, b'V', 0, 1, b'W', 0]
+        );
+        let mut reordered = Arity::default();
+        reordered.to_fill.insert("W".to_owned(), Arity::default());
+        reordered.to_fill.insert("T".to_owned(), "<U<V<>>>".parse().unwrap());
+        assert_eq!(reordered.to_key_bytes(), nested.to_key_bytes());
+
+        let long = Arity {
+            to_fill: BTreeMap::from([("n".repeat(200), Arity::default())]),
+        };
+        assert_eq!(Arity::from_key_bytes(&long.to_key_bytes()), Some(long));
+
+        let bytes = nested.to_key_bytes();
+        assert_eq!(Arity::from_key_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Arity::from_key_bytes(&[bytes.as_slice(), &[0]].concat()), None);
+        assert_eq!(Arity::from_key_bytes(&[1, 1, 0xff, 0]), None);
+    }
+
+    #[test]
+    fn test_resty_hex_edge_cases() {
+        let lower = "ab".repeat(32);
+        let upper = "AB".repeat(32);
+        let mixed = "aB".repeat(32);
+        for src in [&upper, &mixed] {
+            assert_eq!(parse_resty(src).unwrap(), parse_resty(&lower).unwrap());
+        }
+        // 63 digits are not an id, so the resource is untyped and the digits are left over.
+        let short = "a".repeat(63);
+        assert_eq!(parse_resty(&short).unwrap(), (short.as_str(), ResTy::None));
+        // Non-ASCII digits never reach the decoder.
+        let arabic = "\u{663}".repeat(64);
+        assert_eq!(parse_resty(&arabic).unwrap(), (arabic.as_str(), ResTy::None));
+        let Err(nom::Err::Failure(e)) = decode_rid("zz", "zz") else {
+            panic!("decoded invalid hex");
+        };
+        assert_eq!(e.offset_in("zz"), 0);
+    }
+
+    #[test]
+    fn test_zero_resty() {
+        let zero_hex = "0".repeat(64);
+        let zero_b64 = format!(
+            "~b64{}~",
+            base64::engine::general_purpose::STANDARD_NO_PAD.encode([0u8; 32])
+        );
+        for src in [&zero_hex, &zero_b64] {
+            let (rest, r) = parse_resty(src).unwrap();
+            assert_eq!(rest, "");
+            assert!(r.is_zero());
+
+            let input = format!("{src}n");
+            let Err(nom::Err::Failure(e)) = parse_resty_strict(&input) else {
+                panic!("accepted zero id {src}");
+            };
+            assert_eq!(e, PitError::ZeroResourceId { remaining: input.len() });
+            assert_eq!(e.offset_in(&input), 0);
+        }
+        let one = format!("{}1&", "0".repeat(63));
+        let (rest, r) = parse_resty_strict(&one).unwrap();
+        assert_eq!(rest, "&");
+        assert!(!r.is_zero());
+        assert!(!ResTy::None.is_zero());
+        assert_eq!(parse_resty_strict("this").unwrap().1, ResTy::This);
+        assert_eq!(parse_resty_strict("").unwrap().1, ResTy::None);
+
+        // Strictness reaches ids anywhere in an interface, including the shorthand
+        let strict = ParseOptions {
+            strict_ids: true,
+            bare_ids: true,
+            ..ParseOptions::default()
+        };
+        for src in [
+            format!("{{f(I32, R{zero_hex}&) -> ()}}"),
+            format!("{{f(^() -> (R{zero_b64}n)) -> ()}}"),
+            format!("{{f() -> ({zero_hex})}}"),
+        ] {
+            let lenient = Interface::parse_with(&src, &ParseOptions::default());
+            assert_eq!(lenient.is_ok(), !src.ends_with(&format!("({zero_hex})}}")), "{src}");
+            let e = Interface::parse_with(&src, &strict).unwrap_err();
+            assert!(matches!(e, PitError::ZeroResourceId { .. }), "{src}: {e}");
+            let at = &src[e.offset_in(&src)..];
+            assert!(at.starts_with(&zero_hex) || at.starts_with(&zero_b64), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_normalized_display() {
+        let rid = [0x5au8; 32];
+        let hex_src = hex::encode(rid);
+        let b64_src = format!(
+            "~b64{}~",
+            base64::engine::general_purpose::STANDARD_NO_PAD.encode(rid)
+        );
+        let from_hex = parse_resty(&hex_src).unwrap().1;
+        let from_b64 = parse_resty(&b64_src).unwrap().1;
+        assert_eq!(from_hex, from_b64);
+        for r in [&from_hex, &from_b64] {
+            assert_eq!(r.normalized_display(RidFmt::Hex), hex_src);
+            assert_eq!(r.normalized_display(RidFmt::Base64), b64_src);
+        }
+        let a = parse_interface(&format!("{{f(R{hex_src}) -> ()}}")).unwrap().1;
+        let b = parse_interface(&format!("{{f(R{b64_src}) -> ()}}")).unwrap().1;
+        assert_eq!(a, b);
+        assert_eq!(ResTy::This.normalized_display(RidFmt::Base64), "this");
+        assert_eq!(ResTy::None.normalized_display(RidFmt::Hex), "");
+    }
+
+    #[test]
+    fn test_validate_enum() {
+        let allowed = ["io", "math"];
+        let ok = parse_attr("[category=io]").unwrap().1;
+        assert_eq!(ok.validate_enum("category", &allowed), Ok(()));
+        let other = parse_attr("[doc=anything]").unwrap().1;
+        assert_eq!(other.validate_enum("category", &allowed), Ok(()));
+        let bad = parse_attr("[category=gfx]").unwrap().1;
+        let err = bad.validate_enum("category", &allowed).unwrap_err();
+        assert_eq!(
+            err,
+            EnumError {
+                name: "category".to_owned(),
+                value: "gfx".to_owned(),
+                allowed: vec!["io".to_owned(), "math".to_owned()],
+            }
+        );
+        assert_eq!(err.to_string(), "`category` must be one of io, math, not `gfx`");
+    }
+
+    #[test]
+    fn test_arity_unify() {
+        let a: Arity = "<K<> V<T<>>>".parse().unwrap();
+        let b: Arity = "<V<T<>> K<>>".parse().unwrap();
+        assert_eq!(a.unify(&b), Some(a.clone()));
+        assert_eq!(Arity::default().unify(&Arity::default()), Some(Arity::default()));
+
+        let renamed: Arity = "<K<> W<T<>>>".parse().unwrap();
+        assert_eq!(a.unify(&renamed), None);
+        let fewer: Arity = "<K<>>".parse().unwrap();
+        assert_eq!(a.unify(&fewer), None);
+        assert_eq!(fewer.unify(&a), None);
+
+        let shallow: Arity = "<K<> V<>>".parse().unwrap();
+        let deep: Arity = "<K<> V<T<U<>>>>".parse().unwrap();
+        assert_eq!(a.unify(&shallow), None);
+        assert_eq!(a.unify(&deep), None);
+    }
+
+    #[test]
+    fn test_selectors() {
+        let iface = parse_interface("{add(I32,I32) -> (I32);get() -> (F64)}").unwrap().1;
+        let add = &iface.methods["add"];
+        let mut h = Sha3_256::default();
+        Digest::update(&mut h, b"add(I32,I32) -> (I32)");
+        assert_eq!(method_selector("add", add), h.finalize()[..4]);
+        // The name takes part, so the same signature under another name differs.
+        assert_ne!(method_selector("sum", add), method_selector("add", add));
+
+        let sels = iface.selectors().unwrap();
+        assert_eq!(sels.len(), 2);
+        assert_eq!(sels[&method_selector("get", &iface.methods["get"])], "get");
+        assert_eq!(iface.selectors(), iface.clone().selectors());
+
+        // Annotation order does not change the selector.
+        let (_, ordered) = parse_sig("[a=1][b=2](I32) -> ()").unwrap();
+        let mut shuffled = ordered.clone();
+        shuffled.ann.reverse();
+        assert_eq!(method_selector("f", &shuffled), method_selector("f", &ordered));
+
+        // Force a collision with a selector that only looks at the name length.
+        let crafted = parse_interface("{ab() -> ();cd() -> ();xyz() -> ()}").unwrap().1;
+        assert_eq!(
+            crafted.selectors_by(|n, _| [n.len() as u8; 4]),
+            Err(SelectorCollision {
+                selector: [2; 4],
+                methods: ("ab".to_owned(), "cd".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_arity_display_round_trip() {
+        // Small xorshift generator so the test is deterministic without extra dependencies.
+        struct Rng(u64);
+        impl Rng {
+            fn next(&mut self, n: u64) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0 % n
+            }
+        }
+        fn gen_arity(rng: &mut Rng, depth: u32) -> Arity {
+            let slots = if depth == 0 { 0 } else { rng.next(4) };
+            Arity {
+                to_fill: (0..slots)
+                    .map(|_| {
+                        let len = 1 + rng.next(3);
+                        let name: String = (0..len)
+                            .map(|_| b"abzT_0$."[rng.next(8) as usize] as char)
+                            .collect();
+                        (name, gen_arity(rng, depth - 1))
+                    })
+                    .collect(),
+            }
+        }
+        assert_eq!(
+            "<K<> V<T<>>>".parse::<Arity>().unwrap().to_string(),
+            "<K <> V <T <>>>"
+        );
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let a = gen_arity(&mut rng, 4);
+            let s = a.to_string();
+            assert_eq!(s.parse::<Arity>(), Ok(a), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_migrate_abi() {
+        let mut iface = parse_interface("[name=x]{get(I32) -> (F64)}").unwrap().1;
+        let before = iface.rid();
+        assert_eq!(iface.wasm_abi(), 0);
+        iface.migrate_abi(1).unwrap();
+        assert_eq!(iface.wasm_abi(), 1);
+        assert_eq!(iface.to_string(), "[name=x][wasmAbiVer=0]{get(I32) -> (F64)}");
+        assert_ne!(iface.rid(), before);
+        iface.migrate_abi(1).unwrap();
+        assert_eq!(iface.to_string(), "[name=x][wasmAbiVer=0]{get(I32) -> (F64)}");
+
+        let snapshot = iface.clone();
+        assert_eq!(
+            iface.migrate_abi(0),
+            Err(MigrationError::Downgrade { from: 1, to: 0 })
+        );
+        assert_eq!(iface.migrate_abi(3), Err(MigrationError::Unsupported { to: 2 }));
+        assert_eq!(iface, snapshot);
+    }
+
+    #[test]
+    fn test_named_resty() {
+        let (rest, r) = parse_resty("@{fs.File}&").unwrap();
+        assert_eq!(rest, "&");
+        assert_eq!(r, ResTy::Named("fs.File".into()));
+        assert_eq!(r.to_string(), "@{fs.File}");
+        assert!(parse_resty("@fs.File").is_err());
+
+        let iface: Interface = "{open(R@{fs.File}&) -> (R@{fs.File})}".parse().unwrap();
+        assert_eq!(iface.to_string(), "{open(R@{fs.File}&) -> (R@{fs.File})}");
+        assert_eq!(iface.rid(), iface.clone().rid());
+
+        let rid = [7u8; 32];
+        let names = BTreeMap::from([("fs.File".to_string(), rid)]);
+        assert_eq!(r.resolve(&names), Some(ResTy::Of(rid)));
+        assert_eq!(ResTy::This.resolve(&names), Some(ResTy::This));
+        assert_eq!(ResTy::Named("net.Socket".into()).resolve(&names), None);
+    }
+
+    #[test]
+    fn test_named_resty_flags() {
+        for (nullable, take) in [(true, true), (false, false), (true, false)] {
+            let arg = Arg::resource(ResTy::Named("namen".into()), nullable, take);
+            let text = arg.to_string();
+            assert_eq!(parse_arg(&text).unwrap(), ("", arg), "{text}");
+        }
+        assert_eq!(
+            Arg::resource(ResTy::Named("x".into()), true, false).to_string(),
+            "R@{x}n&"
+        );
+    }
+
+    #[test]
+    fn test_validate_version_values_and_names() {
+        let (_, mut iface) =
+            parse_interface("[ridFmtVer=1][ridFmtVer=zz][wasmAbiVer=0]{f(R@{fs.File}) -> ()}")
+                .unwrap();
+        iface.methods.get_mut("f").unwrap().rets.push(Arg::resource(
+            ResTy::Named("not ident".into()),
+            false,
+            true,
+        ));
+        let problems = iface.validate().unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::InvalidResourceName(
+                    Location {
+                        method: "f".to_owned(),
+                        path: vec![(ArgSide::Return, 0)],
+                    },
+                    "not ident".to_owned(),
+                ),
+                Problem::ConflictingVersionAttr {
+                    name: "ridFmtVer".to_owned(),
+                    values: vec!["1".to_owned(), "zz".to_owned()],
+                },
+                Problem::InvalidVersionValue {
+                    name: "ridFmtVer".to_owned(),
+                    value: "zz".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            problems[2].to_string(),
+            "`ridFmtVer` value `zz` is not a hex number"
+        );
+    }
+
+    #[test]
+    fn test_with_annotation() {
+        let base: Interface = "{f(I32) -> ()}".parse().unwrap();
+        let annotated = base
+            .clone()
+            .with_annotation(parse_attr("[wasmAbiVer=0]").unwrap().1)
+            .with_annotation(parse_attr("[doc=hi]").unwrap().1);
+        assert_eq!(annotated.to_string(), "[doc=hi][wasmAbiVer=0]{f(I32) -> ()}");
+        assert_ne!(annotated.rid(), base.rid());
+        let stripped = annotated.without_annotation("doc").without_annotation("wasmAbiVer");
+        assert_eq!(stripped, base);
+        assert_eq!(stripped.rid(), base.rid());
+    }
+
+    #[test]
+    fn test_interface_diff() {
+        let old: Interface = "[a=1][b=2]{close(Rthis) -> ();read(I32, I64) -> (I32);size() -> (I64)}"
+            .parse()
+            .unwrap();
+        let mut new: Interface = "[a=1][b=2]{read(I32) -> (I32);size() -> (F64);write(I32) -> ()}"
+            .parse()
+            .unwrap();
+        new.ann.reverse();
+        assert!(old.diff(&old).is_empty());
+
+        let d = old.diff(&new);
+        assert!(!d.ann_changed);
+        assert_eq!(d.added, vec!["write".to_owned()]);
+        assert_eq!(d.removed, vec!["close".to_owned()]);
+        assert_eq!(d.changed.len(), 2);
+        assert_eq!(
+            d.changed["read"],
+            MethodDiff {
+                params: vec![ArgDiff::Removed {
+                    index: 1,
+                    arg: Arg::i64(),
+                }],
+                rets: vec![],
+                ann_changed: false,
+            }
+        );
+        assert_eq!(
+            d.changed["size"].rets,
+            vec![ArgDiff::Changed {
+                index: 0,
+                old: Arg::i64(),
+                new: Arg::f64(),
+                ty_changed: true,
+                ann_changed: false,
+            }]
+        );
+        assert!(d.changed["size"].params.is_empty());
+    }
+
+    #[test]
+    fn test_apply_resource_defaults() {
+        let src = "{f(Rthis, [take=]Rthis, Rthis&, ^(Rthis) -> ()) -> (Rthis)}";
+        let mut plain: Interface = src.parse().unwrap();
+        let before = plain.clone();
+        plain.apply_resource_defaults();
+        assert_eq!(plain, before);
+
+        let mut iface: Interface = format!("[resourceDefault=borrow]{src}").parse().unwrap();
+        iface.apply_resource_defaults();
+        let sig = &iface.methods["f"];
+        let takes: Vec<bool> = sig.params[..3]
+            .iter()
+            .chain(&sig.rets)
+            .map(|a| matches!(a.ty, ArgTy::Resource { take: true, .. }))
+            .collect();
+        assert_eq!(takes, [false, true, false, false]);
+        let ArgTy::Func(inner) = &sig.params[3].ty else {
+            panic!()
+        };
+        assert!(matches!(inner.params[0].ty, ArgTy::Resource { take: false, .. }));
+
+        let again = iface.clone();
+        iface.apply_resource_defaults();
+        assert_eq!(iface, again);
+    }
+
+    #[test]
+    fn test_is_compatible_with() {
+        let old: Interface = "{get(Rthisn, I32) -> (Rthis);put(Rthis&) -> ()}".parse().unwrap();
+        let superset: Interface =
+            "[v=2]{get(Rthisn, [doc=x]I32) -> (Rthis);put(Rthisn&) -> ();size() -> (I64)}"
+                .parse()
+                .unwrap();
+        assert_eq!(superset.is_compatible_with(&old), Ok(()));
+        assert_eq!(old.is_compatible_with(&old), Ok(()));
+
+        let broken: Interface = "{get(Rthis, I64) -> (Rthisn)}".parse().unwrap();
+        assert_eq!(
+            broken.is_compatible_with(&old).unwrap_err(),
+            vec![
+                Incompatibility::NullabilityNarrowed {
+                    method: "get".to_owned(),
+                    side: ArgSide::Param,
+                    index: 0,
+                },
+                Incompatibility::TypeChanged {
+                    method: "get".to_owned(),
+                    side: ArgSide::Param,
+                    index: 1,
+                    old: ArgTy::I32,
+                    new: ArgTy::I64,
+                },
+                Incompatibility::NullabilityNarrowed {
+                    method: "get".to_owned(),
+                    side: ArgSide::Return,
+                    index: 0,
+                },
+                Incompatibility::MissingMethod("put".to_owned()),
+            ]
+        );
+
+        let arity: Interface = "{get(Rthisn) -> (Rthis);put(Rthis) -> ()}".parse().unwrap();
+        assert_eq!(
+            arity.is_compatible_with(&old).unwrap_err(),
+            vec![
+                Incompatibility::CountChanged {
+                    method: "get".to_owned(),
+                    side: ArgSide::Param,
+                    old: 2,
+                    new: 1,
+                },
+                Incompatibility::TypeChanged {
+                    method: "put".to_owned(),
+                    side: ArgSide::Param,
+                    index: 0,
+                    old: ArgTy::Resource {
+                        ty: ResTy::This,
+                        nullable: false,
+                        take: false,
+                    },
+                    new: ArgTy::Resource {
+                        ty: ResTy::This,
+                        nullable: false,
+                        take: true,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interface_merge() {
+        let a: Interface = "[doc=a][wasmAbiVer=0]{get() -> (I32)}".parse().unwrap();
+        let b: Interface = "[doc=b]{get() -> (I32);put(I32) -> ()}".parse().unwrap();
+        let merged = a.clone().merge(b).unwrap();
+        assert_eq!(
+            merged.to_string(),
+            "[doc=b][wasmAbiVer=0]{get() -> (I32);put(I32) -> ()}"
+        );
+
+        let c: Interface = "{get() -> (I64)}".parse().unwrap();
+        let err = a.merge(c).unwrap_err();
+        assert_eq!(err.method, "get");
+        assert_eq!(err.ours.to_string(), "() -> (I32)");
+        assert_eq!(err.theirs.to_string(), "() -> (I64)");
+        assert_eq!(
+            err.to_string(),
+            "method `get` is declared as `() -> (I32)` and `() -> (I64)`"
+        );
+    }
+
+    #[test]
+    fn test_structurally_eq() {
+        let bare: Interface = "{f(I32, ^(Rthis&) -> ()) -> (I64)}".parse().unwrap();
+        let annotated: Interface =
+            "[name=F][doc=x]{f[pure]([unit=s]I32, ^[doc=cb]([doc=r]Rthis&) -> ()) -> ([doc=y]I64)}"
+                .parse()
+                .unwrap();
+        assert_ne!(bare, annotated);
+        assert_ne!(bare.rid(), annotated.rid());
+        assert!(bare.structurally_eq(&annotated));
+        assert_eq!(annotated.strip_annotations(), bare);
+        assert_eq!(annotated.strip_annotations().rid(), bare.rid());
+
+        let other: Interface = "{f(I32, ^(Rthis) -> ()) -> (I64)}".parse().unwrap();
+        assert!(!bare.structurally_eq(&other));
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let (rest, doc) = parse_document("\n[doc=x]{f(I32) -> ()}").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            doc,
+            Document::Interface("[doc=x]{f(I32) -> ()}".parse().unwrap())
+        );
+
+        let id = "ab".repeat(32);
+        let src = format!("{id}: [\n root [doc=x]\n method f [doc=y]\n]");
+        let (rest, doc) = parse_document(&src).unwrap();
+        assert_eq!(rest, "");
+        let Document::Info(info) = doc else {
+            panic!("expected info, got {doc:?}");
+        };
+        let entry = &info.interfaces[&[0xab; 32]];
+        assert_eq!(entry.methods["f"].attrs[0].value, "y");
+
+        assert!(matches!(
+            parse_document("abc: [\n]"),
+            Err(nom::Err::Failure(PitError::BadHexLength { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_interface_builder() {
+        let built = InterfaceBuilder::new()
+            .attr(parse_attr("[wasmAbiVer=0]").unwrap().1)
+            .attr(parse_attr("[doc=files]").unwrap().1)
+            .method(
+                "read",
+                SigBuilder::new()
+                    .resource_param(ResTy::This, false, false)
+                    .i64_param()
+                    .ret(Arg::i32().with_attr(parse_attr("[unit=bytes]").unwrap().1)),
+            )
+            .method(
+                "close",
+                SigBuilder::new()
+                    .ann(parse_attr("[idempotent=true]").unwrap().1)
+                    .resource_param(ResTy::This, true, true),
+            )
+            .build();
+        let parsed: Interface = "[doc=files][wasmAbiVer=0]{close[idempotent=true](Rthisn) -> ();read(Rthis&, I64) -> ([unit=bytes]I32)}"
+            .parse()
+            .unwrap();
+        assert_eq!(built, parsed);
+        assert_eq!(built.rid(), parsed.rid());
+    }
+
+    #[test]
+    fn test_check_resource_consistency() {
+        let rid = "12".repeat(32);
+        let mixed: Sig = format!("(R{rid}&, I32, R{rid}) -> (Rthis&)").parse().unwrap();
+        assert_eq!(
+            mixed.check_resource_consistency(),
+            vec![ResourceConflict {
+                ty: ResTy::Of([0x12; 32]),
+                owned: vec![(ArgSide::Param, 2)],
+                borrowed: vec![(ArgSide::Param, 0)],
+            }]
+        );
+        let consistent: Sig = format!("(R{rid}&, R{rid}n&, R, R&) -> (Rthis)").parse().unwrap();
+        assert!(consistent.check_resource_consistency().is_empty());
+    }
+
+    #[test]
+    fn test_all_attrs() {
+        let iface: Interface = "[doc=i][v=1]{a[pure]([unit=s]I32) -> ();b(^[doc=cb]([doc=x]I64) -> ()) -> ([doc=r]Rthis)}"
+            .parse()
+            .unwrap();
+        let all: Vec<_> = iface.all_attrs().collect();
+        assert_eq!(all.len(), 7);
+        let loc = |method: &str, path: &[(ArgSide, usize)]| Location {
+            method: method.to_owned(),
+            path: path.to_vec(),
+        };
+        let found: Vec<(AttrLocation, &str)> =
+            all.iter().map(|(l, a)| (l.clone(), a.name.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (AttrLocation::Interface, "doc"),
+                (AttrLocation::Interface, "v"),
+                (AttrLocation::Sig(loc("a", &[])), "pure"),
+                (AttrLocation::Arg(loc("a", &[(ArgSide::Param, 0)])), "unit"),
+                (AttrLocation::Sig(loc("b", &[(ArgSide::Param, 0)])), "doc"),
+                (
+                    AttrLocation::Arg(loc("b", &[(ArgSide::Param, 0), (ArgSide::Param, 0)])),
+                    "doc"
+                ),
+                (AttrLocation::Arg(loc("b", &[(ArgSide::Return, 0)])), "doc"),
+            ]
+        );
+
+        let entry = info::InfoEntry::parse_typed(
+            "root [name=I]\nmethod b [brief=x]\nreturn b 0 [unit=s]\nparam a 1 [doc=p]\n",
+        )
+        .unwrap()
+        .1;
+        let with: Vec<(AttrLocation, &str)> = iface
+            .all_attrs_with(&entry)
+            .map(|(l, a)| (l, a.name.as_str()))
+            .collect();
+        assert_eq!(with[..found.len()], found[..]);
+        assert_eq!(
+            with[found.len()..],
+            [
+                (AttrLocation::Info, "name"),
+                (AttrLocation::InfoArg(loc("a", &[(ArgSide::Param, 1)])), "doc"),
+                (AttrLocation::InfoMethod("b".to_owned()), "brief"),
+                (AttrLocation::InfoArg(loc("b", &[(ArgSide::Return, 0)])), "unit"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iter_params() {
+        let iface: Interface = "{put(Rthis&, I32, F64) -> ();get(I32) -> (I64, F32)}"
+            .parse()
+            .unwrap();
+        assert_eq!(iface.iter_params().count(), 4);
+        let params: Vec<(&str, usize, &Arg)> = iface.iter_params().collect();
+        assert_eq!(
+            params,
+            vec![
+                ("get", 0, &Arg::i32()),
+                ("put", 0, &Arg::resource(ResTy::This, false, false)),
+                ("put", 1, &Arg::i32()),
+                ("put", 2, &Arg::f64()),
+            ]
+        );
+        let rets: Vec<(&str, usize, &Arg)> = iface.iter_returns().collect();
+        assert_eq!(rets, vec![("get", 0, &Arg::i64()), ("get", 1, &Arg::f32())]);
+    }
+
+    #[test]
+    fn test_bare_resource_id() {
+        let rid = "fd".repeat(32);
+        let b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode([0xfd; 32]);
+        let explicit: Interface = format!("{{f(R{rid}, I32) -> (R{rid})}}").parse().unwrap();
+        let opts = ParseOptions {
+            bare_ids: true,
+            ..Default::default()
+        };
+        for src in [
+            format!("{{f({rid}, I32) -> ({rid})}}"),
+            format!("{{f([doc=x]{rid}, i) -> (~b64{b64}~)}}"),
+        ] {
+            // Only accepted when enabled
+            assert!(src.parse::<Interface>().is_err(), "{src}");
+            let short = Interface::parse_with(&src, &opts).unwrap();
+            assert!(short.structurally_eq(&explicit), "{src}");
+            let rendered = short.strip_annotations().to_string();
+            assert_eq!(rendered, explicit.to_string());
+            assert_eq!(short.strip_annotations().rid(), explicit.rid());
+        }
+        assert_eq!(
+            parse_arg_typed(&rid),
+            Err(nom::Err::Error(PitError::UnknownArgType { remaining: 64 }))
+        );
+        assert_eq!(parse_arg_with(&rid, &opts).unwrap().1, explicit.methods["f"].params[0]);
+        // A compact `f` or `d` is still a primitive.
+        let (_, prims) = parse_sig_with("(f, d) -> ()", &opts).unwrap();
+        assert_eq!(prims.params, vec![Arg::f32(), Arg::f64()]);
+    }
+
+    #[test]
+    fn test_common_method_prefix() {
+        let prefix = |s: &str| s.parse::<Interface>().unwrap().common_method_prefix();
+        assert_eq!(
+            prefix("{db.get() -> ();db.put() -> ()}"),
+            Some("db".to_owned())
+        );
+        assert_eq!(
+            prefix("{app.db.get() -> ();app.db.put() -> ();app.log() -> ()}"),
+            Some("app".to_owned())
+        );
+        assert_eq!(prefix("{get() -> ();put() -> ()}"), None);
+        assert_eq!(prefix("{db.get() -> ();fs.get() -> ()}"), None);
+        assert_eq!(prefix("{db() -> ();db.get() -> ()}"), None);
+        assert_eq!(prefix("{dbx.get() -> ();db.put() -> ()}"), None);
+        assert_eq!(prefix("{}"), None);
+    }
+
+    #[test]
+    fn test_pure_methods() {
+        let iface: Interface = "{a[pure=true]() -> ();b[pure=1]() -> ();c[pure]() -> ();d[pure=false]() -> ();e() -> ()}"
+            .parse()
+            .unwrap();
+        assert!(iface.methods["a"].is_pure());
+        assert!(!iface.methods["d"].is_pure());
+        assert!(!iface.methods["e"].is_pure());
+        assert_eq!(
+            iface.pure_methods(),
+            BTreeSet::from(["a".to_owned(), "b".to_owned(), "c".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_primitive_annotations_kept() {
+        // Primitive arguments carry annotations like any other, so nothing is dropped.
+        for src in ["[doc=x]I32", "[doc=x][unit=ms]l", "[doc=x]V128"] {
+            let (rest, arg) = parse_arg(src).unwrap();
+            assert_eq!(rest, "");
+            assert!(arg.ty.is_primitive() || arg.ty == ArgTy::V128);
+            assert_eq!(arg.ann[0], parse_attr("[doc=x]").unwrap().1);
+        }
+        let iface: Interface = "{f([doc=x]I32) -> ([unit=ms]I64)}".parse().unwrap();
+        assert_eq!(iface.to_string(), "{f([doc=x]I32) -> ([unit=ms]I64)}");
+    }
+
+    #[test]
+    fn test_typed_attr_values() {
+        let attr = |s: &str| parse_attr(s).unwrap().1;
+        assert_eq!(attr("[pure=true]").as_bool(), Some(true));
+        assert_eq!(attr("[pure=false]").as_bool(), Some(false));
+        assert_eq!(attr("[pure=yes]").as_bool(), None);
+        assert_eq!(attr("[align=16]").as_u64(), Some(16));
+        assert_eq!(attr("[align=-1]").as_u64(), None);
+        assert_eq!(attr("[align=0x10]").as_u64(), None);
+        assert_eq!(attr("[offset=-12]").as_i64(), Some(-12));
+        assert_eq!(attr("[offset=9223372036854775808]").as_i64(), None);
+        assert_eq!(attr("[offset=]").as_i64(), None);
+
+        assert_eq!(Attr::from_bool("pure", true), attr("[pure=true]"));
+        assert_eq!(Attr::from_u64("align", 16), attr("[align=16]"));
+        assert_eq!(Attr::from_i64("offset", -12).to_string(), "[offset=-12]");
+        assert_eq!(Attr::from_u64("max", u64::MAX).as_u64(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_arity_diff() {
+        let arity = |s: &str| s.parse::<Arity>().unwrap();
+        let old = arity("<K <> V <T <>>>");
+        assert!(old.diff(&old).is_empty());
+
+        let added = old.diff(&arity("<E <> K <> V <T <>>>"));
+        assert_eq!(
+            added,
+            vec![ArityOp::Added {
+                slot: "E".to_owned(),
+                arity: Arity::default(),
+            }]
+        );
+
+        let removed = old.diff(&arity("<V <T <>>>"));
+        assert_eq!(
+            removed,
+            vec![ArityOp::Removed {
+                slot: "K".to_owned(),
+                arity: Arity::default(),
+            }]
+        );
+
+        let nested = old.diff(&arity("<K <> V <U <>>>"));
+        assert_eq!(
+            nested,
+            vec![ArityOp::Nested {
+                slot: "V".to_owned(),
+                ops: vec![
+                    ArityOp::Removed {
+                        slot: "T".to_owned(),
+                        arity: Arity::default(),
+                    },
+                    ArityOp::Added {
+                        slot: "U".to_owned(),
+                        arity: Arity::default(),
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_attr_new() {
+        let ok = Attr::new("llm.context", "a [b]").unwrap();
+        assert!(ok.is_valid());
+        assert_eq!(ok.to_string().parse::<Attr>().unwrap(), ok);
+        for bad in ["a=b", "a]", "has space", "", "x[y"] {
+            assert_eq!(
+                Attr::new(bad, "v"),
+                Err(AttrError::InvalidName(bad.to_owned())),
+                "{bad:?}"
+            );
+        }
+        assert_eq!(
+            AttrError::InvalidName("a=b".into()).to_string(),
+            "invalid attribute name `a=b`"
+        );
+        // Field literals skip the check.
+        let literal = Attr {
+            name: "a=b".into(),
+            value: String::new(),
+        };
+        assert!(!literal.is_valid());
+    }
+
+    #[test]
+    fn test_attr_namespaces() {
+        let (_, attrs) = parse_attrs(
+            "[llm.context=a][wasmAbiVer=0][llm.intent=b][llmx=c][doc.fr=d][llm.hint.short=e]",
+        )
+        .unwrap();
+        let ns = |n: &str| {
+            attrs_in_namespace(&attrs, n)
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ns("llm"), ["llm.context", "llm.hint.short", "llm.intent"]);
+        assert_eq!(ns("doc"), ["doc.fr"]);
+        assert_eq!(ns("llm.hint"), ["llm.hint.short"]);
+        assert!(ns("wasmAbiVer").is_empty());
+
+        let spaces: Vec<_> = attrs.iter().map(Attr::namespace).collect();
+        assert_eq!(
+            spaces,
+            [Some("doc"), Some("llm"), Some("llm"), Some("llm"), None, None]
+        );
+    }
+
+    #[test]
+    fn test_merge_with_policies() {
+        let attr = |name: &str, value: &str| Attr {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        };
+        let a = vec![attr("z", "1"), attr("doc", "first")];
+        let b = vec![attr("doc", "second"), attr("a", "2")];
+        assert_eq!(
+            merge_with(a.clone(), b.clone(), MergePolicy::LastWins),
+            vec![attr("a", "2"), attr("doc", "second"), attr("z", "1")]
+        );
+        assert_eq!(
+            merge(a.clone(), b.clone()),
+            merge_with(a.clone(), b.clone(), MergePolicy::LastWins)
+        );
+        assert_eq!(
+            merge_with(a.clone(), b.clone(), MergePolicy::FirstWins),
+            vec![attr("a", "2"), attr("doc", "first"), attr("z", "1")]
+        );
+        assert_eq!(
+            merge_with(a, b, MergePolicy::KeepAll),
+            vec![attr("z", "1"), attr("doc", "first"), attr("doc", "second"), attr("a", "2")]
+        );
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        let (iface, rest) = Interface::parse_prefix("{get(I32) -> (F64)}rest").unwrap();
+        assert_eq!(iface, "{get(I32) -> (F64)}".parse().unwrap());
+        assert_eq!(rest, "rest");
+        assert!("{get(I32) -> (F64)}rest".parse::<Interface>().is_err());
+
+        let (_, rest) = Interface::parse_prefix("{} {}").unwrap();
+        assert_eq!(rest, " {}");
+        assert!(Interface::parse_prefix("get(I32)").is_err());
+    }
+
+    #[test]
+    fn test_parse_streaming() {
+        let src = "[doc=a } inside \\] brackets][escFmtVer=0]{add(I32,[x=}]I64) -> (F64)}rest";
+        let end = src.len() - "rest".len();
+        for i in 0..end {
+            assert!(
+                matches!(Interface::parse_streaming(&src[..i]), Err(nom::Err::Incomplete(_))),
+                "prefix {:?} should be incomplete",
+                &src[..i]
+            );
+        }
+        let (rest, iface) = Interface::parse_streaming(src).unwrap();
+        assert_eq!(rest, "rest");
+        assert_eq!(iface, parse_interface(src).unwrap().1);
+
+        assert!(matches!(Interface::parse_streaming("x{"), Err(nom::Err::Error(_))));
+        assert!(matches!(
+            Interface::parse_streaming("{add(X) -> ()}"),
+            Err(nom::Err::Error(_))
+        ));
+
+        // Without `escFmtVer` a backslash does not hide the `]` after it
+        let src = "[doc=a\\]{f() -> ()}";
+        let (_, iface) = Interface::parse_streaming(src).unwrap();
+        assert_eq!(iface.ann[0].value, "a\\");
+        assert!(matches!(
+            Interface::parse_streaming(&src[..src.len() - 1]),
+            Err(nom::Err::Incomplete(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_streaming_sig_arg() {
+        let src = "[doc=a ) [b] \\](I32,^[x=(](R@{y}n&) -> ()) -> (F64),";
+        let end = src.len() - 1;
+        for i in 0..end {
+            assert!(
+                matches!(Sig::parse_streaming(&src[..i]), Err(nom::Err::Incomplete(_))),
+                "prefix {:?} should be incomplete",
+                &src[..i]
+            );
+        }
+        let (rest, sig) = Sig::parse_streaming(src).unwrap();
+        assert_eq!(rest, ",");
+        assert_eq!(sig, parse_sig(src).unwrap().1);
+        assert!(matches!(Sig::parse_streaming("x("), Err(nom::Err::Error(_))));
+
+        // A word is only complete once something that cannot extend it follows
+        for src in ["[a=b] I32,", "R@{y}n&)", "^(I32) -> ()"] {
+            for i in 0..src.len() {
+                assert!(
+                    matches!(Arg::parse_streaming(&src[..i]), Err(nom::Err::Incomplete(_))),
+                    "prefix {:?} should be incomplete",
+                    &src[..i]
+                );
+            }
+            assert_eq!(Arg::parse_streaming(src), parse_arg_typed(src), "{src}");
+        }
+        assert!(matches!(Arg::parse_streaming("I33,"), Err(nom::Err::Error(_))));
+        assert!(matches!(Arg::parse_streaming(","), Err(nom::Err::Error(_))));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let attr = |name: &str, value: &str| Attr {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        };
+        let (_, parsed) =
+            parse_interface("[b=1][a=2]{f[y=1][x=2]([q=1][p=2]R&,^(I32) -> ([d=1][c=2]I64)) -> ()}")
+                .unwrap();
+        let mut shuffled = parsed.clone();
+        shuffled.ann.reverse();
+        let f = shuffled.methods.get_mut("f").unwrap();
+        f.ann.reverse();
+        f.params[0].ann.reverse();
+        let ArgTy::Func(inner) = &mut f.params[1].ty else {
+            unreachable!()
+        };
+        inner.rets[0].ann.reverse();
+        assert_ne!(shuffled, parsed);
+        assert_eq!(shuffled.canonicalize(), parsed.canonicalize());
+        assert_eq!(shuffled.rid(), parsed.rid());
+        assert_eq!(parsed.canonicalize(), parsed);
+
+        // Duplicate names keep their order
+        let dup = Interface {
+            ann: vec![attr("doc", "b"), attr("x", "1"), attr("doc", "a")],
+            ..Default::default()
+        };
+        assert_eq!(
+            dup.canonicalize().ann,
+            vec![attr("doc", "b"), attr("doc", "a"), attr("x", "1")]
+        );
+        assert_eq!(dup.rid(), dup.canonicalize().rid());
+        let mut reversed = dup.clone();
+        reversed.ann.reverse();
+        assert_ne!(dup.rid(), reversed.rid());
+    }
+
+    #[test]
+    fn test_parse_arg_unknown_type() {
+        // Words that only start like a primitive, the empty input, and a `(` (function
+        // arguments start with `^`) all reach the unknown-type arm.
+        for (src, at) in [
+            ("X32", 0),
+            ("I33", 0),
+            ("i32", 0),
+            ("U32)", 0),
+            ("", 0),
+            ("(I32) -> ()", 0),
+            ("[doc=x] Q", 8),
+        ] {
+            let Err(nom::Err::Error(e)) = parse_arg_typed(src) else {
+                panic!("{src:?} parsed");
+            };
+            assert_eq!(e, PitError::UnknownArgType { remaining: src.len() - at }, "{src:?}");
+        }
+        assert!(matches!(
+            parse_arg("^(I32) -> ()").unwrap().1.ty,
+            ArgTy::Func(_)
+        ));
+    }
+
+    #[test]
+    fn test_sub_word_integers() {
+        let src = "{pack(I8,I16,U8,U16) -> (I32);wide(I64) -> (U16)}";
+        let iface: Interface = src.parse().unwrap();
+        assert_eq!(iface.to_string(), src);
+        let pack = &iface.methods["pack"];
+        let tys: Vec<_> = pack.params.iter().map(|a| a.ty.clone()).collect();
+        assert_eq!(tys, [ArgTy::I8, ArgTy::I16, ArgTy::U8, ArgTy::U16]);
+        assert_eq!(pack.rets[0].ty, ArgTy::I32);
+        assert!(tys.iter().all(|t| t.is_primitive() && t.kind() == ArgKind::I32));
+
+        // Compact rendering keeps the verbose spelling for sub-word types
+        let compact: Interface = "[primFmtVer=0]{f(I8,I32) -> ()}".parse().unwrap();
+        assert_eq!(compact.to_string(), "[primFmtVer=0]{f(I8,i) -> ()}");
+        assert_eq!(compact.to_string().parse::<Interface>().unwrap(), compact);
+
+        // Primitives must be whole words
+        assert!("I3".parse::<Arg>().is_err());
+        assert!("I320".parse::<Arg>().is_err());
+        assert!("U32".parse::<Arg>().is_err());
+        assert_eq!("I32".parse::<Arg>().unwrap().ty, ArgTy::I32);
+    }
+
+    #[test]
+    fn test_arg_ownership_helpers() {
+        let owned: Arg = "[doc=x]Rthis".parse().unwrap();
+        assert!(owned.is_owned() && !owned.is_borrowed());
+
+        let borrowed = owned.clone().with_take(false);
+        assert!(borrowed.is_borrowed() && !borrowed.is_owned());
+        assert_eq!(borrowed.to_string(), "[doc=x]Rthis&");
+        assert_eq!(borrowed.clone().with_nullable(true).to_string(), "[doc=x]Rthisn&");
+        assert_eq!(
+            borrowed.with_nullable(true).with_take(true).with_nullable(false),
+            owned
+        );
+
+        let prim = Arg::i32();
+        assert!(!prim.is_owned() && !prim.is_borrowed());
+        assert_eq!(prim.clone().with_take(false).with_nullable(true), prim);
+    }
+
+    #[test]
+    fn test_referenced_rids() {
+        let a = "11".repeat(32);
+        let b = "22".repeat(32);
+        let iface: Interface = format!(
+            "{{get(R{a}&, Rthis) -> (R{b}n);each(^(R{a}) -> ()) -> (R, I32)}}"
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            iface.referenced_rids().into_iter().collect::<Vec<_>>(),
+            [[0x11; 32], [0x22; 32]]
+        );
+        assert!(Interface::default().referenced_rids().is_empty());
+    }
+
+    #[test]
+    fn test_summary() {
+        let iface: Interface = "{close(Rthis) -> ();read(Rthis&, I32) -> (I64, Rn);\
+            each(Rthis&, ^(I32) -> ()) -> ();len(Rthis&) -> (U16)}"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            iface.summary(),
+            InterfaceSummary {
+                methods: 4,
+                params: 6,
+                returns: 3,
+                resources: 5,
+                primitives: 3,
+                funcs: 1,
+                is_resource_interface: true,
+            }
+        );
+        let plain: Interface = "{f(^(Rthis) -> ()) -> ()}".parse().unwrap();
+        assert!(!plain.summary().is_resource_interface);
+        assert_eq!(Interface::default().summary(), InterfaceSummary::default());
+    }
+
+    #[test]
+    fn test_validate_strict_this() {
+        let resource: Interface = "{close(Rthis) -> ();each(^(Rthis&) -> ()) -> ()}".parse().unwrap();
+        assert!(resource.uses_this() && resource.is_resource_interface());
+        assert_eq!(resource.validate_strict(), Ok(()));
+
+        let stray: Interface = "{each(I32, ^(Rthis&) -> ()) -> ()}".parse().unwrap();
+        assert!(stray.uses_this() && !stray.is_resource_interface());
+        assert_eq!(stray.validate(), Ok(()));
+        let loc = Location {
+            method: "each".to_owned(),
+            path: vec![(ArgSide::Param, 1), (ArgSide::Param, 0)],
+        };
+        let problems = stray.validate_strict().unwrap_err();
+        assert_eq!(problems, [Problem::ThisOutsideResource(loc)]);
+        assert_eq!(
+            problems[0].to_string(),
+            "`this` at each.param[1].param[0], but no method takes or returns `this`"
+        );
+
+        let plain: Interface = "{get(I32) -> (I64)}".parse().unwrap();
+        assert!(!plain.uses_this());
+        assert_eq!(plain.validate_strict(), Ok(()));
+    }
+
+    #[test]
+    fn test_render_into_fixed_buffer() {
+        struct Fixed<const N: usize> {
+            buf: [u8; N],
+            len: usize,
+        }
+        impl<const N: usize> fmt::Write for Fixed<N> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                let end = self.len + s.len();
+                if end > N {
+                    return Err(fmt::Error);
+                }
+                self.buf[self.len..end].copy_from_slice(s.as_bytes());
+                self.len = end;
+                Ok(())
+            }
+        }
+
+        let iface: Interface = format!(
+            "[escFmtVer=0][ridFmtVer=0]{{get([doc=a \\[b [c]]R{}&) -> (I32)}}",
+            "ab".repeat(32)
+        )
+        .parse()
+        .unwrap();
+        let gattrs = |k: &str| iface.ann.iter().find_map(|a| a.as_ver(k));
+        let mut w = Fixed { buf: [0; 256], len: 0 };
+        iface.render_into(&mut w, &gattrs).unwrap();
+        let rendered = core::str::from_utf8(&w.buf[..w.len]).unwrap();
+        assert_eq!(rendered, iface.to_string());
+        assert!(rendered.contains("~b64"));
+
+        let mut verbose = Fixed { buf: [0; 256], len: 0 };
+        iface.render_into(&mut verbose, &|_| None).unwrap();
+        assert!(core::str::from_utf8(&verbose.buf[..verbose.len])
+            .unwrap()
+            .contains(&"ab".repeat(32)));
+
+        let mut small = Fixed { buf: [0; 8], len: 0 };
+        assert!(iface.render_into(&mut small, &gattrs).is_err());
+    }
+
+    #[test]
+    fn test_render_with() {
+        let iface: Interface = format!("[primFmtVer=0]{{get(R{}) -> (I32)}}", "ab".repeat(32))
+            .parse()
+            .unwrap();
+        let render = |ctx: &RenderCtx| {
+            let mut s = String::new();
+            iface.render_with(&mut s, ctx).unwrap();
+            s
+        };
+        let ctx = RenderCtx::from_interface(&iface);
+        assert_eq!(ctx.get("primFmtVer"), Some(1));
+        assert_eq!(render(&ctx), iface.to_string());
+        assert!(render(&ctx).contains(&"ab".repeat(32)));
+
+        let b64 = render(&ctx.with("ridFmtVer", 1));
+        assert!(b64.contains("~b64"));
+        assert!(!b64.contains(&"ab".repeat(32)));
+        assert!(b64.contains("(i)"));
+        assert_eq!(render(&ctx.with("ridFmtVer", 0)), iface.to_string());
+        assert_eq!(ctx.with("noSuchVer", 1), ctx);
+        assert_eq!(ctx.prim_fmt_ver, Some(1));
+        assert_eq!(ctx.rid_fmt_ver, None);
+    }
+
+    #[test]
+    fn test_parse_and_rid() {
+        for src in [
+            "{get(I32) -> (F64)} tail",
+            "[b=1][a=2]{ z(Rthis&) -> ();\n a([doc=x]I64) -> (I32) }",
+            "[ridFmtVer=0][primFmtVer=0]{f(i,R@{x}) -> (d)}",
+        ] {
+            let (rest, (iface, rid)) = Interface::parse_and_rid(src).unwrap();
+            let (expected_rest, expected) = parse_interface(src).unwrap();
+            assert_eq!(rest, expected_rest);
+            assert_eq!(iface, expected);
+            assert_eq!(rid, expected.rid());
+        }
+        assert!(Interface::parse_and_rid("{get(X) -> ()}").is_err());
+    }
+
+    #[test]
+    fn test_legacy_parsers() {
+        use nom::error::{Error, ErrorKind};
+        // Same results as the typed parsers, with nom's default error at the same position
+        assert_eq!(parse_arg("I32,"), Ok((",", Arg::new(ArgTy::I32))));
+        assert_eq!(parse_arg("[a=b]X32"), Err(nom::Err::Error(Error::new("X32", ErrorKind::Alt))));
+        assert_eq!(
+            parse_sig("(I32) (F64)"),
+            Err(nom::Err::Error(Error::new("(F64)", ErrorKind::Tag)))
+        );
+        let src = "{f(I32) -> ()}";
+        assert_eq!(parse_interface(src).unwrap(), parse_interface_typed(src).unwrap());
+        assert_eq!(Arity::parse("<K<>>").unwrap(), Arity::parse_typed("<K<>>").unwrap());
+        let short = format!("{}: [\n]", "a".repeat(63));
+        assert_eq!(
+            info::Info::parse(&short).unwrap_err(),
+            nom::Err::Failure(Error::new(short.as_str(), ErrorKind::TakeWhileMN))
+        );
+    }
+}
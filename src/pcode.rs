@@ -1,4 +1,24 @@
-use alloc::{boxed::Box, string::String, vec::Vec};
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
+
+use nom::{
+    IResult, Parser,
+    bytes::complete::take_while_m_n,
+    character::complete::{char, digit1, multispace0},
+    combinator::{cut, opt, recognize},
+    error::{ErrorKind, ParseError},
+    multi::separated_list0,
+    sequence::{delimited, preceded},
+};
+
+use crate::{ArgKind, Interface, PitError, ident_typed, token};
 
 /// Expression tree for pcode operations.
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
@@ -18,9 +38,847 @@ pub enum PExpr {
     LitF32(u32),
     LitF64(u64),
 }
+impl PExpr {
+    /// Parses an expression.
+    ///
+    /// - `$n` is `Param(n)`.
+    /// - An identifier is `Var`.
+    /// - `123i32`, `-1i64`, `4.5f32`, `4.5f64` are literals; integers may be signed or
+    ///   unsigned and are stored as their two's complement bits, floats as their IEEE bits.
+    ///   `0x7fc00000_f32` and `0x7ff8000000000000_f64` give float bits directly.
+    /// - `<rid>.method(obj; args...) -> pat` is a call, with `<rid>` as 64 hex digits and
+    ///   the arguments separated by `,`.
+    ///
+    /// Returns a tuple of the remaining input and the parsed `PExpr`.
+    pub fn parse(a: &str) -> IResult<&str, PExpr, PitError> {
+        let (a, _) = multispace0(a)?;
+        // A rid may start with digits, so calls are tried before literals.
+        match parse_call(a) {
+            Err(nom::Err::Error(_)) => {}
+            r => return r,
+        }
+        if let Some(a) = a.strip_prefix('$') {
+            let (a, n) = digit1.map_opt(|d: &str| d.parse().ok()).parse(a)?;
+            return Ok((a, PExpr::Param(n)));
+        }
+        if a.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+            return parse_lit(a);
+        }
+        ident_typed.map(|v: &str| PExpr::Var(v.to_owned())).parse(a)
+    }
+}
+/// A runtime value produced by [`PExpr::eval`].
+///
+/// Numbers are stored as bits, like the literals of [`PExpr`].
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum Value {
+    I32(u32),
+    I64(u64),
+    F32(u32),
+    F64(u64),
+    /// An opaque handle to a resource, as issued by the host.
+    Resource(u32),
+}
+/// Error returned by [`PExpr::eval`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum EvalError {
+    /// `Param(index)` with only `len` parameters supplied.
+    ParamOutOfRange { index: usize, len: usize },
+    /// A `Var` not bound by any enclosing pattern.
+    UnboundVar(String),
+    /// A call result bound by a pattern with more than one parameter.
+    PatternArity(usize),
+}
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::ParamOutOfRange { index, len } => {
+                write!(f, "parameter ${index} out of range ({len} supplied)")
+            }
+            EvalError::UnboundVar(v) => write!(f, "unbound variable `{v}`"),
+            EvalError::PatternArity(n) => {
+                write!(f, "pattern binds {n} names but a call returns one value")
+            }
+        }
+    }
+}
+impl core::error::Error for EvalError {}
+/// Error returned by [`PExpr::typecheck`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum PcodeTypeError {
+    /// `Param(index)` with only `len` parameter kinds supplied.
+    ParamOutOfRange { index: usize, len: usize },
+    /// A `Var` not bound by any enclosing pattern.
+    UnboundVar(String),
+    /// A call to a rid that is not in the registry.
+    UnknownInterface([u8; 32]),
+    /// A call to a method the interface does not declare.
+    UnknownMethod { rid: [u8; 32], method: String },
+    /// A call whose object is not a resource.
+    ObjNotResource { method: String, found: ArgKind },
+    /// A call with the wrong number of arguments.
+    ArgCount {
+        method: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A call argument whose kind differs from the declared parameter.
+    ArgMismatch {
+        method: String,
+        index: usize,
+        expected: ArgKind,
+        found: ArgKind,
+    },
+    /// A pattern binding a name to a call that does not return exactly one value.
+    PatternArity { method: String, rets: usize },
+}
+impl Display for PcodeTypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PcodeTypeError::ParamOutOfRange { index, len } => {
+                write!(f, "parameter ${index} out of range ({len} declared)")
+            }
+            PcodeTypeError::UnboundVar(v) => write!(f, "unbound variable `{v}`"),
+            PcodeTypeError::UnknownInterface(rid) => {
+                write!(f, "unknown interface {}", hex::encode(rid))
+            }
+            PcodeTypeError::UnknownMethod { rid, method } => {
+                write!(f, "interface {} has no method `{method}`", hex::encode(rid))
+            }
+            PcodeTypeError::ObjNotResource { method, found } => {
+                write!(f, "`{method}` called on a non-resource ({found:?})")
+            }
+            PcodeTypeError::ArgCount {
+                method,
+                expected,
+                found,
+            } => write!(f, "`{method}` takes {expected} arguments, {found} given"),
+            PcodeTypeError::ArgMismatch {
+                method,
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "argument {index} of `{method}`: expected {expected:?}, found {found:?}"
+            ),
+            PcodeTypeError::PatternArity { method, rets } => {
+                write!(f, "pattern binds a name but `{method}` returns {rets} values")
+            }
+        }
+    }
+}
+impl core::error::Error for PcodeTypeError {}
+/// Returns whether a value of kind `found` may be passed where `expected` is declared.
+///
+/// Primitives must match exactly. Resources only need to be resources: the resource type,
+/// nullability and ownership are not tracked through pcode yet.
+fn kind_compatible(expected: &ArgKind, found: &ArgKind) -> bool {
+    match (expected, found) {
+        (ArgKind::ExternRef(_), ArgKind::ExternRef(_)) => true,
+        _ => expected == found,
+    }
+}
+impl PExpr {
+    /// Checks the expression against the interfaces in `registry`, returning its result kind.
+    ///
+    /// `params[n]` is the kind of `Param(n)`. Every call must name a registered interface
+    /// and one of its methods, on a resource object, with arguments matching the method's
+    /// parameters; a pattern binding a name requires the method to return one value.
+    pub fn typecheck(
+        &self,
+        registry: &BTreeMap<[u8; 32], Interface>,
+        params: &[ArgKind],
+    ) -> Result<ArgKind, PcodeTypeError> {
+        self.typecheck_in(registry, params, &BTreeMap::new())
+    }
+    fn typecheck_in(
+        &self,
+        registry: &BTreeMap<[u8; 32], Interface>,
+        params: &[ArgKind],
+        env: &BTreeMap<String, ArgKind>,
+    ) -> Result<ArgKind, PcodeTypeError> {
+        Ok(match self {
+            PExpr::Param(n) => params
+                .get(*n)
+                .ok_or(PcodeTypeError::ParamOutOfRange {
+                    index: *n,
+                    len: params.len(),
+                })?
+                .clone(),
+            PExpr::Var(v) => env
+                .get(v)
+                .ok_or_else(|| PcodeTypeError::UnboundVar(v.clone()))?
+                .clone(),
+            PExpr::LitI32(_) => ArgKind::I32,
+            PExpr::LitI64(_) => ArgKind::I64,
+            PExpr::LitF32(_) => ArgKind::F32,
+            PExpr::LitF64(_) => ArgKind::F64,
+            PExpr::Call {
+                rid,
+                method,
+                obj,
+                args,
+                ret,
+            } => {
+                let iface = registry
+                    .get(rid)
+                    .ok_or(PcodeTypeError::UnknownInterface(*rid))?;
+                let sig = iface
+                    .methods
+                    .get(method)
+                    .ok_or_else(|| PcodeTypeError::UnknownMethod {
+                        rid: *rid,
+                        method: method.clone(),
+                    })?
+                    .call_descriptor();
+                let found = obj.typecheck_in(registry, params, env)?;
+                if !matches!(found, ArgKind::ExternRef(_)) {
+                    return Err(PcodeTypeError::ObjNotResource {
+                        method: method.clone(),
+                        found,
+                    });
+                }
+                if args.len() != sig.params.len() {
+                    return Err(PcodeTypeError::ArgCount {
+                        method: method.clone(),
+                        expected: sig.params.len(),
+                        found: args.len(),
+                    });
+                }
+                for (index, (a, expected)) in args.iter().zip(&sig.params).enumerate() {
+                    let found = a.typecheck_in(registry, params, env)?;
+                    if !kind_compatible(expected, &found) {
+                        return Err(PcodeTypeError::ArgMismatch {
+                            method: method.clone(),
+                            index,
+                            expected: expected.clone(),
+                            found,
+                        });
+                    }
+                }
+                let mut env = env.clone();
+                match (ret.params.as_slice(), sig.rets.as_slice()) {
+                    ([], _) => {}
+                    ([name], [kind]) => {
+                        env.insert(name.clone(), kind.clone());
+                    }
+                    _ => {
+                        return Err(PcodeTypeError::PatternArity {
+                            method: method.clone(),
+                            rets: sig.rets.len(),
+                        });
+                    }
+                }
+                ret.body.typecheck_in(registry, params, &env)?
+            }
+        })
+    }
+}
+/// Callback resolving a call: `(rid, method, obj, args) -> result`.
+pub type Resolver<'a> = dyn FnMut(&[u8; 32], &str, Value, &[Value]) -> Value + 'a;
+impl PExpr {
+    /// Evaluates the expression.
+    ///
+    /// `Param(n)` reads `params[n]` and literals evaluate to themselves. A `Call` evaluates
+    /// its object and arguments in order, passes them to `resolver`, and evaluates the `ret`
+    /// pattern's body with the result bound to its single parameter (a pattern with no
+    /// parameters discards it).
+    pub fn eval(&self, params: &[Value], resolver: &mut Resolver<'_>) -> Result<Value, EvalError> {
+        self.eval_in(params, &BTreeMap::new(), resolver)
+    }
+    /// Returns the `Var` names referenced but not bound by an enclosing pattern.
+    pub fn free_vars(&self) -> BTreeSet<String> {
+        let mut out = BTreeSet::new();
+        self.collect_free(&mut Vec::new(), &mut out);
+        out
+    }
+    fn collect_free<'a>(&'a self, bound: &mut Vec<&'a str>, out: &mut BTreeSet<String>) {
+        match self {
+            PExpr::Var(v) if !bound.contains(&v.as_str()) => {
+                out.insert(v.clone());
+            }
+            PExpr::Call { obj, args, ret, .. } => {
+                obj.collect_free(bound, out);
+                for a in args {
+                    a.collect_free(bound, out);
+                }
+                ret.collect_free(bound, out);
+            }
+            _ => {}
+        }
+    }
+    /// Replaces each `Param(n)` with `bindings[&n]`, leaving unbound params intact.
+    ///
+    /// Substitution is capture-avoiding: a pattern parameter that would bind a free `Var`
+    /// of a substituted expression is renamed (to `name_1`, `name_2`, ...) first.
+    pub fn substitute(&self, bindings: &BTreeMap<usize, PExpr>) -> PExpr {
+        let mut fv = BTreeSet::new();
+        for e in bindings.values() {
+            fv.extend(e.free_vars());
+        }
+        self.subst(bindings, &fv)
+    }
+    fn subst(&self, bindings: &BTreeMap<usize, PExpr>, fv: &BTreeSet<String>) -> PExpr {
+        match self {
+            PExpr::Param(n) => bindings.get(n).cloned().unwrap_or(PExpr::Param(*n)),
+            PExpr::Call {
+                rid,
+                method,
+                obj,
+                args,
+                ret,
+            } => PExpr::Call {
+                rid: *rid,
+                method: method.clone(),
+                obj: Box::new(obj.subst(bindings, fv)),
+                args: args.iter().map(|a| a.subst(bindings, fv)).collect(),
+                ret: ret.subst(bindings, fv),
+            },
+            e => e.clone(),
+        }
+    }
+    /// Replaces free occurrences of `Var(from)` with `Var(to)`.
+    fn rename(&self, from: &str, to: &str) -> PExpr {
+        match self {
+            PExpr::Var(v) if v == from => PExpr::Var(to.to_owned()),
+            PExpr::Call {
+                rid,
+                method,
+                obj,
+                args,
+                ret,
+            } => PExpr::Call {
+                rid: *rid,
+                method: method.clone(),
+                obj: Box::new(obj.rename(from, to)),
+                args: args.iter().map(|a| a.rename(from, to)).collect(),
+                ret: if ret.params.iter().any(|p| p == from) {
+                    ret.clone()
+                } else {
+                    Pat {
+                        params: ret.params.clone(),
+                        body: Box::new(ret.body.rename(from, to)),
+                    }
+                },
+            },
+            e => e.clone(),
+        }
+    }
+    /// Collects every `Var` name and pattern parameter in the tree.
+    fn names(&self, out: &mut BTreeSet<String>) {
+        match self {
+            PExpr::Var(v) => {
+                out.insert(v.clone());
+            }
+            PExpr::Call { obj, args, ret, .. } => {
+                obj.names(out);
+                for a in args {
+                    a.names(out);
+                }
+                out.extend(ret.params.iter().cloned());
+                ret.body.names(out);
+            }
+            _ => {}
+        }
+    }
+    /// Returns the highest `Param` index referenced, or `None` if there are none.
+    pub fn max_param(&self) -> Option<usize> {
+        match self {
+            PExpr::Param(n) => Some(*n),
+            PExpr::Call { obj, args, ret, .. } => core::iter::once(&**obj)
+                .chain(args)
+                .chain(core::iter::once(&*ret.body))
+                .filter_map(PExpr::max_param)
+                .max(),
+            _ => None,
+        }
+    }
+    fn eval_in(
+        &self,
+        params: &[Value],
+        env: &BTreeMap<String, Value>,
+        resolver: &mut Resolver<'_>,
+    ) -> Result<Value, EvalError> {
+        Ok(match self {
+            PExpr::Param(n) => *params.get(*n).ok_or(EvalError::ParamOutOfRange {
+                index: *n,
+                len: params.len(),
+            })?,
+            PExpr::Var(v) => *env.get(v).ok_or_else(|| EvalError::UnboundVar(v.clone()))?,
+            PExpr::LitI32(v) => Value::I32(*v),
+            PExpr::LitI64(v) => Value::I64(*v),
+            PExpr::LitF32(v) => Value::F32(*v),
+            PExpr::LitF64(v) => Value::F64(*v),
+            PExpr::Call {
+                rid,
+                method,
+                obj,
+                args,
+                ret,
+            } => {
+                let obj = obj.eval_in(params, env, resolver)?;
+                let args = args
+                    .iter()
+                    .map(|a| a.eval_in(params, env, resolver))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let result = resolver(rid, method, obj, &args);
+                let mut env = env.clone();
+                match ret.params.as_slice() {
+                    [] => {}
+                    [name] => {
+                        env.insert(name.clone(), result);
+                    }
+                    names => return Err(EvalError::PatternArity(names.len())),
+                }
+                ret.body.eval_in(params, &env, resolver)?
+            }
+        })
+    }
+}
+/// Parses a literal: a decimal number followed by its type suffix, or raw float bits.
+fn parse_lit(a: &str) -> IResult<&str, PExpr, PitError> {
+    if let Some(bits) = a.strip_prefix("0x") {
+        let (a, hex_bits) = take_while_m_n(1, 16, |c: char| c.is_ascii_hexdigit())(bits)?;
+        let lit = if let Some(a) = a.strip_prefix("_f32") {
+            u32::from_str_radix(hex_bits, 16).ok().map(|v| (a, PExpr::LitF32(v)))
+        } else if let Some(a) = a.strip_prefix("_f64") {
+            u64::from_str_radix(hex_bits, 16).ok().map(|v| (a, PExpr::LitF64(v)))
+        } else {
+            return Err(nom::Err::Error(PitError::from_error_kind(a, ErrorKind::Tag)));
+        };
+        return lit.ok_or(nom::Err::Error(PitError::from_error_kind(bits, ErrorKind::MapRes)));
+    }
+    let (a, text) = recognize((opt(char('-')), digit1, opt((char('.'), digit1)))).parse(a)?;
+    let int = |min: i128, max: i128| {
+        text.parse::<i128>()
+            .ok()
+            .filter(|v| (min..=max).contains(v))
+    };
+    let (rest, lit) = if let Some(rest) = a.strip_prefix("i32") {
+        let v = int(i32::MIN.into(), u32::MAX.into());
+        (rest, v.map(|v| PExpr::LitI32(v as u32)))
+    } else if let Some(rest) = a.strip_prefix("i64") {
+        let v = int(i64::MIN.into(), u64::MAX.into());
+        (rest, v.map(|v| PExpr::LitI64(v as u64)))
+    } else if let Some(rest) = a.strip_prefix("f32") {
+        let v = text.parse::<f32>().ok();
+        (rest, v.map(|v| PExpr::LitF32(v.to_bits())))
+    } else if let Some(rest) = a.strip_prefix("f64") {
+        let v = text.parse::<f64>().ok();
+        (rest, v.map(|v| PExpr::LitF64(v.to_bits())))
+    } else {
+        return Err(nom::Err::Error(PitError::from_error_kind(a, ErrorKind::Tag)));
+    };
+    match lit {
+        Some(lit) => Ok((rest, lit)),
+        None => Err(nom::Err::Error(PitError::from_error_kind(a, ErrorKind::MapRes))),
+    }
+}
+/// Parses `<rid>.method(obj; args...) -> pat`.
+fn parse_call(a: &str) -> IResult<&str, PExpr, PitError> {
+    let (a, hex_rid) = take_while_m_n(64, 64, |c: char| c.is_ascii_hexdigit())(a)?;
+    let (a, _) = char('.')(a)?;
+    let mut rid = [0u8; 32];
+    hex::decode_to_slice(hex_rid, &mut rid)
+        .map_err(|_| nom::Err::Error(PitError::from_error_kind(a, ErrorKind::HexDigit)))?;
+    // Past `<rid>.` this can only be a call, so errors are not backtracked into `Var`.
+    let (a, (method, obj, args, ret)) = cut((
+        ident_typed,
+        preceded((multispace0, char('(')), PExpr::parse),
+        delimited(
+            multispace0,
+            opt(preceded(char(';'), separated_list0(char(','), PExpr::parse))),
+            (multispace0, char(')')),
+        ),
+        preceded((multispace0, token("->")), Pat::parse),
+    ))
+    .parse(a)?;
+    Ok((
+        a,
+        PExpr::Call {
+            rid,
+            method: method.to_owned(),
+            obj: Box::new(obj),
+            args: args.unwrap_or_default(),
+            ret,
+        },
+    ))
+}
+/// Display implementation for PExpr, in the syntax accepted by [`PExpr::parse`].
+///
+/// Integers render unsigned; finite floats render as the shortest decimal that reads back
+/// to the same bits, and NaNs and infinities as raw bits.
+impl Display for PExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PExpr::Param(n) => write!(f, "${n}"),
+            PExpr::Var(v) => write!(f, "{v}"),
+            PExpr::LitI32(v) => write!(f, "{v}i32"),
+            PExpr::LitI64(v) => write!(f, "{v}i64"),
+            PExpr::LitF32(v) => match f32::from_bits(*v) {
+                x if x.is_finite() => write!(f, "{x}f32"),
+                _ => write!(f, "0x{v:08x}_f32"),
+            },
+            PExpr::LitF64(v) => match f64::from_bits(*v) {
+                x if x.is_finite() => write!(f, "{x}f64"),
+                _ => write!(f, "0x{v:016x}_f64"),
+            },
+            PExpr::Call {
+                rid,
+                method,
+                obj,
+                args,
+                ret,
+            } => {
+                write!(f, "{}.{method}({obj}", hex::encode(rid))?;
+                for (i, a) in args.iter().enumerate() {
+                    write!(f, "{}{a}", if i == 0 { "; " } else { ", " })?;
+                }
+                write!(f, ") -> {ret}")
+            }
+        }
+    }
+}
 /// Pattern for pcode expressions, including parameters and body.
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct Pat {
     pub params: Vec<String>,
     pub body: Box<PExpr>,
 }
+impl Pat {
+    /// Parses a pattern of the form `|a, b| body`, binding the call results to `a` and `b`.
+    ///
+    /// Returns a tuple of the remaining input and the parsed `Pat`.
+    pub fn parse(a: &str) -> IResult<&str, Pat, PitError> {
+        let (a, _) = multispace0(a)?;
+        let (a, params) = delimited(
+            (char('|'), multispace0),
+            separated_list0((multispace0, char(','), multispace0), ident_typed),
+            (multispace0, char('|')),
+        )
+        .parse(a)?;
+        let (a, body) = PExpr::parse(a)?;
+        Ok((
+            a,
+            Pat {
+                params: params.into_iter().map(|p| p.to_owned()).collect(),
+                body: Box::new(body),
+            },
+        ))
+    }
+}
+impl Pat {
+    /// Returns the `Var` names the body references other than the pattern's own parameters.
+    ///
+    /// A pattern is closed when this is empty.
+    pub fn free_vars(&self) -> BTreeSet<String> {
+        let mut out = BTreeSet::new();
+        self.collect_free(&mut Vec::new(), &mut out);
+        out
+    }
+    /// Substitutes into the body, first renaming parameters that would capture `fv`.
+    fn subst(&self, bindings: &BTreeMap<usize, PExpr>, fv: &BTreeSet<String>) -> Pat {
+        let mut params = self.params.clone();
+        let mut body = (*self.body).clone();
+        if params.iter().any(|p| fv.contains(p)) {
+            let mut taken = fv.clone();
+            taken.extend(params.iter().cloned());
+            body.names(&mut taken);
+            for p in params.iter_mut().filter(|p| fv.contains(*p)) {
+                let fresh = (1..)
+                    .map(|i| format!("{p}_{i}"))
+                    .find(|n| !taken.contains(n))
+                    .unwrap();
+                body = body.rename(p, &fresh);
+                taken.insert(fresh.clone());
+                *p = fresh;
+            }
+        }
+        Pat {
+            params,
+            body: Box::new(body.subst(bindings, fv)),
+        }
+    }
+    fn collect_free<'a>(&'a self, bound: &mut Vec<&'a str>, out: &mut BTreeSet<String>) {
+        let n = bound.len();
+        bound.extend(self.params.iter().map(|p| p.as_str()));
+        self.body.collect_free(bound, out);
+        bound.truncate(n);
+    }
+}
+/// Display implementation for Pat, formats as `|a, b| body`.
+impl Display for Pat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "|{}| {}", self.params.join(", "), self.body)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::ToString, vec};
+
+    #[test]
+    fn test_parse_literals() {
+        let lit = |s: &str| {
+            let (rest, e) = PExpr::parse(s).unwrap();
+            assert_eq!(rest, "");
+            e
+        };
+        assert_eq!(lit("123i32"), PExpr::LitI32(123));
+        assert_eq!(lit("-1i32"), PExpr::LitI32(u32::MAX));
+        assert_eq!(lit("4294967295i32"), PExpr::LitI32(u32::MAX));
+        assert_eq!(lit("-2i64"), PExpr::LitI64(-2i64 as u64));
+        assert_eq!(lit("4.5f32"), PExpr::LitF32(4.5f32.to_bits()));
+        assert_eq!(lit("-0.25f64"), PExpr::LitF64((-0.25f64).to_bits()));
+        assert_eq!(lit("7f64"), PExpr::LitF64(7f64.to_bits()));
+        assert_eq!(lit("$3"), PExpr::Param(3));
+        assert_eq!(lit("x_1"), PExpr::Var("x_1".to_owned()));
+        assert!(PExpr::parse("4294967296i32").is_err());
+        assert!(PExpr::parse("1.5i32").is_err());
+        assert!(PExpr::parse("12").is_err());
+    }
+
+    #[test]
+    fn test_parse_nested_call() {
+        let a = [0x11u8; 32];
+        let b = [0x22u8; 32];
+        let src = format!(
+            "{}.get($0; 1i32, {}.len(x) -> |n| n) -> |lo, hi| hi",
+            hex::encode(a),
+            hex::encode(b)
+        );
+        let (rest, e) = PExpr::parse(&src).unwrap();
+        assert_eq!(rest, "");
+        let inner = PExpr::Call {
+            rid: b,
+            method: "len".to_owned(),
+            obj: Box::new(PExpr::Var("x".to_owned())),
+            args: vec![],
+            ret: Pat {
+                params: vec!["n".to_owned()],
+                body: Box::new(PExpr::Var("n".to_owned())),
+            },
+        };
+        assert_eq!(
+            e,
+            PExpr::Call {
+                rid: a,
+                method: "get".to_owned(),
+                obj: Box::new(PExpr::Param(0)),
+                args: vec![PExpr::LitI32(1), inner],
+                ret: Pat {
+                    params: vec!["lo".to_owned(), "hi".to_owned()],
+                    body: Box::new(PExpr::Var("hi".to_owned())),
+                },
+            }
+        );
+        let bad = format!("{}.get($0 1i32) -> || $0", hex::encode(a));
+        assert!(matches!(PExpr::parse(&bad), Err(nom::Err::Failure(_))));
+    }
+
+    #[test]
+    fn test_display() {
+        let rid = [0xabu8; 32];
+        let e = PExpr::Call {
+            rid,
+            method: "put".to_owned(),
+            obj: Box::new(PExpr::Param(0)),
+            args: vec![
+                PExpr::LitI32(-1i32 as u32),
+                PExpr::LitI64(42),
+                PExpr::LitF32(1.5f32.to_bits()),
+                PExpr::LitF64((-0.0f64).to_bits()),
+                PExpr::LitF32(0x7fc0_0001),
+                PExpr::LitF64(f64::INFINITY.to_bits()),
+            ],
+            ret: Pat {
+                params: vec!["a".to_owned(), "b".to_owned()],
+                body: Box::new(PExpr::Var("b".to_owned())),
+            },
+        };
+        let text = e.to_string();
+        assert_eq!(
+            text,
+            format!(
+                "{}.put($0; 4294967295i32, 42i64, 1.5f32, -0f64, 0x7fc00001_f32, 0x7ff0000000000000_f64) -> |a, b| b",
+                hex::encode(rid)
+            )
+        );
+        assert_eq!(PExpr::parse(&text).unwrap(), ("", e));
+
+        let empty = Pat {
+            params: vec![],
+            body: Box::new(PExpr::LitF64(0.1f64.to_bits())),
+        };
+        assert_eq!(empty.to_string(), "|| 0.1f64");
+        assert_eq!(Pat::parse(&empty.to_string()).unwrap(), ("", empty));
+    }
+
+    #[test]
+    fn test_eval() {
+        let rid = [0x11u8; 32];
+        let src = format!(
+            "{r}.open($0; 7i32) -> |h| {r}.read(h; {r}.len(h) -> |n| n) -> |d| d",
+            r = hex::encode(rid)
+        );
+        let e = PExpr::parse(&src).unwrap().1;
+        let mut calls = vec![];
+        let mut resolver = |r: &[u8; 32], m: &str, obj: Value, args: &[Value]| {
+            assert_eq!(r, &rid);
+            calls.push((m.to_owned(), obj, args.to_vec()));
+            match m {
+                "open" => Value::Resource(5),
+                "len" => Value::I64(3),
+                _ => Value::F64(2.5f64.to_bits()),
+            }
+        };
+        assert_eq!(
+            e.eval(&[Value::Resource(1)], &mut resolver),
+            Ok(Value::F64(2.5f64.to_bits()))
+        );
+        assert_eq!(
+            calls,
+            [
+                ("open".to_owned(), Value::Resource(1), vec![Value::I32(7)]),
+                ("len".to_owned(), Value::Resource(5), vec![]),
+                ("read".to_owned(), Value::Resource(5), vec![Value::I64(3)]),
+            ]
+        );
+
+        let mut stub = |_: &[u8; 32], _: &str, _: Value, _: &[Value]| Value::I32(0);
+        assert_eq!(PExpr::LitI64(9).eval(&[], &mut stub), Ok(Value::I64(9)));
+        assert_eq!(
+            PExpr::Param(2).eval(&[Value::I32(0)], &mut stub),
+            Err(EvalError::ParamOutOfRange { index: 2, len: 1 })
+        );
+        let unbound = format!("{}.get($0) -> || h", hex::encode(rid));
+        assert_eq!(
+            PExpr::parse(&unbound).unwrap().1.eval(&[Value::I32(0)], &mut stub),
+            Err(EvalError::UnboundVar("h".to_owned()))
+        );
+        let pair = format!("{}.get($0) -> |a, b| a", hex::encode(rid));
+        assert_eq!(
+            PExpr::parse(&pair).unwrap().1.eval(&[Value::I32(0)], &mut stub),
+            Err(EvalError::PatternArity(2))
+        );
+    }
+
+    #[test]
+    fn test_free_vars() {
+        let r = hex::encode([0x33u8; 32]);
+        let src = format!("{r}.f(a; $2, {r}.g(b) -> |a| {r}.h(a; c, $0) -> |c| c) -> |b| x");
+        let e = PExpr::parse(&src).unwrap().1;
+        // `a` in `h(a; ...)` is shadowed by `|a|`, `c` in the last body by `|c|`; the outer
+        // `a`, `b` and the `c` argument of `h` are free, as is `x` (its `|b|` binds only `b`).
+        let expected: BTreeSet<String> =
+            ["a", "b", "c", "x"].into_iter().map(|v| v.to_owned()).collect();
+        assert_eq!(e.free_vars(), expected);
+        assert_eq!(e.max_param(), Some(2));
+
+        let PExpr::Call { ret, .. } = &e else { unreachable!() };
+        assert_eq!(ret.free_vars(), BTreeSet::from(["x".to_owned()]));
+        let closed = Pat::parse("|y| y").unwrap().1;
+        assert!(closed.free_vars().is_empty());
+        assert_eq!(PExpr::LitI32(0).max_param(), None);
+        assert_eq!(PExpr::parse("z").unwrap().1.max_param(), None);
+    }
+
+    #[test]
+    fn test_substitute() {
+        let r = hex::encode([0x44u8; 32]);
+        let parse = |s: String| PExpr::parse(&s).unwrap().1;
+        let template = parse(format!("{r}.f($0; $1) -> |x| {r}.g(x; $1, y) -> |y| y"));
+
+        // Partial: only `$0` is bound; `$1` stays.
+        let partial = template.substitute(&BTreeMap::from([(0, PExpr::LitI32(5))]));
+        assert_eq!(
+            partial,
+            parse(format!("{r}.f(5i32; $1) -> |x| {r}.g(x; $1, y) -> |y| y"))
+        );
+
+        // `$1 := x` must not be captured by `|x|`, which is renamed instead.
+        let bound = template.substitute(&BTreeMap::from([(1, PExpr::Var("x".to_owned()))]));
+        assert_eq!(
+            bound,
+            parse(format!("{r}.f($0; x) -> |x_1| {r}.g(x_1; x, y) -> |y| y"))
+        );
+        let mut fv = template.free_vars();
+        fv.insert("x".to_owned());
+        assert_eq!(bound.free_vars(), fv);
+
+        // The fresh name skips `x_1`, which the body already uses.
+        let taken = parse(format!("{r}.f($0) -> |x| {r}.g(x_1; $1) -> |z| x"));
+        let bound = taken.substitute(&BTreeMap::from([(1, PExpr::Var("x".to_owned()))]));
+        assert_eq!(
+            bound,
+            parse(format!("{r}.f($0) -> |x_2| {r}.g(x_1; x) -> |z| x_2"))
+        );
+    }
+
+    #[test]
+    fn test_typecheck() {
+        use crate::{ResTy, parse_interface};
+        let file = parse_interface("{open(I32) -> (Rthis);read(I64,F32) -> (F64)}").unwrap().1;
+        let rid = file.rid();
+        let registry = BTreeMap::from([(rid, file)]);
+        let r = hex::encode(rid);
+        let res = ArgKind::ExternRef(ResTy::Of(rid));
+        let check = |src: String| {
+            PExpr::parse(&src)
+                .unwrap()
+                .1
+                .typecheck(&registry, &[res.clone(), ArgKind::I64])
+        };
+
+        assert_eq!(
+            check(format!("{r}.open($0; 3i32) -> |h| {r}.read(h; $1, 1.5f32) -> |x| x")),
+            Ok(ArgKind::F64)
+        );
+        assert_eq!(
+            check(format!("{r}.read($0; $1) -> |x| x")),
+            Err(PcodeTypeError::ArgCount {
+                method: "read".to_owned(),
+                expected: 2,
+                found: 1,
+            })
+        );
+        assert_eq!(
+            check(format!("{r}.write($0; 1i32) -> || 0i32")),
+            Err(PcodeTypeError::UnknownMethod {
+                rid,
+                method: "write".to_owned(),
+            })
+        );
+        assert_eq!(
+            check(format!("{r}.open($0; $1) -> || 0i32")),
+            Err(PcodeTypeError::ArgMismatch {
+                method: "open".to_owned(),
+                index: 0,
+                expected: ArgKind::I32,
+                found: ArgKind::I64,
+            })
+        );
+        assert_eq!(
+            check(format!("{r}.open($1; 1i32) -> || 0i32")),
+            Err(PcodeTypeError::ObjNotResource {
+                method: "open".to_owned(),
+                found: ArgKind::I64,
+            })
+        );
+        assert_eq!(
+            check(format!("{}.open($0; 1i32) -> || 0i32", hex::encode([0u8; 32]))),
+            Err(PcodeTypeError::UnknownInterface([0; 32]))
+        );
+        assert_eq!(
+            check(format!("{r}.open($0; 1i32) -> |a, b| a")),
+            Err(PcodeTypeError::PatternArity {
+                method: "open".to_owned(),
+                rets: 1,
+            })
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use alloc::collections::BTreeMap;
+
+use crate::Interface;
+
+/// A collection of interfaces keyed by their resource ID.
+#[derive(Default, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub struct Registry {
+    pub interfaces: BTreeMap<[u8; 32], Interface>,
+}
+
+impl Registry {
+    /// Adds an interface to the registry, returning its resource ID.
+    pub fn insert(&mut self, iface: Interface) -> [u8; 32] {
+        let rid = iface.rid();
+        self.interfaces.insert(rid, iface);
+        rid
+    }
+
+    /// Returns the interface with the given resource ID, if registered.
+    pub fn get(&self, rid: &[u8; 32]) -> Option<&Interface> {
+        self.interfaces.get(rid)
+    }
+}
+
+/// Collects interfaces into a registry, keying each by its resource ID.
+impl FromIterator<Interface> for Registry {
+    fn from_iter<T: IntoIterator<Item = Interface>>(iter: T) -> Self {
+        let mut r = Registry::default();
+        for i in iter {
+            r.insert(i);
+        }
+        r
+    }
+}
@@ -1,7 +1,13 @@
 pub use core;
 use core::fmt::Write;
 
+#[cfg(feature = "testing")]
+use alloc::string::ToString;
+use alloc::{string::String, vec::Vec};
+use nom::IResult;
 use sha3::digest::Update;
+
+use crate::PitError;
 /// Wrapper for types implementing `Update`, allowing use with `core::fmt::Write`.
 pub struct WriteUpdate<'a, 'b> {
     pub wrapped: &'a mut (dyn Update + 'b),
@@ -13,3 +19,193 @@ impl<'a,'b> Write for WriteUpdate<'a,'b>{
         Ok(())
     }
 }
+/// Serde helpers that encode 32-byte resource ids as lowercase hex strings.
+#[cfg(feature = "serde")]
+pub(crate) mod hex_id {
+    use alloc::{collections::BTreeMap, string::String};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    fn decode<'de, D: Deserializer<'de>>(s: &str) -> Result<[u8; 32], D::Error> {
+        let mut id = [0u8; 32];
+        hex::decode_to_slice(s, &mut id).map_err(D::Error::custom)?;
+        Ok(id)
+    }
+
+    pub fn serialize<S: Serializer>(id: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(id))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 32], D::Error> {
+        decode::<D>(&String::deserialize(d)?)
+    }
+
+    /// The same encoding applied to the keys of a map.
+    pub mod map {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer, V: Serialize>(
+            m: &BTreeMap<[u8; 32], V>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            s.collect_map(m.iter().map(|(k, v)| (hex::encode(k), v)))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+            d: D,
+        ) -> Result<BTreeMap<[u8; 32], V>, D::Error> {
+            BTreeMap::<String, V>::deserialize(d)?
+                .into_iter()
+                .map(|(k, v)| Ok((decode::<D>(&k)?, v)))
+                .collect()
+        }
+    }
+}
+/// Shrinks `input` to a small substring that still fails `parse` with the same error.
+///
+/// Chunks of characters are removed (delta debugging) as long as the parse keeps failing
+/// with an error of the same kind, ignoring where it occurred. Returns `input` unchanged
+/// if it parses.
+pub fn minimize_parse_failure<T>(
+    input: &str,
+    parse: impl Fn(&str) -> IResult<&str, T, PitError>,
+) -> String {
+    let kind = |e: PitError| match e {
+        PitError::ExpectedToken { expected, .. } => PitError::ExpectedToken {
+            expected,
+            remaining: 0,
+        },
+        PitError::ExpectedChar { expected, .. } => PitError::ExpectedChar {
+            expected,
+            remaining: 0,
+        },
+        PitError::BadHexLength { expected, .. } => PitError::BadHexLength {
+            expected,
+            remaining: 0,
+        },
+        PitError::UnknownArgType { .. } => PitError::UnknownArgType { remaining: 0 },
+        PitError::TrailingInput { .. } => PitError::TrailingInput { remaining: 0 },
+        PitError::InvalidIndex { .. } => PitError::InvalidIndex {
+            len: 0,
+            remaining: 0,
+        },
+        PitError::ZeroResourceId { .. } => PitError::ZeroResourceId { remaining: 0 },
+        PitError::TooDeep { .. } => PitError::TooDeep { remaining: 0 },
+        PitError::Nom { kind, .. } => PitError::Nom { kind, remaining: 0 },
+    };
+    let error = |s: &str| parse(s).err().map(|e| kind(PitError::from_nom(e)));
+    let Some(target) = error(input) else {
+        return input.into();
+    };
+    let mut chars: Vec<char> = input.chars().collect();
+    let mut chunk = chars.len().div_ceil(2);
+    while chunk > 0 {
+        let mut removed = false;
+        let mut start = 0;
+        while start < chars.len() {
+            let end = (start + chunk).min(chars.len());
+            let candidate: String = chars[..start].iter().chain(&chars[end..]).collect();
+            if error(&candidate) == Some(target) {
+                chars.drain(start..end);
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !removed {
+            chunk /= 2;
+        }
+    }
+    chars.into_iter().collect()
+}
+/// Checks that `x` survives a render, parse, render cycle, returning where it does not.
+///
+/// On a mismatch, returns the differing region of the first and second renderings: the
+/// text after their common prefix and before their common suffix. If the first rendering
+/// does not parse, the second element is empty and the first holds the text from the
+/// error position on.
+#[cfg(feature = "testing")]
+pub fn roundtrip_mismatch<T: core::fmt::Display>(
+    x: &T,
+    parse: impl Fn(&str) -> Result<T, PitError>,
+) -> Option<(String, String)> {
+    let first = x.to_string();
+    let second = match parse(&first) {
+        Ok(y) => y.to_string(),
+        Err(e) => return Some((first[e.offset_in(&first)..].into(), String::new())),
+    };
+    if first == second {
+        return None;
+    }
+    let prefix = first
+        .char_indices()
+        .zip(second.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(first.len().min(second.len()), |((i, _), _)| i);
+    let (a, b) = (&first[prefix..], &second[prefix..]);
+    let suffix = a
+        .chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    Some((
+        a[..a.len() - suffix].into(),
+        b[..b.len() - suffix].into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn test_minimize_parse_failure() {
+        let mut src = String::from("[doc=big interface]{");
+        for i in 0..20 {
+            src += &format!("m{i}(I32, Rthis&) -> (I64);");
+        }
+        src += "bad(I32, Q) -> ()}";
+        // The list of methods backtracks over `bad`, so this reports the missing `}`.
+        let min = minimize_parse_failure(&src, crate::parse_interface_typed);
+        assert!(min.len() <= 3, "{min:?}");
+        let err = PitError::from_nom(crate::parse_interface_typed(&min).unwrap_err());
+        assert!(matches!(err, PitError::ExpectedChar { expected: '}', .. }));
+        assert_eq!(minimize_parse_failure("{}", crate::parse_interface_typed), "{}");
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_roundtrip_mismatch() {
+        use crate::{Attr, Interface, parse_attr, parse_attr_typed};
+        use core::fmt::{self, Display, Formatter};
+
+        let iface: Interface = r"[doc=a\]b][escFmtVer=0]{f(I32) -> ()}".parse().unwrap();
+        assert_eq!(roundtrip_mismatch(&iface, |s| s.parse()), None);
+
+        // Renders attribute values without escaping brackets.
+        struct Unescaped(Attr);
+        impl Display for Unescaped {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                write!(f, "[{}={}]", self.0.name, self.0.value)
+            }
+        }
+        let parse = |s: &str| {
+            parse_attr_typed(s)
+                .map(|(_, a)| Unescaped(a))
+                .map_err(PitError::from_nom)
+        };
+        let balanced = Unescaped(parse_attr("[doc=a [b] c]").unwrap().1);
+        assert_eq!(roundtrip_mismatch(&balanced, parse), None);
+        let broken = Unescaped(Attr {
+            name: "doc".to_string(),
+            value: "a]b".to_string(),
+        });
+        assert_eq!(
+            roundtrip_mismatch(&broken, parse),
+            Some(("b]".to_string(), String::new()))
+        );
+    }
+}
@@ -0,0 +1,556 @@
+use alloc::{
+    borrow::ToOwned,
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::fmt::{Display, Formatter, Write};
+
+use crate::{Arg, ArgTy, Attr, Interface, ResTy, Sig};
+
+/// Converts an identifier to a WIT label.
+///
+/// Words are split at `_`, `.`, `$`, `-` and lower-to-upper case changes, lowercased and
+/// joined with `-`. A word starting with a digit is glued to the previous one, since WIT
+/// words must start with a letter. The label is `%`-escaped so keywords stay usable.
+fn label(s: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut cur = String::new();
+    let mut prev_lower = false;
+    for c in s.chars() {
+        if !c.is_ascii_alphanumeric() {
+            if !cur.is_empty() {
+                words.push(core::mem::take(&mut cur));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower {
+            words.push(core::mem::take(&mut cur));
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        cur.push(c.to_ascii_lowercase());
+    }
+    if !cur.is_empty() {
+        words.push(cur);
+    }
+    let mut out = String::from("%");
+    for w in words {
+        if out.len() == 1 {
+            if w.starts_with(|c: char| c.is_ascii_digit()) {
+                out.push('x');
+            }
+        } else if !w.starts_with(|c: char| c.is_ascii_digit()) {
+            out.push('-');
+        }
+        out.push_str(&w);
+    }
+    if out.len() == 1 {
+        out.push('x');
+    }
+    out
+}
+
+fn name_attr(ann: &[Attr]) -> Option<&str> {
+    ann.iter()
+        .find(|a| a.name == "name")
+        .map(|a| a.value.as_str())
+}
+
+/// Name of the WIT resource standing for `ty`; `this` is named after the interface.
+fn resource(ty: &ResTy, this: &str) -> String {
+    match ty {
+        ResTy::None => "%any".to_owned(),
+        ResTy::Of(rid) => alloc::format!("%r{}", hex::encode(rid)),
+        ResTy::This => label(this),
+        ResTy::Named(n) => label(n),
+    }
+}
+
+/// Records that the item `pit` is written as the WIT label `wit`, failing if a different
+/// item already took that label.
+fn claim(seen: &mut BTreeMap<String, String>, wit: &str, pit: String) -> Result<(), WitError> {
+    let wit = wit.trim_start_matches('%');
+    match seen.get(wit) {
+        Some(first) if *first != pit => Err(WitError::LabelCollision {
+            label: wit.to_owned(),
+            first: first.clone(),
+            second: pit,
+        }),
+        _ => {
+            seen.insert(wit.to_owned(), pit);
+            Ok(())
+        }
+    }
+}
+
+fn has_func(s: &Sig) -> bool {
+    s.params
+        .iter()
+        .chain(s.rets.iter())
+        .any(|a| matches!(a.ty, ArgTy::Func(_)))
+}
+
+/// Writes the WIT type of `a`. Results cannot borrow, so a borrowed resource in result
+/// position (`ret`) is written as owned.
+fn ty(out: &mut String, a: &Arg, this: &str, ret: bool) {
+    match &a.ty {
+        ArgTy::I32 => out.push_str("s32"),
+        ArgTy::I64 => out.push_str("s64"),
+        ArgTy::F32 => out.push_str("float32"),
+        ArgTy::F64 => out.push_str("float64"),
+        ArgTy::V128 => out.push_str("tuple<u64, u64>"),
+        ArgTy::I8 => out.push_str("s8"),
+        ArgTy::I16 => out.push_str("s16"),
+        ArgTy::U8 => out.push_str("u8"),
+        ArgTy::U16 => out.push_str("u16"),
+        ArgTy::Resource { ty, nullable, take } => {
+            let r = resource(ty, this);
+            let r = if *take || ret {
+                alloc::format!("own<{r}>")
+            } else {
+                alloc::format!("borrow<{r}>")
+            };
+            if *nullable {
+                let _ = write!(out, "option<{r}>");
+            } else {
+                out.push_str(&r);
+            }
+        }
+        // Filtered out by `has_func` before rendering.
+        ArgTy::Func(_) => unreachable!(),
+    }
+}
+
+impl Interface {
+    /// Renders the interface as a WIT `interface name { ... }` block.
+    ///
+    /// `I8`, `I16`, `U8`, `U16`, `I32` and `I64` map to `s8`, `s16`, `u8`, `u16`, `s32` and
+    /// `s64`, floats to `float32`/`float64` and `V128` to `tuple<u64, u64>`. A resource
+    /// argument becomes `own<R>` when taken, `borrow<R>` otherwise, wrapped in `option`
+    /// when nullable; each resource type used is declared first, with `this` named after
+    /// the interface and ids as `r` plus their hex. Method and parameter names come from a
+    /// `name` annotation when present, and are converted to kebab case. Several returns
+    /// become a `tuple`.
+    ///
+    /// WIT has no function types, so a method taking or returning a function is emitted
+    /// as a `//` comment instead. WIT results cannot borrow, so a borrowed resource return
+    /// is written as `own<R>`.
+    ///
+    /// Fails with [`WitError::LabelCollision`] if two distinct resources or methods, or two
+    /// parameters of one method, convert to the same label (e.g. `getX` and `get_x`).
+    pub fn to_wit(&self, name: &str) -> Result<String, WitError> {
+        // Resources and functions share the interface's namespace.
+        let mut labels = BTreeMap::new();
+        let mut resources = BTreeSet::new();
+        for s in self.methods.values().filter(|s| !has_func(s)) {
+            for a in s.params.iter().chain(s.rets.iter()) {
+                if let ArgTy::Resource { ty, .. } = &a.ty {
+                    let r = resource(ty, name);
+                    claim(&mut labels, &r, alloc::format!("resource `R{ty}`"))?;
+                    resources.insert(r);
+                }
+            }
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(out, "interface {} {{", label(name));
+        for r in resources.iter() {
+            let _ = writeln!(out, "  resource {r};");
+        }
+        for (method, s) in self.methods.iter() {
+            let wit = label(name_attr(&s.ann).unwrap_or(method));
+            claim(&mut labels, &wit, alloc::format!("method `{method}`"))?;
+            let method = wit;
+            if has_func(s) {
+                let _ = writeln!(out, "  // {}: unsupported function argument", &method[1..]);
+                continue;
+            }
+            let _ = write!(out, "  {method}: func(");
+            let mut params = BTreeMap::new();
+            for (i, a) in s.params.iter().enumerate() {
+                if i != 0 {
+                    out.push_str(", ");
+                }
+                let wit = match name_attr(&a.ann) {
+                    Some(n) => label(n),
+                    None => alloc::format!("p{i}"),
+                };
+                claim(&mut params, &wit, alloc::format!("param {i} of `{method}`"))?;
+                out.push_str(&wit);
+                out.push_str(": ");
+                ty(&mut out, a, name, false);
+            }
+            out.push(')');
+            match &s.rets[..] {
+                [] => {}
+                [r] => {
+                    out.push_str(" -> ");
+                    ty(&mut out, r, name, true);
+                }
+                rets => {
+                    out.push_str(" -> tuple<");
+                    for (i, r) in rets.iter().enumerate() {
+                        if i != 0 {
+                            out.push_str(", ");
+                        }
+                        ty(&mut out, r, name, true);
+                    }
+                    out.push('>');
+                }
+            }
+            out.push_str(";\n");
+        }
+        out.push_str("}\n");
+        Ok(out)
+    }
+}
+
+/// Error returned by [`Interface::from_wit`].
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[non_exhaustive]
+pub enum WitError {
+    /// Something else was found where `expected` was required, at byte `offset`.
+    Expected {
+        expected: &'static str,
+        offset: usize,
+    },
+    /// A WIT item other than a plain `resource` declaration or function.
+    UnsupportedItem(String),
+    /// A WIT type with no counterpart in the interface model.
+    UnsupportedType(String),
+    /// A resource used without being declared in the interface block.
+    UnknownResource(String),
+    /// Two distinct items would be written with the same WIT label.
+    LabelCollision {
+        label: String,
+        first: String,
+        second: String,
+    },
+}
+impl Display for WitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            WitError::Expected { expected, offset } => {
+                write!(f, "expected {expected} at offset {offset}")
+            }
+            WitError::UnsupportedItem(s) => write!(f, "unsupported WIT item `{s}`"),
+            WitError::UnsupportedType(s) => write!(f, "unsupported WIT type `{s}`"),
+            WitError::UnknownResource(s) => write!(f, "undeclared resource `{s}`"),
+            WitError::LabelCollision {
+                label,
+                first,
+                second,
+            } => write!(f, "{first} and {second} are both written as `{label}`"),
+        }
+    }
+}
+impl core::error::Error for WitError {}
+
+/// Cursor over WIT source that skips whitespace and `//` comments between tokens.
+struct Wit<'a> {
+    src: &'a str,
+    pos: usize,
+}
+impl<'a> Wit<'a> {
+    fn skip(&mut self) {
+        loop {
+            let rest = &self.src[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+    fn eat(&mut self, t: &str) -> bool {
+        self.skip();
+        if self.src[self.pos..].starts_with(t) {
+            self.pos += t.len();
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, t: &'static str) -> Result<(), WitError> {
+        if self.eat(t) {
+            Ok(())
+        } else {
+            Err(WitError::Expected {
+                expected: t,
+                offset: self.pos,
+            })
+        }
+    }
+    /// Reads a label, without its `%` escape.
+    fn label(&mut self) -> Result<&'a str, WitError> {
+        self.skip();
+        self.eat("%");
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(WitError::Expected {
+                expected: "identifier",
+                offset: self.pos,
+            });
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+    fn ty(&mut self, resources: &BTreeMap<&str, ResTy>) -> Result<ArgTy, WitError> {
+        let name = self.label()?;
+        let resource = |r: &str| {
+            resources
+                .get(r)
+                .cloned()
+                .ok_or_else(|| WitError::UnknownResource(r.to_owned()))
+        };
+        Ok(match name {
+            "s32" => ArgTy::I32,
+            "s64" => ArgTy::I64,
+            "s8" => ArgTy::I8,
+            "s16" => ArgTy::I16,
+            "u8" => ArgTy::U8,
+            "u16" => ArgTy::U16,
+            "float32" | "f32" => ArgTy::F32,
+            "float64" | "f64" => ArgTy::F64,
+            "own" | "borrow" => {
+                self.expect("<")?;
+                let ty = resource(self.label()?)?;
+                self.expect(">")?;
+                ArgTy::Resource {
+                    ty,
+                    nullable: false,
+                    take: name == "own",
+                }
+            }
+            "option" => {
+                self.expect("<")?;
+                let inner = self.ty(resources)?;
+                self.expect(">")?;
+                match inner {
+                    ArgTy::Resource {
+                        ty,
+                        nullable: false,
+                        take,
+                    } => ArgTy::Resource {
+                        ty,
+                        nullable: true,
+                        take,
+                    },
+                    _ => return Err(WitError::UnsupportedType("option".to_owned())),
+                }
+            }
+            r if resources.contains_key(r) => ArgTy::Resource {
+                ty: resource(r)?,
+                nullable: false,
+                take: true,
+            },
+            t => return Err(WitError::UnsupportedType(t.to_owned())),
+        })
+    }
+}
+
+impl Interface {
+    /// Parses a WIT `interface name { ... }` block, the inverse of [`Interface::to_wit`]
+    /// for primitives and resources.
+    ///
+    /// The block may contain `resource R;` declarations and functions whose params and
+    /// results are `s8`, `s16`, `u8`, `u16`, `s32`, `s64`, `float32`, `float64`,
+    /// `own<R>`, `borrow<R>`, a bare `R` (owned) and `option` of a resource. A resource
+    /// named like the interface becomes `this`, `any` an untyped resource, `r` followed by
+    /// 64 hex digits a [`ResTy::Of`] that id, and any other one a [`ResTy::Named`]. Method
+    /// names have `-` replaced by `_`; a param not named `p<index>` keeps its name as a
+    /// `name` annotation. Anything else is an error.
+    pub fn from_wit(src: &str) -> Result<Interface, WitError> {
+        let mut w = Wit { src, pos: 0 };
+        w.expect("interface")?;
+        let this = w.label()?;
+        w.expect("{")?;
+        let mut resources = BTreeMap::new();
+        let mut iface = Interface::default();
+        while !w.eat("}") {
+            let name = w.label()?;
+            if name == "resource" {
+                let r = w.label()?;
+                let mut rid = [0u8; 32];
+                let ty = if r == this {
+                    ResTy::This
+                } else if r == "any" {
+                    ResTy::None
+                } else if let Some(h) = r.strip_prefix('r')
+                    && h.len() == 64
+                    && hex::decode_to_slice(h, &mut rid).is_ok()
+                {
+                    ResTy::Of(rid)
+                } else {
+                    ResTy::Named(r.replace('-', "_"))
+                };
+                resources.insert(r, ty);
+                w.expect(";")?;
+                continue;
+            }
+            if !w.eat(":") {
+                return Err(WitError::UnsupportedItem(name.to_owned()));
+            }
+            if !w.eat("func") {
+                return Err(WitError::UnsupportedItem(w.label().unwrap_or(name).to_owned()));
+            }
+            w.expect("(")?;
+            let mut sig = Sig::default();
+            let mut first = true;
+            while !w.eat(")") {
+                if !first {
+                    w.expect(",")?;
+                }
+                first = false;
+                let pname = w.label()?;
+                w.expect(":")?;
+                let ty = w.ty(&resources)?;
+                let ann = match pname.strip_prefix('p') {
+                    Some(i) if i.parse() == Ok(sig.params.len()) => vec![],
+                    _ => vec![Attr {
+                        name: "name".to_owned(),
+                        value: pname.to_owned(),
+                    }],
+                };
+                sig.params.push(Arg { ty, ann });
+            }
+            if w.eat("->") {
+                w.skip();
+                if w.src[w.pos..].starts_with("tuple") {
+                    w.label()?;
+                    w.expect("<")?;
+                    loop {
+                        sig.rets.push(Arg {
+                            ty: w.ty(&resources)?,
+                            ann: vec![],
+                        });
+                        if w.eat(">") {
+                            break;
+                        }
+                        w.expect(",")?;
+                    }
+                } else {
+                    sig.rets.push(Arg {
+                        ty: w.ty(&resources)?,
+                        ann: vec![],
+                    });
+                }
+            }
+            w.expect(";")?;
+            iface.methods.insert(name.replace('-', "_"), sig);
+        }
+        w.skip();
+        if w.pos != src.len() {
+            return Err(WitError::Expected {
+                expected: "end of input",
+                offset: w.pos,
+            });
+        }
+        Ok(iface)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_interface;
+
+    #[test]
+    fn test_to_wit() {
+        let rid = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let src = alloc::format!(
+            "{{add[name=addNumbers]([name=lhs]I32,I64) -> (F64);get_obj() -> (R{rid}n);poke(Rthis&,V128) -> ();swap(F32) -> (I32,Rthis);each(^() -> ()) -> ()}}"
+        );
+        let (_, iface) = parse_interface(&src).unwrap();
+        assert_eq!(
+            iface.to_wit("myCounter").unwrap(),
+            "interface %my-counter {\n\
+             \x20 resource %my-counter;\n\
+             \x20 resource %r0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef;\n\
+             \x20 %add-numbers: func(%lhs: s32, p1: s64) -> float64;\n\
+             \x20 // each: unsupported function argument\n\
+             \x20 %get-obj: func() -> option<own<%r0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef>>;\n\
+             \x20 %poke: func(p0: borrow<%my-counter>, p1: tuple<u64, u64>);\n\
+             \x20 %swap: func(p0: float32) -> tuple<s32, own<%my-counter>>;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_to_wit_borrowed_result() {
+        let (_, iface) = parse_interface("{get(Rthis&) -> (Rthis&,Rthisn&)}").unwrap();
+        let wit = iface.to_wit("cell").unwrap();
+        assert!(wit.contains(
+            "%get: func(p0: borrow<%cell>) -> tuple<own<%cell>, option<own<%cell>>>;"
+        ));
+        assert!(!wit.contains("-> borrow"));
+    }
+
+    #[test]
+    fn test_to_wit_label_collision() {
+        let collides = |src: &str| {
+            let (_, iface) = parse_interface(src).unwrap();
+            match iface.to_wit("store") {
+                Err(WitError::LabelCollision { label, .. }) => label,
+                other => panic!("{src}: {other:?}"),
+            }
+        };
+        assert_eq!(collides("{getX() -> ();get_x() -> ()}"), "get-x");
+        assert_eq!(collides("{f(R@{fs.File}, R@{fs_file}) -> ()}"), "fs-file");
+        assert_eq!(collides("{f(R@{Store}) -> (Rthis)}"), "store");
+        assert_eq!(collides("{item() -> (R@{item})}"), "item");
+        assert_eq!(collides("{f(I32, [name=p0]I64) -> ()}"), "p0");
+        // The same item may be used many times
+        let (_, iface) = parse_interface("{a(R@{x}) -> (R@{x});b(Rthis) -> (Rthis)}").unwrap();
+        assert!(iface.to_wit("store").is_ok());
+    }
+
+    #[test]
+    fn test_label() {
+        assert_eq!(label("getHTTPValue2"), "%get-httpvalue2");
+        assert_eq!(label("set_x.y"), "%set-x-y");
+        assert_eq!(label("v0"), "%v0");
+        assert_eq!(label("_1a"), "%x1a");
+    }
+
+    #[test]
+    fn test_from_wit() {
+        let src = "{add([name=lhs]I32,I64) -> (F64);get_obj() -> (Rthisn);noop() -> ();poke(Rthis&,R@{widget}&) -> (R@{widget});swap(F32) -> (I32,Rthis)}";
+        let (_, iface) = parse_interface(src).unwrap();
+        let wit = iface.to_wit("counter").unwrap();
+        assert_eq!(Interface::from_wit(&wit).unwrap(), iface);
+
+        // Ids round-trip through their `r<hex>` resource names
+        let rid = "0123456789abcdef".repeat(4);
+        let (_, iface) = parse_interface(&alloc::format!("{{f(R{rid}&) -> (R{rid}n)}}")).unwrap();
+        let wit = iface.to_wit("ids").unwrap();
+        assert!(wit.contains(&alloc::format!("resource %r{rid};")));
+        assert_eq!(Interface::from_wit(&wit).unwrap(), iface);
+
+        let iface = Interface::from_wit(
+            "interface store {\n  resource item;\n  // a comment\n  get: func(key: s32) -> item;\n}\n",
+        )
+        .unwrap();
+        assert_eq!(iface, parse_interface("{get([name=key]I32) -> (R@{item})}").unwrap().1);
+
+        assert_eq!(
+            Interface::from_wit("interface x { f: func(s: string); }"),
+            Err(WitError::UnsupportedType("string".to_owned()))
+        );
+        assert_eq!(
+            Interface::from_wit("interface x { f: func() -> own<y>; }"),
+            Err(WitError::UnknownResource("y".to_owned()))
+        );
+        assert_eq!(
+            Interface::from_wit("interface x { record r { a: s32 } }"),
+            Err(WitError::UnsupportedItem("record".to_owned()))
+        );
+    }
+}